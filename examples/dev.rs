@@ -12,7 +12,7 @@ async fn main() -> anyhow::Result<()> {
         conf::C.mysql.url
     );
 
-    let env = db::Db::new().await?;
+    let env = db::Db::new(false).await?;
 
     let data = sqlx::query_as::<_, model::ArbDiffRate>("select * from arb_diff_rate")
         .fetch_all(env.database())
@@ -1,43 +1,12 @@
-use chrono::Local;
+use arbitrage::{conf, helper};
 use log::info;
-use std::io::Write;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    use env_logger::fmt::Color;
-    use env_logger::Env;
-    use log::LevelFilter;
+    lazy_static::initialize(&conf::C);
+    helper::log::init_log();
 
-    let env = Env::default().filter_or("MY_LOG_LEVEL", "debug");
-
-    env_logger::Builder::from_env(env)
-        .format(|buf, record| {
-            let level_color = match record.level() {
-                log::Level::Error => Color::Red,
-                log::Level::Warn => Color::Yellow,
-                log::Level::Info => Color::Green,
-                log::Level::Debug | log::Level::Trace => Color::Cyan,
-            };
-
-            let mut level_style = buf.style();
-            level_style.set_color(level_color).set_bold(true);
-
-            let mut style = buf.style();
-            style.set_color(Color::White).set_dimmed(true);
-
-            writeln!(
-                buf,
-                "{} {} [ {} ] {}",
-                Local::now().format("%Y-%m-%d %H:%M:%S"),
-                level_style.value(record.level()),
-                style.value(record.module_path().unwrap_or("<unnamed>")),
-                record.args()
-            )
-        })
-        .filter(None, LevelFilter::Debug)
-        .init();
-
-    info!("xxyyzz");
+    info!(strategy_id = 1, symbol = "BTCUSDT"; "xxyyzz");
 
     Ok(())
 }
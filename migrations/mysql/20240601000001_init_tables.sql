@@ -1,4 +1,6 @@
-create table arbitrage.arb_diff_rate
+-- 初始表结构, 已手动建表的数据库不受影响
+
+create table if not exists arb_diff_rate
 (
     id                  bigint auto_increment comment 'id'
         primary key,
@@ -18,7 +20,7 @@ create table arbitrage.arb_diff_rate
 )
     comment '差价比率配置表' charset = utf8;
 
-create table arbitrage.arb_diff_rate_his
+create table if not exists arb_diff_rate_his
 (
     id           bigint auto_increment comment 'id'
         primary key,
@@ -31,7 +33,7 @@ create table arbitrage.arb_diff_rate_his
 )
     comment '差价比率数据历史记录表' charset = utf8mb4;
 
-create table arbitrage.arb_diff_rate_info
+create table if not exists arb_diff_rate_info
 (
     id            bigint auto_increment comment 'id'
         primary key,
@@ -53,7 +55,7 @@ create table arbitrage.arb_diff_rate_info
 )
     comment '差价数据表' charset = utf8;
 
-create table arbitrage.arb_strategy
+create table if not exists arb_strategy
 (
     id                  bigint auto_increment comment 'id'
         primary key,
@@ -88,7 +90,7 @@ create table arbitrage.arb_strategy
 )
     comment '期现套利策略表' charset = utf8mb4;
 
-create table arbitrage.arb_strategy_ex
+create table if not exists arb_strategy_ex
 (
     id                  bigint auto_increment comment 'id'
         primary key,
@@ -113,7 +115,7 @@ create table arbitrage.arb_strategy_ex
 )
     comment '预生成策略执行表' charset = utf8;
 
-create table arbitrage.arb_strategy_ex_info
+create table if not exists arb_strategy_ex_info
 (
     id                 bigint auto_increment comment 'id'
         primary key,
@@ -140,7 +142,7 @@ create table arbitrage.arb_strategy_ex_info
 )
     comment '策略执行记录表' charset = utf8;
 
-create table arb_stable_coin
+create table if not exists arb_stable_coin
 (
     id             bigint auto_increment comment 'id'
         primary key,
@@ -164,7 +166,7 @@ create table arb_stable_coin
     comment '稳定币对冲策略表' charset = utf8mb4;
 
 
-create table arb_stable_coin_info
+create table if not exists arb_stable_coin_info
 (
     id             bigint auto_increment comment 'id'
         primary key,
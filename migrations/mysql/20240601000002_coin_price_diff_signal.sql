@@ -0,0 +1,34 @@
+create table if not exists arb_coin_price
+(
+    id                    bigint auto_increment comment 'id'
+        primary key,
+    platform              varchar(64)    default ''      not null comment '平台 binance、bybit、kucoin',
+    symbol                varchar(64)                    not null comment '交易对',
+    binance_spot_price    decimal(30, 10) default 0      not null comment 'binance 现货价格',
+    binance_futures_price decimal(30, 10) default 0      not null comment 'binance U本位永续价格',
+    bybit_futures_price   decimal(30, 10) default 0      not null comment 'bybit 永续价格',
+    kucoin_futures_price  decimal(30, 10) default 0      not null comment 'kucoin 永续价格',
+    created               varchar(64)    default ''      not null comment '创建时间',
+    updated               varchar(64)    default ''      not null comment '更新时间',
+    bak                   varchar(255)                   null comment '备注',
+    unique key uk_symbol (symbol)
+)
+    comment '多平台币种价格表' charset = utf8mb4;
+
+create table if not exists arb_diff_signal
+(
+    id                    bigint auto_increment comment 'id'
+        primary key,
+    symbol                varchar(64)                    not null comment '交易对',
+    from_compare          varchar(64)                    not null comment '比较平台 From',
+    to_compare            varchar(64)                    not null comment '比较平台 To',
+    price_diff            decimal(30, 10) default 0      not null comment '差价',
+    price_diff_rate       decimal(30, 10) default 0      not null comment '差价比率',
+    binance_futures_price decimal(30, 10) default 0      not null comment 'binance U本位永续价格',
+    bybit_futures_price   decimal(30, 10) default 0      not null comment 'bybit 永续价格',
+    kucoin_futures_price  decimal(30, 10) default 0      not null comment 'kucoin 永续价格',
+    created               varchar(64)    default ''      not null comment '创建时间',
+    updated               varchar(64)    default ''      not null comment '更新时间',
+    key idx_symbol_compare (symbol, from_compare, to_compare)
+)
+    comment '跨平台差价信号表' charset = utf8mb4;
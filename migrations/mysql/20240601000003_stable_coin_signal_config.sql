@@ -0,0 +1,2 @@
+alter table arb_stable_coin
+    add column signal_config varchar(2048) default '' not null comment '指标及开平仓规则(JSON), 为空时按 strategy 使用预设' after strategy;
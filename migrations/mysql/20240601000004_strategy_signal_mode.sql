@@ -0,0 +1,5 @@
+alter table arb_strategy
+    add column signal_mode varchar(32) default 'threshold' not null comment '开平仓信号 threshold 差价比率阀值, zscore 差价比率 z-score' after option_close,
+    add column lookback    int         default 0           not null comment 'zscore 统计最近的 arb_diff_rate_his 条数' after signal_mode;
+
+create index idx_diff_rate_id on arb_diff_rate_his (diff_rate_id, id);
@@ -0,0 +1,6 @@
+alter table arb_diff_rate_info
+    add column annualized_rate decimal(20, 4) null comment '年化差价比率, 无交割日期时为空' after diff_rate,
+    add column delivery_time   bigint         null comment '交割时间(毫秒), 取 from/to 中较早的交割合约' after annualized_rate;
+
+alter table arb_diff_rate_his
+    add column annualized_rate decimal(20, 4) null comment '年化差价比率, 无交割日期时为空' after diff_rate;
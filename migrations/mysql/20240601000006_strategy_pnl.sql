@@ -0,0 +1,19 @@
+create table if not exists arb_strategy_pnl
+(
+    id              bigint auto_increment comment 'id'
+        primary key,
+    arb_strategy_id bigint                        not null comment 'arb_strategy 表ID',
+    user_id         bigint                        not null comment '用户ID',
+    coin            varchar(64)    default ''     not null comment '币种',
+    realized_usdt   decimal(30, 8) default 0      not null comment '已实现盈亏(USDT), 已扣手续费、含资金费',
+    unrealized_usdt decimal(30, 8) default 0      not null comment '未实现盈亏(USDT), 按当前价格计算未平仓位',
+    realized_coin   decimal(30, 8) default 0      not null comment '已实现盈亏(币)',
+    unrealized_coin decimal(30, 8) default 0      not null comment '未实现盈亏(币)',
+    fee_usdt        decimal(30, 8) default 0      not null comment '手续费(USDT)',
+    funding_usdt    decimal(30, 8) default 0      not null comment '资金费(USDT), 收入为正',
+    coin_price      decimal(30, 8) default 0      not null comment '计算时币种现货价格(USDT)',
+    created         int            default 0      null comment '创建时间',
+    updated         int            default 0      null comment '更新时间',
+    constraint uk_arb_strategy_id unique (arb_strategy_id)
+)
+    comment '期现套利策略盈亏表' charset = utf8mb4;
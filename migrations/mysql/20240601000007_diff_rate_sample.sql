@@ -0,0 +1,10 @@
+create table if not exists arb_diff_rate_sample
+(
+    id           bigint auto_increment comment 'id'
+        primary key,
+    diff_rate_id bigint                        not null comment 'arb_diff_rate 表id',
+    diff_rate    decimal(30, 8) default 0      not null comment '差价比率, 不截断',
+    created      int            default 0      not null comment '采样时间',
+    key idx_diff_rate_id_created (diff_rate_id, created)
+)
+    comment '差价比率逐秒采样表, 供 zscore 信号使用, 只保留最近窗口' charset = utf8mb4;
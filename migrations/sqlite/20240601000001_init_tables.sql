@@ -0,0 +1,165 @@
+-- 与 mysql 相同的表结构, sqlite 没有 decimal 类型, 以 text 存储保证精度
+
+create table if not exists arb_diff_rate
+(
+    id                  integer primary key autoincrement,
+    platform            text    default '' not null,
+    coin                text               not null,
+    option_choose       text               not null,
+    from_market         text               not null,
+    from_symbol         text               not null,
+    to_market           text               not null,
+    to_symbol           text               not null,
+    investment_currency text               not null,
+    return_currency     text               not null,
+    diff_status         integer default 0  null,
+    created             integer default 0  null,
+    updated             integer default 0  null,
+    bak                 text               null
+);
+
+create table if not exists arb_diff_rate_his
+(
+    id           integer primary key autoincrement,
+    diff_rate_id integer                not null,
+    diff_price   text    default '0'    not null,
+    diff_rate    text    default '0'    not null,
+    created      integer default 0      null,
+    updated      integer default 0      null,
+    bak          text                   null
+);
+
+create table if not exists arb_diff_rate_info
+(
+    id            integer primary key autoincrement,
+    diff_rate_id  integer             not null,
+    platform      text    default ''  not null,
+    coin          text                not null,
+    option_choose text                not null,
+    from_market   text                not null,
+    from_symbol   text                not null,
+    from_price    text                not null,
+    to_market     text                not null,
+    to_symbol     text                not null,
+    to_price      text                not null,
+    diff_price    text    default '0' not null,
+    diff_rate     text    default '0' not null,
+    created       integer default 0   null,
+    updated       integer default 0   null,
+    bak           text                null
+);
+
+create table if not exists arb_strategy
+(
+    id                  integer primary key autoincrement,
+    diff_rate_id        integer            not null,
+    user_id             integer            not null,
+    platform            text    default '' not null,
+    option_choose       text               not null,
+    coin                text               not null,
+    from_market         text    default '' not null,
+    from_symbol         text    default '' not null,
+    from_price_truncate integer default 2  not null,
+    from_amt_truncate   integer default 2  not null,
+    to_market           text    default '' not null,
+    to_symbol           text    default '' not null,
+    to_price_truncate   integer default 2  not null,
+    to_amt_truncate     integer default 2  not null,
+    from_to_desc        text               not null,
+    to_from_desc        text               not null,
+    option_open         text               not null,
+    option_close        text               not null,
+    option_amt          text               not null,
+    contract_mul        integer            not null,
+    margin_mul          integer default 1  not null,
+    fok_diff            text               null,
+    spot_fee            text               not null,
+    futures_fee         text               not null,
+    delivery_fee        text               not null,
+    doing_status        integer default 0  not null,
+    created             integer default 0  null,
+    updated             integer default 0  null,
+    bak                 text               null
+);
+
+create table if not exists arb_strategy_ex
+(
+    id                  integer primary key autoincrement,
+    user_id             integer            not null,
+    platform            text    default '' not null,
+    option_choose       text               not null,
+    arb_strategy_id     integer            not null,
+    coin                text    default '' not null,
+    market              text    default '' not null,
+    symbol              text    default '' not null,
+    option_type         text               not null,
+    option_status       integer default 0  null,
+    option_amount       text               null,
+    option_executed_amt text               null,
+    current_order_id    text               null,
+    created             integer default 0  null,
+    updated             integer default 0  null,
+    bak                 text               null
+);
+
+create table if not exists arb_strategy_ex_info
+(
+    id                 integer primary key autoincrement,
+    user_id            integer            not null,
+    platform           text    default '' not null,
+    option_choose      text               not null,
+    arb_strategy_id    integer            not null,
+    arb_strategy_ex_id integer            not null,
+    coin               text               not null,
+    market             text               null,
+    symbol             text               not null,
+    option_type        text               not null,
+    price              text               not null,
+    amount             text               not null,
+    executed_amt       text               null,
+    order_id           text               null,
+    is_ok              integer default 0  not null,
+    created            integer default 0  null,
+    updated            integer default 0  null,
+    bak                text               null
+);
+
+create table if not exists arb_stable_coin
+(
+    id             integer primary key autoincrement,
+    user_id        integer              not null,
+    platform       text    default ''   not null,
+    coin           text                 not null,
+    market         text    default ''   not null,
+    symbol         text    default ''   not null,
+    price_truncate integer default 2    not null,
+    amt_truncate   integer default 2    not null,
+    strategy       text    default '11' not null,
+    option_open    text                 not null,
+    option_close   text                 not null,
+    option_amt     text                 not null,
+    fok_diff       text                 null,
+    doing_status   integer default 0    not null,
+    created        integer default 0    null,
+    updated        integer default 0    null,
+    bak            text                 null
+);
+
+create table if not exists arb_stable_coin_info
+(
+    id             integer primary key autoincrement,
+    stable_coin_id integer            not null,
+    user_id        integer            not null,
+    platform       text    default '' not null,
+    coin           text               not null,
+    market         text               null,
+    symbol         text               not null,
+    option_type    text               not null,
+    price          text               not null,
+    amount         text               not null,
+    order_id       text               null,
+    is_ok          integer default 0  not null,
+    created        integer default 0  null,
+    updated        integer default 0  null,
+    bak            text               null
+);
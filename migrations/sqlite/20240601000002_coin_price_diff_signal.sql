@@ -0,0 +1,30 @@
+create table if not exists arb_coin_price
+(
+    id                    integer primary key autoincrement,
+    platform              text default ''  not null,
+    symbol                text             not null unique,
+    binance_spot_price    text default '0' not null,
+    binance_futures_price text default '0' not null,
+    bybit_futures_price   text default '0' not null,
+    kucoin_futures_price  text default '0' not null,
+    created               text default ''  not null,
+    updated               text default ''  not null,
+    bak                   text             null
+);
+
+create table if not exists arb_diff_signal
+(
+    id                    integer primary key autoincrement,
+    symbol                text             not null,
+    from_compare          text             not null,
+    to_compare            text             not null,
+    price_diff            text default '0' not null,
+    price_diff_rate       text default '0' not null,
+    binance_futures_price text default '0' not null,
+    bybit_futures_price   text default '0' not null,
+    kucoin_futures_price  text default '0' not null,
+    created               text default ''  not null,
+    updated               text default ''  not null
+);
+
+create index if not exists idx_symbol_compare on arb_diff_signal (symbol, from_compare, to_compare);
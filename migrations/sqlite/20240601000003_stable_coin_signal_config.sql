@@ -0,0 +1 @@
+alter table arb_stable_coin add column signal_config text default '' not null;
@@ -0,0 +1,3 @@
+alter table arb_strategy add column signal_mode text default 'threshold' not null;
+alter table arb_strategy add column lookback integer default 0 not null;
+create index idx_diff_rate_his_diff_rate_id on arb_diff_rate_his (diff_rate_id, id);
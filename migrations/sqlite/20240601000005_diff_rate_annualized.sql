@@ -0,0 +1,3 @@
+alter table arb_diff_rate_info add column annualized_rate text null;
+alter table arb_diff_rate_info add column delivery_time integer null;
+alter table arb_diff_rate_his add column annualized_rate text null;
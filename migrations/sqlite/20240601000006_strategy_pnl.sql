@@ -0,0 +1,16 @@
+create table if not exists arb_strategy_pnl
+(
+    id              integer primary key autoincrement,
+    arb_strategy_id integer           not null unique,
+    user_id         integer           not null,
+    coin            text default ''   not null,
+    realized_usdt   text default '0'  not null,
+    unrealized_usdt text default '0'  not null,
+    realized_coin   text default '0'  not null,
+    unrealized_coin text default '0'  not null,
+    fee_usdt        text default '0'  not null,
+    funding_usdt    text default '0'  not null,
+    coin_price      text default '0'  not null,
+    created         integer default 0 null,
+    updated         integer default 0 null
+);
@@ -0,0 +1,9 @@
+create table if not exists arb_diff_rate_sample
+(
+    id           integer primary key autoincrement,
+    diff_rate_id integer          not null,
+    diff_rate    text default '0' not null,
+    created      integer default 0 not null
+);
+
+create index if not exists idx_diff_rate_id_created on arb_diff_rate_sample (diff_rate_id, created);
@@ -0,0 +1,287 @@
+use crate::conf::C;
+use crate::helper::shutdown;
+use crate::{model, service, sql};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Local;
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+// 管理接口, 所有请求需要 Authorization: Bearer <token>
+pub async fn serve() {
+    let Some(admin) = C.admin.as_ref() else {
+        return;
+    };
+    let token = match admin.token() {
+        Ok(token) => token,
+        Err(e) => {
+            error!("admin token err: {:?}", e);
+            return;
+        }
+    };
+    let addr = match admin.listen.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(e) => {
+            error!("admin listen {} err: {:?}", admin.listen, e);
+            return;
+        }
+    };
+
+    let app = router().layer(middleware::from_fn_with_state(Arc::new(token), auth));
+    info!("admin api listening on {}", addr);
+    if let Err(e) = axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown::token().cancelled_owned())
+        .await
+    {
+        error!("admin api err: {:?}", e);
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/strategies", get(list_strategies).post(create_strategy))
+        .route("/strategies/validate", post(validate_strategy))
+        .route("/strategies/:id", get(get_strategy))
+        .route("/strategies/:id/start", post(start_strategy))
+        .route("/strategies/:id/stop", post(stop_strategy))
+        .route("/strategies/:id/unwind", post(unwind_strategy))
+        .route("/strategies/:id/legs", get(strategy_legs))
+        .route("/strategies/:id/fills", get(strategy_fills))
+        .route("/strategies/:id/pnl", get(strategy_pnl))
+        .route("/stable-coins", get(list_stable_coins).post(create_stable_coin))
+        .route("/stable-coins/validate", post(validate_stable_coin))
+        .route("/stable-coins/:id", get(get_stable_coin))
+        .route("/stable-coins/:id/start", post(start_stable_coin))
+        .route("/stable-coins/:id/stop", post(stop_stable_coin))
+        .route("/diff-rates", get(diff_rates))
+        .route("/signals", get(signals))
+}
+
+async fn auth<B>(
+    State(token): State<Arc<String>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response, StatusCode> {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if token.is_empty()
+        || ring::constant_time::verify_slices_are_equal(bearer.as_bytes(), token.as_bytes())
+            .is_err()
+    {
+        warn!("admin api unauthorized: {} {}", req.method(), req.uri());
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(req).await)
+}
+
+pub enum ApiError {
+    BadRequest(Value),
+    NotFound,
+    Internal(anyhow::Error),
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(e: E) -> Self {
+        let e = e.into();
+        match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => ApiError::NotFound,
+            _ => ApiError::Internal(e),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, msg) = match self {
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, json!("not found")),
+            // 内部错误只记录日志, 不向调用方暴露细节
+            ApiError::Internal(e) => {
+                error!("admin api err: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, json!("internal error"))
+            }
+        };
+        (status, Json(json!({ "code": status.as_u16(), "msg": msg }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    doing_status: Option<i8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PnlQuery {
+    refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnwindRequest {
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LimitQuery {
+    limit: Option<u32>,
+}
+
+async fn list_strategies(Query(q): Query<ListQuery>) -> ApiResult<Vec<model::ArbStrategy>> {
+    let list = match q.doing_status {
+        Some(doing_status) => sql::get_arb_strategy_list_by_doing_status(doing_status).await?,
+        None => sql::get_arb_strategy_list().await?,
+    };
+    Ok(Json(list))
+}
+
+async fn get_strategy(Path(id): Path<i64>) -> ApiResult<model::ArbStrategy> {
+    Ok(Json(sql::get_arb_strategy_by_id(id).await?))
+}
+
+async fn validate_strategy(Json(s): Json<model::ArbStrategy>) -> ApiResult<Value> {
+    let errors = service::validate_strategy(&s).await?;
+    Ok(Json(json!({ "ok": errors.is_empty(), "errors": errors })))
+}
+
+// 新建策略默认不执行, 通过 start 开启
+async fn create_strategy(Json(mut s): Json<model::ArbStrategy>) -> ApiResult<Value> {
+    let errors = service::validate_strategy(&s).await?;
+    if !errors.is_empty() {
+        return Err(ApiError::BadRequest(json!(errors)));
+    }
+    s.doing_status = model::arb_strategy::DOING_STATUS_UN_RUN;
+    s.created = Some(Local::now().timestamp());
+    s.updated = s.created;
+    let id = sql::insert_arb_strategy(s).await?;
+    info!(strategy_id = id; "admin api create arb_strategy");
+    Ok(Json(json!({ "id": id })))
+}
+
+async fn start_strategy(Path(id): Path<i64>) -> ApiResult<Value> {
+    let s = sql::get_arb_strategy_by_id(id).await?;
+    if s.doing_status == model::arb_strategy::DOING_STATUS_DONE {
+        return Err(ApiError::BadRequest(json!("strategy already done")));
+    }
+    sql::update_strategy_by_id(id, model::arb_strategy::DOING_STATUS_RUN).await?;
+    warn!(strategy_id = id; "admin api start arb_strategy");
+    service::scheduler::wake(id);
+    Ok(Json(json!({ "id": id, "doing_status": model::arb_strategy::DOING_STATUS_RUN })))
+}
+
+async fn stop_strategy(Path(id): Path<i64>) -> ApiResult<Value> {
+    let s = sql::get_arb_strategy_by_id(id).await?;
+    if s.doing_status == model::arb_strategy::DOING_STATUS_DONE {
+        return Err(ApiError::BadRequest(json!("strategy already done")));
+    }
+    sql::update_strategy_by_id(id, model::arb_strategy::DOING_STATUS_UN_RUN).await?;
+    warn!(strategy_id = id; "admin api stop arb_strategy");
+    Ok(Json(json!({ "id": id, "doing_status": model::arb_strategy::DOING_STATUS_UN_RUN })))
+}
+
+// 紧急平仓, 在服务进程内执行, 等待调度器中该策略的步骤结束后再撤单平仓
+async fn unwind_strategy(
+    Path(id): Path<i64>,
+    Json(req): Json<UnwindRequest>,
+) -> ApiResult<Value> {
+    if req.reason.trim().is_empty() {
+        return Err(ApiError::BadRequest(json!("reason required")));
+    }
+    warn!(strategy_id = id, reason = req.reason.as_str(); "admin api unwind arb_strategy");
+    let steps = service::operator::unwind(id, &req.reason).await?;
+    Ok(Json(json!({ "id": id, "steps": steps })))
+}
+
+async fn strategy_legs(Path(id): Path<i64>) -> ApiResult<Vec<model::ArbStrategyEx>> {
+    Ok(Json(sql::get_arb_strategy_ex_list_by_strategy_id(id).await?))
+}
+
+async fn strategy_fills(Path(id): Path<i64>) -> ApiResult<Vec<model::ArbStrategyExInfo>> {
+    Ok(Json(sql::get_arb_strategy_ex_info_list_by_strategy_id(id).await?))
+}
+
+// 最近一次计算的盈亏, refresh=true 时重新拉取成交计算
+async fn strategy_pnl(
+    Path(id): Path<i64>,
+    Query(q): Query<PnlQuery>,
+) -> ApiResult<Value> {
+    if q.refresh.unwrap_or(false) {
+        let s = sql::get_arb_strategy_by_id(id).await?;
+        let pnl = service::pnl::refresh(&s).await?;
+        return Ok(Json(json!(pnl)));
+    }
+    let pnl = sql::get_arb_strategy_pnl_by_strategy_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(json!({ "summary": pnl })))
+}
+
+async fn list_stable_coins(Query(q): Query<ListQuery>) -> ApiResult<Vec<model::ArbStableCoin>> {
+    let list = match q.doing_status {
+        Some(doing_status) => sql::get_arb_stable_coin_list_by_doing_status(doing_status).await?,
+        None => sql::get_arb_stable_coin_list().await?,
+    };
+    Ok(Json(list))
+}
+
+async fn get_stable_coin(Path(id): Path<i64>) -> ApiResult<model::ArbStableCoin> {
+    Ok(Json(sql::get_arb_stable_coin_by_id(id).await?))
+}
+
+async fn validate_stable_coin(Json(s): Json<model::ArbStableCoin>) -> ApiResult<Value> {
+    let errors = service::validate_stable_coin(&s).await?;
+    Ok(Json(json!({ "ok": errors.is_empty(), "errors": errors })))
+}
+
+async fn create_stable_coin(Json(mut s): Json<model::ArbStableCoin>) -> ApiResult<Value> {
+    let errors = service::validate_stable_coin(&s).await?;
+    if !errors.is_empty() {
+        return Err(ApiError::BadRequest(json!(errors)));
+    }
+    s.doing_status = model::arb_stable_coin::DOING_STATUS_UN_RUN;
+    s.created = Some(Local::now().timestamp());
+    s.updated = s.created;
+    let id = sql::insert_arb_stable_coin(s).await?;
+    info!(stable_coin_id = id; "admin api create arb_stable_coin");
+    Ok(Json(json!({ "id": id })))
+}
+
+async fn start_stable_coin(Path(id): Path<i64>) -> ApiResult<Value> {
+    let s = sql::get_arb_stable_coin_by_id(id).await?;
+    if s.doing_status == model::arb_stable_coin::DOING_STATUS_DONE {
+        return Err(ApiError::BadRequest(json!("stable coin already done")));
+    }
+    sql::update_stable_coin_by_id(id, model::arb_stable_coin::DOING_STATUS_RUN).await?;
+    warn!(stable_coin_id = id; "admin api start arb_stable_coin");
+    Ok(Json(json!({ "id": id, "doing_status": model::arb_stable_coin::DOING_STATUS_RUN })))
+}
+
+async fn stop_stable_coin(Path(id): Path<i64>) -> ApiResult<Value> {
+    let s = sql::get_arb_stable_coin_by_id(id).await?;
+    if s.doing_status == model::arb_stable_coin::DOING_STATUS_DONE {
+        return Err(ApiError::BadRequest(json!("stable coin already done")));
+    }
+    sql::update_stable_coin_by_id(id, model::arb_stable_coin::DOING_STATUS_UN_RUN).await?;
+    warn!(stable_coin_id = id; "admin api stop arb_stable_coin");
+    Ok(Json(json!({ "id": id, "doing_status": model::arb_stable_coin::DOING_STATUS_UN_RUN })))
+}
+
+async fn diff_rates() -> ApiResult<Vec<model::ArbDiffRateInfo>> {
+    Ok(Json(sql::get_arb_diff_rate_info_list().await?))
+}
+
+async fn signals(Query(q): Query<LimitQuery>) -> ApiResult<Vec<model::ArbDiffSignal>> {
+    Ok(Json(sql::get_arb_diff_signal_list(q.limit.unwrap_or(100)).await?))
+}
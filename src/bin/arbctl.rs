@@ -0,0 +1,358 @@
+use anyhow::anyhow;
+use arbitrage::conf;
+use arbitrage::conf::secret;
+use arbitrage::service::operator;
+use arbitrage::{db, helper, model, service, sql};
+use chrono::{Local, NaiveDate, TimeZone};
+use serde_json::{Map, Value};
+use std::fs;
+
+const USAGE: &str = "usage:
+  arbctl secret gen-key                 生成主密钥 (hex)
+  arbctl secret encrypt <plaintext>     使用主密钥加密, 输出 enc:... 填入 config.toml
+  arbctl secret rotate <new_key_hex>    使用新主密钥重新加密 config.toml 中所有 enc: 值
+
+  arbctl strategy validate --<field> <value> ...   按 arb_strategy 字段校验策略及交易所规则
+  arbctl strategy create --<field> <value> ...     校验通过后创建策略 (未执行状态)
+  arbctl strategy legs <strategy_id>               执行明细及订单实时状态
+  arbctl strategy pnl <strategy_id>                按实际成交、手续费及资金费计算并保存盈亏
+  arbctl strategy unwind <strategy_id> <reason>    紧急平仓, 不加 --yes 只打印平仓步骤, 服务运行时改用 admin 接口
+
+  arbctl leg done <ex_id> <reason>      强制标记执行明细已完成
+  arbctl leg undone <ex_id> <reason>    强制标记执行明细未完成
+  arbctl leg cancel <ex_id> <reason>    撤销执行明细当前挂单
+
+  arbctl report [--date YYYY-MM-DD | --from YYYY-MM-DD --to YYYY-MM-DD] [--out <dir>]
+                                        生成运营报表 (csv 及 index.html), 默认昨天, 输出到 reports/<from>_<to>
+
+  arbctl migrate                        执行未应用的表结构迁移
+  arbctl migrate status                 查看已应用及未应用的迁移
+
+主密钥通过环境变量 ARB_MASTER_KEY 或 ARB_MASTER_KEY_FILE 提供
+例: arbctl strategy create --diff_rate_id 1 --user_id 1 --option_choose positive --coin BTC \\
+      --from_market spot --from_symbol BTCUSDT --to_market delivery --to_symbol BTCUSD_240927 ...";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    match args.as_slice() {
+        ["secret", rest @ ..] => secret_cmd(rest),
+        ["migrate", rest @ ..] => {
+            init_without_check().await?;
+            migrate_cmd(rest).await
+        }
+        ["strategy", rest @ ..] => {
+            init().await?;
+            strategy_cmd(rest).await
+        }
+        ["leg", rest @ ..] => {
+            init().await?;
+            leg_cmd(rest).await
+        }
+        ["report", rest @ ..] => {
+            init().await?;
+            report_cmd(rest).await
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+async fn init() -> anyhow::Result<()> {
+    init_without_check().await?;
+    db::check_schema().await
+}
+
+async fn init_without_check() -> anyhow::Result<()> {
+    lazy_static::initialize(&conf::C);
+    db::init_env_without_rocksdb().await?;
+    helper::log::init_log();
+    Ok(())
+}
+
+async fn migrate_cmd(args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        [] => {
+            let applied = db::migrate().await?;
+            if applied.is_empty() {
+                println!("schema up to date");
+            }
+            for version in applied {
+                println!("applied {}", version);
+            }
+        }
+        ["status"] => {
+            let (applied, pending) = db::migrate_status().await?;
+            for m in db::migrator()?.iter() {
+                let state = if applied.contains(&m.version) {
+                    "applied"
+                } else if pending.contains(&m.version) {
+                    "pending"
+                } else {
+                    continue;
+                };
+                println!("{:<16} {:<8} {}", m.version, state, m.description);
+            }
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+    Ok(())
+}
+
+async fn report_cmd(args: &[&str]) -> anyhow::Result<()> {
+    let mut date = None;
+    let mut from = None;
+    let mut to = None;
+    let mut out = None;
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter.next().ok_or_else(|| anyhow!(USAGE))?;
+        match *flag {
+            "--date" => date = Some(NaiveDate::parse_from_str(value, "%Y-%m-%d")?),
+            "--from" => from = Some(NaiveDate::parse_from_str(value, "%Y-%m-%d")?),
+            "--to" => to = Some(NaiveDate::parse_from_str(value, "%Y-%m-%d")?),
+            "--out" => out = Some(value.to_string()),
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+    // --to 为包含的最后一天
+    let (from, to) = match (date, from, to) {
+        (Some(d), None, None) => (d, d),
+        (None, Some(f), Some(t)) if f <= t => (f, t),
+        (None, None, None) => {
+            let yesterday = Local::now()
+                .date_naive()
+                .pred_opt()
+                .ok_or_else(|| anyhow!("invalid date"))?;
+            (yesterday, yesterday)
+        }
+        _ => return Err(anyhow!(USAGE)),
+    };
+    let local = |d: NaiveDate| -> anyhow::Result<i64> {
+        let t = d.and_hms_opt(0, 0, 0).ok_or_else(|| anyhow!("invalid date"))?;
+        Local
+            .from_local_datetime(&t)
+            .earliest()
+            .map(|t| t.timestamp())
+            .ok_or_else(|| anyhow!("invalid local time: {}", t))
+    };
+    let end = to.succ_opt().ok_or_else(|| anyhow!("invalid date"))?;
+    let report = service::report::build(local(from)?, local(end)?).await?;
+    let out = out.unwrap_or_else(|| format!("reports/{}_{}", from, to));
+    for file in report.write(std::path::Path::new(&out))? {
+        println!("{}", file);
+    }
+    Ok(())
+}
+
+fn secret_cmd(args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        ["gen-key"] => {
+            println!("{}", secret::generate_key()?);
+        }
+        ["encrypt", plain] => {
+            println!("{}", secret::encrypt(&secret::master_key()?, plain)?);
+        }
+        ["rotate", new_key] => {
+            let old = secret::master_key()?;
+            let new = secret::parse_key(new_key)?;
+            let path = conf::config_path();
+            let text = fs::read_to_string(&path)?;
+            let (rotated, count) = secret::rotate(&old, &new, &text)?;
+            fs::write(format!("{}.bak", path), &text)?;
+            fs::write(&path, rotated)?;
+            println!(
+                "rotated {} secrets in {}, old file kept as {}.bak",
+                count, path, path
+            );
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+    Ok(())
+}
+
+async fn strategy_cmd(args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        ["validate", flags @ ..] => {
+            let s = parse_strategy(flags)?;
+            let errors = service::validate_strategy(&s).await?;
+            print_errors(&errors);
+        }
+        ["create", flags @ ..] => {
+            let mut s = parse_strategy(flags)?;
+            let errors = service::validate_strategy(&s).await?;
+            if !errors.is_empty() {
+                print_errors(&errors);
+                return Err(anyhow!("validate failed, strategy not created"));
+            }
+            s.doing_status = model::arb_strategy::DOING_STATUS_UN_RUN;
+            s.created = Some(Local::now().timestamp());
+            s.updated = s.created;
+            let id = sql::insert_arb_strategy(s).await?;
+            println!("created arb_strategy id: {}", id);
+        }
+        ["legs", id] => {
+            let (s, legs) = operator::get_legs(id.parse()?).await?;
+            println!(
+                "strategy {} {} {} -> {} doing_status: {}",
+                s.id, s.option_choose, s.from_symbol, s.to_symbol, s.doing_status
+            );
+            println!(
+                "{:<8} {:<28} {:<10} {:<16} {:<7} {:>14} {:>14} {:<20} {}",
+                "ex_id",
+                "option_type",
+                "market",
+                "symbol",
+                "status",
+                "amount",
+                "executed",
+                "order_id",
+                "order_status"
+            );
+            for leg in legs {
+                let ex = &leg.ex;
+                let status = if ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE {
+                    "done"
+                } else {
+                    "undone"
+                };
+                let order_status = match (&leg.order_status, leg.order_executed) {
+                    (Some(st), Some(executed)) => format!("{} ({})", st, executed),
+                    (Some(st), None) => st.clone(),
+                    _ => "-".to_string(),
+                };
+                println!(
+                    "{:<8} {:<28} {:<10} {:<16} {:<7} {:>14} {:>14} {:<20} {}",
+                    ex.id,
+                    ex.option_type,
+                    ex.market,
+                    ex.symbol,
+                    status,
+                    ex.option_amount,
+                    ex.option_executed_amt,
+                    ex.current_order_id,
+                    order_status
+                );
+            }
+        }
+        ["pnl", id] => {
+            let s = sql::get_arb_strategy_by_id(id.parse()?).await?;
+            let pnl = service::pnl::refresh(&s).await?;
+            println!(
+                "{:<10} {:<16} {:<6} {:>18} {:>18} {:>18} {:>14} {:>14} {:>7}",
+                "market",
+                "symbol",
+                "asset",
+                "position",
+                "realized",
+                "unrealized",
+                "fee_usdt",
+                "funding",
+                "trades"
+            );
+            for leg in pnl.legs.iter() {
+                println!(
+                    "{:<10} {:<16} {:<6} {:>18} {:>18} {:>18} {:>14} {:>14} {:>7}",
+                    leg.market,
+                    leg.symbol,
+                    leg.asset,
+                    leg.position.round_dp(8),
+                    leg.realized.round_dp(8),
+                    leg.unrealized.round_dp(8),
+                    leg.fee_usdt.round_dp(8),
+                    leg.funding.round_dp(8),
+                    leg.trades
+                );
+            }
+            let p = &pnl.summary;
+            println!(
+                "realized: {} USDT / {} {}, unrealized: {} USDT / {} {}, fee: {} USDT, funding: {} USDT, {} price: {}",
+                p.realized_usdt,
+                p.realized_coin,
+                p.coin,
+                p.unrealized_usdt,
+                p.unrealized_coin,
+                p.coin,
+                p.fee_usdt,
+                p.funding_usdt,
+                p.coin,
+                p.coin_price
+            );
+        }
+        ["unwind", id, rest @ ..] => {
+            let id: i64 = id.parse()?;
+            let yes = rest.contains(&"--yes");
+            let reason = reason(rest.iter().filter(|a| **a != "--yes").copied())?;
+            if yes {
+                let steps = operator::unwind(id, &reason).await?;
+                for step in steps {
+                    println!("done: {:?}", step);
+                }
+            } else {
+                let s = sql::get_arb_strategy_by_id(id).await?;
+                let list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await?;
+                let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(id).await?;
+                println!("dry run, open orders will be canceled first, add --yes to execute:");
+                for step in operator::unwind_plan(&s, &list, &infos) {
+                    println!("  {:?}", step);
+                }
+            }
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+    Ok(())
+}
+
+async fn leg_cmd(args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        ["done", id, rest @ ..] => {
+            operator::mark_leg(id.parse()?, true, &reason(rest.iter().copied())?).await?
+        }
+        ["undone", id, rest @ ..] => {
+            operator::mark_leg(id.parse()?, false, &reason(rest.iter().copied())?).await?
+        }
+        ["cancel", id, rest @ ..] => {
+            operator::cancel_leg_order(id.parse()?, &reason(rest.iter().copied())?).await?
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+    println!("ok");
+    Ok(())
+}
+
+// 人工干预必须填写原因
+fn reason<'a>(args: impl Iterator<Item = &'a str>) -> anyhow::Result<String> {
+    let reason = args.collect::<Vec<_>>().join(" ");
+    if reason.trim().is_empty() {
+        return Err(anyhow!("reason is required"));
+    }
+    Ok(reason)
+}
+
+// --field value 转为 arb_strategy, 数字按数字解析, 其余按字符串
+fn parse_strategy(flags: &[&str]) -> anyhow::Result<model::ArbStrategy> {
+    let mut obj = Map::new();
+    let mut iter = flags.iter();
+    while let Some(flag) = iter.next() {
+        let key = flag
+            .strip_prefix("--")
+            .ok_or(anyhow!("unexpected argument: {}", flag))?;
+        let value = iter.next().ok_or(anyhow!("missing value for --{}", key))?;
+        let value = match serde_json::from_str::<Value>(value) {
+            Ok(v @ Value::Number(_)) => v,
+            _ => Value::String(value.to_string()),
+        };
+        obj.insert(key.replace('-', "_"), value);
+    }
+    Ok(serde_json::from_value(Value::Object(obj))?)
+}
+
+fn print_errors(errors: &[String]) {
+    if errors.is_empty() {
+        println!("ok");
+    }
+    for e in errors {
+        println!("- {}", e);
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::anyhow;
+use arbitrage::mock::{MockServer, Script};
+use arbitrage::{conf, helper};
+use log::info;
+use std::time::Duration;
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "usage:
+  mock_binance [script.toml] [--rest 127.0.0.1:9900] [--ws 127.0.0.1:9901]
+
+本地模拟币安 REST/WS 接口, 脚本格式见 doc/mock_binance.toml
+配置文件中设置 [binance_endpoint] 后服务连接到模拟服务";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut script = Script::default();
+    let mut rest = "127.0.0.1:9900".to_string();
+    let mut ws = "127.0.0.1:9901".to_string();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--rest" => rest = iter.next().ok_or_else(|| anyhow!(USAGE))?.clone(),
+            "--ws" => ws = iter.next().ok_or_else(|| anyhow!(USAGE))?.clone(),
+            path if !path.starts_with("--") => {
+                script = toml::from_str(&std::fs::read_to_string(path)?)?;
+            }
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+
+    lazy_static::initialize(&conf::C);
+    helper::log::init_log();
+    let server = MockServer::start(script, &rest, &ws).await?;
+    println!(
+        "mock binance rest: http://{}, ws: ws://{}",
+        server.rest_addr, server.ws_addr
+    );
+
+    tokio::signal::ctrl_c().await?;
+    info!(orders = server.orders().len(), transfers = server.transfers().len(); "mock binance stopped");
+    helper::shutdown::shutdown(SHUTDOWN_TIMEOUT).await;
+    helper::log::flush();
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use anyhow::anyhow;
+use arbitrage::service::replay::{self, Options, Speed};
+use arbitrage::service::PriceStream;
+use arbitrage::{conf, db, helper, service};
+use log::{info, warn};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+
+// 回放结束后等待差价按最后的价格再计算一轮
+const SETTLE: Duration = Duration::from_secs(2);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+const USAGE: &str = "usage:
+  replay <dir|file> [--speed max|1x|10x] [--feeds binance_spot,binance_futures] [--from <ms>] [--to <ms>]
+
+回放 recorder 录制的原始行情, 经 set_price_data/get_diff_signal 及价格缓存/差价比率处理, 不执行策略
+默认实时速度; 必须通过 ARB_CONFIG 指定数据库、Redis 与实盘不同的配置文件";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = parse(&args)?;
+
+    lazy_static::initialize(&conf::C);
+    replay::ensure_isolated()?;
+    db::init_env_without_rocksdb().await?;
+    helper::log::init_log();
+    db::check_schema().await?;
+
+    let (price_tx, price_rx) = unbounded_channel::<PriceStream>();
+    let (signal_tx, signal_rx) = unbounded_channel::<PriceStream>();
+    // 按帧顺序写入价格缓存并按虚拟时钟计算差价比率
+    let pipeline = tokio::spawn(replay::process(price_rx, signal_tx));
+    let consumer = tokio::spawn(service::set_price_data(signal_rx));
+    helper::shutdown::spawn(service::get_diff_signal());
+
+    let stats = tokio::select! {
+        result = replay::replay(&opts, price_tx) => result?,
+        _ = tokio::signal::ctrl_c() => {
+            warn!("replay interrupted");
+            replay::Stats::default()
+        }
+    };
+    pipeline.await??;
+    consumer.await?;
+    tokio::time::sleep(SETTLE).await;
+    helper::shutdown::shutdown(SHUTDOWN_TIMEOUT).await;
+    info!(frames = stats.frames, skipped = stats.skipped; "replay done");
+    println!(
+        "frames: {}, skipped: {}, from: {:?}, to: {:?}",
+        stats.frames, stats.skipped, stats.first_ts, stats.last_ts
+    );
+    helper::log::flush();
+    Ok(())
+}
+
+fn parse(args: &[String]) -> anyhow::Result<Options> {
+    let (path, flags) = args.split_first().ok_or_else(|| anyhow!(USAGE))?;
+    let mut opts = Options {
+        path: PathBuf::from(path),
+        speed: Speed::Rate(1.0),
+        feeds: vec![],
+        from: None,
+        to: None,
+    };
+    let mut iter = flags.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter.next().ok_or_else(|| anyhow!(USAGE))?;
+        match flag.as_str() {
+            "--speed" => opts.speed = value.parse()?,
+            "--feeds" => opts.feeds = value.split(',').map(|f| f.to_string()).collect(),
+            "--from" => opts.from = Some(value.parse()?),
+            "--to" => opts.to = Some(value.parse()?),
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+    Ok(opts)
+}
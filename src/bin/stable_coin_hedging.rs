@@ -1,10 +1,10 @@
-#[macro_use]
-extern crate tokio;
-
 use arbitrage::{conf, db, helper, service};
 use futures::future::BoxFuture;
 use log::warn;
-use std::collections::HashMap;
+use std::time::Duration;
+
+// 收到退出信号后等待进行中的任务完成的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -14,45 +14,27 @@ async fn main() -> anyhow::Result<()> {
     db::init_env().await?;
     // 初始化日志
     helper::log::init_log();
-
-    let (close_tx, mut close_rx) = tokio::sync::mpsc::unbounded_channel::<bool>();
-
-    let wait_loop = tokio::spawn(async move {
-        'hello: loop {
-            select! {
-                _ = close_rx.recv() => break 'hello
-            }
-        }
-    });
-
-    // 线程池通道
-    let mut txs = HashMap::new();
-    let mut rxs = HashMap::new();
-    for i in 0..10 {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        txs.insert(i, tx.clone());
-        rxs.insert(i, rx);
-    }
+    // 检查表结构版本
+    db::check_schema().await?;
 
     let streams: Vec<BoxFuture<'static, ()>> = vec![
-        Box::pin(service::inspect_stable_coin(txs.clone())), // 轮训策略
+        Box::pin(service::scheduler::schedule_stable_coin()), // 定时唤醒策略
+        Box::pin(service::kline::start()), // 订阅配置的K线
     ];
 
     for stream in streams {
-        tokio::spawn(stream);
+        helper::shutdown::spawn(stream);
     }
 
-    // 开始线程池
-    service::event_stable_coin_start(rxs).await;
+    // 开始策略调度
+    service::scheduler::start();
 
-    select! {
-        _ = wait_loop => { warn!("Finished!") }
-        _ = tokio::signal::ctrl_c() => {
-            warn!("Closing stream...");
-            close_tx.send(true).unwrap();
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
+    tokio::signal::ctrl_c().await?;
+    warn!("Shutting down, waiting for in-flight tasks...");
+    if helper::shutdown::shutdown(SHUTDOWN_TIMEOUT).await {
+        warn!("Finished!");
     }
+    helper::log::flush();
 
     Ok(())
 }
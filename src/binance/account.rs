@@ -0,0 +1,44 @@
+use crate::binance::errors::*;
+use crate::binance::signer::Signer;
+use crate::binance::MyApi;
+use crate::conf::C;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+// user_id -> MyApi, 每个账户独立的 client, 下单频率、余额、错误互不影响
+static APIS: Lazy<DashMap<i64, MyApi>> = Lazy::new(DashMap::new);
+// 公开接口(exchangeInfo、K线)使用, 不需要账户
+static PUBLIC: Lazy<MyApi> = Lazy::new(|| MyApi::new_with_key(String::new(), String::new()));
+
+/// Returns the shared api for public endpoints, requests are not signed with any account.
+pub fn public_api() -> &'static MyApi {
+    &PUBLIC
+}
+
+/// Returns the api of the account that owns `user_id`.
+/// When no `[[accounts]]` are configured every user falls back to `binance_api_config`,
+/// otherwise a user without credentials is an error rather than trading on another account.
+pub fn get_api(user_id: i64) -> Result<MyApi> {
+    if let Some(api) = APIS.get(&user_id) {
+        return Ok(api.clone());
+    }
+
+    let (api_key, secret_key, key_type) = if C.accounts.is_empty() {
+        let c = &C.binance_api_config;
+        (c.api_key(), c.secret_key(), &c.key_type)
+    } else {
+        let account = C
+            .accounts
+            .iter()
+            .find(|a| a.user_id == user_id)
+            .ok_or_else(|| Error::Msg(format!("no api credentials for user_id: {user_id}")))?;
+        (account.api_key(), account.secret_key(), &account.key_type)
+    };
+    let reveal_err =
+        |e: anyhow::Error| Error::Msg(format!("user_id: {user_id}, decrypt credentials err: {e}"));
+    let signer = Signer::new(key_type, &secret_key.map_err(reveal_err)?)?;
+    let api = MyApi::new_with_signer(api_key.map_err(reveal_err)?, signer);
+
+    APIS.insert(user_id, api.clone());
+    Ok(api)
+}
@@ -2,7 +2,8 @@ use crate::binance::client::Client;
 use crate::binance::config::Config;
 use crate::binance::errors::*;
 use crate::binance::rest_model::*;
-use crate::binance::util::{build_request, to_f64, to_i64};
+use crate::binance::signer::Signer;
+use crate::binance::util::{build_request, build_signed_request, to_f64, to_i64};
 use crate::conf::C;
 use serde_json::Value;
 
@@ -16,6 +17,8 @@ pub struct FuturesOrderRequest {
     pub quantity: Option<f64>,
     pub price: Option<f64>,
     pub time_in_force: Option<TimeInForce>,
+    /// Only reduce an existing position
+    pub reduce_only: Option<bool>,
     pub recv_window: Option<u64>,
 }
 
@@ -84,24 +87,44 @@ pub struct MyApi {
 }
 
 impl MyApi {
-    pub fn new() -> Self {
+    /// 使用 binance_api_config 创建, 解密失败或私钥无效时返回错误
+    pub fn new() -> Result<Self> {
+        let c = &C.binance_api_config;
+        let reveal_err =
+            |e: anyhow::Error| Error::Msg(format!("decrypt binance credentials err: {e}"));
+        let signer = Signer::new(&c.key_type, &c.secret_key().map_err(reveal_err)?)?;
+        Ok(Self::new_with_signer(c.api_key().map_err(reveal_err)?, signer))
+    }
+
+    /// 使用 HMAC secret 创建
+    pub fn new_with_key(api_key: String, secret_key: String) -> Self {
+        Self::new_with_signer(api_key, Signer::hmac(&secret_key))
+    }
+
+    /// 使用指定账户的 key 创建, 每个账户拥有独立的 client
+    pub fn new_with_signer(api_key: String, signer: Signer) -> Self {
+        Self::new_with_config(api_key, signer, &Config::with_endpoints())
+    }
+
+    /// 使用指定地址创建, 如 mock 服务
+    pub fn new_with_config(api_key: String, signer: Signer, config: &Config) -> Self {
         MyApi {
-            client: Client::new(
-                Some(C.binance_api_config.api_key.clone()),
-                Some(C.binance_api_config.secret_key.clone()),
-                Config::default().rest_api_endpoint,
+            client: Client::new_with_signer(
+                Some(api_key.clone()),
+                signer.clone(),
+                config.rest_api_endpoint.clone(),
                 Some(5),
             ),
-            futures_client: Client::new(
-                Some(C.binance_api_config.api_key.clone()),
-                Some(C.binance_api_config.secret_key.clone()),
-                Config::default().futures_rest_api_endpoint,
+            futures_client: Client::new_with_signer(
+                Some(api_key.clone()),
+                signer.clone(),
+                config.futures_rest_api_endpoint.clone(),
                 Some(5),
             ),
-            delivery_client: Client::new(
-                Some(C.binance_api_config.api_key.clone()),
-                Some(C.binance_api_config.secret_key.clone()),
-                Config::default().delivery_rest_api_endpoint,
+            delivery_client: Client::new_with_signer(
+                Some(api_key),
+                signer,
+                config.delivery_rest_api_endpoint.clone(),
                 Some(5),
             ),
             recv_window: 5000,
@@ -112,8 +135,28 @@ impl MyApi {
         self.client.get("/api/v3/time", None).await
     }
 
+    /// Symbol rules of `market` (spot, futures, delivery)
+    pub async fn get_symbol_info(&self, market: &str, symbol: &str) -> Result<SymbolInfo> {
+        let info: ExchangeInformation = match market {
+            "spot" => {
+                let request = build_request([("symbol", symbol)]);
+                self.client.get("/api/v3/exchangeInfo", Some(&request)).await?
+            }
+            "futures" => self.futures_client.get("/fapi/v1/exchangeInfo", None).await?,
+            "delivery" => self.delivery_client.get("/dapi/v1/exchangeInfo", None).await?,
+            _ => return Err(Error::Msg(format!("unknown market {market}"))),
+        };
+
+        info.symbols
+            .into_iter()
+            .find(|s| s.symbol == symbol)
+            .ok_or_else(|| Error::UnknownSymbol(symbol.to_string()))
+    }
+
+    /// Klines of `market` (spot, futures, delivery)
     pub async fn get_klines<S1, S2, S3, S4, S5>(
         &self,
+        market: &str,
         symbol: S1,
         interval: S2,
         limit: S3,
@@ -138,8 +181,12 @@ impl MyApi {
 
         let request = build_request(parameters);
 
-        let parsed_data: Vec<Vec<Value>> =
-            self.client.get("/api/v3/klines", Some(&request)).await?;
+        let parsed_data: Vec<Vec<Value>> = match market {
+            "spot" => self.client.get("/api/v3/klines", Some(&request)).await?,
+            "futures" => self.futures_client.get("/fapi/v1/klines", Some(&request)).await?,
+            "delivery" => self.delivery_client.get("/dapi/v1/klines", Some(&request)).await?,
+            _ => return Err(Error::Msg(format!("unknown market {market}"))),
+        };
 
         let klines = KlineSummaries::AllKlineSummaries(
             parsed_data
@@ -162,6 +209,94 @@ impl MyApi {
         Ok(klines)
     }
 
+    /// Trades of an order on `market` (spot, futures, delivery)
+    pub async fn get_order_trades(
+        &self,
+        market: &str,
+        symbol: &str,
+        order_id: &str,
+    ) -> Result<Vec<Trade>> {
+        let request = build_signed_request(
+            [("symbol", symbol), ("orderId", order_id)],
+            self.recv_window,
+        )?;
+        match market {
+            "spot" => self.client.get_signed("/api/v3/myTrades", &request).await,
+            "futures" => {
+                self.futures_client
+                    .get_signed("/fapi/v1/userTrades", &request)
+                    .await
+            }
+            "delivery" => {
+                self.delivery_client
+                    .get_signed("/dapi/v1/userTrades", &request)
+                    .await
+            }
+            _ => Err(Error::Msg(format!("unknown market {market}"))),
+        }
+    }
+
+    /// Funding fee income of `market` (futures, delivery) between start and end time in ms,
+    /// paged by startTime until a page comes back short
+    pub async fn get_funding_income(
+        &self,
+        market: &str,
+        symbol: &str,
+        start_time: u64,
+        end_time: u64,
+    ) -> Result<Vec<Income>> {
+        const LIMIT: usize = 1000;
+        let mut incomes = Vec::new();
+        let mut start_time = start_time;
+        loop {
+            let page = self
+                .funding_income_page(market, symbol, start_time, end_time, LIMIT)
+                .await?;
+            let (len, last) = (page.len(), page.last().map(|i| i.time));
+            incomes.extend(page);
+            // one funding fee per settlement for a symbol, so resume right after the last one
+            match last {
+                Some(time) if len >= LIMIT && time < end_time => start_time = time + 1,
+                _ => return Ok(incomes),
+            }
+        }
+    }
+
+    async fn funding_income_page(
+        &self,
+        market: &str,
+        symbol: &str,
+        start_time: u64,
+        end_time: u64,
+        limit: usize,
+    ) -> Result<Vec<Income>> {
+        let (start_time, end_time, limit) =
+            (start_time.to_string(), end_time.to_string(), limit.to_string());
+        let request = build_signed_request(
+            [
+                ("symbol", symbol),
+                ("incomeType", "FUNDING_FEE"),
+                ("startTime", start_time.as_str()),
+                ("endTime", end_time.as_str()),
+                ("limit", limit.as_str()),
+            ],
+            self.recv_window,
+        )?;
+        match market {
+            "futures" => {
+                self.futures_client
+                    .get_signed("/fapi/v1/income", &request)
+                    .await
+            }
+            "delivery" => {
+                self.delivery_client
+                    .get_signed("/dapi/v1/income", &request)
+                    .await
+            }
+            _ => Err(Error::Msg(format!("no funding on market {market}"))),
+        }
+    }
+
     pub async fn universal_transfer(
         &self,
         asset: String,
@@ -181,64 +316,29 @@ impl MyApi {
             .await
     }
 
-    // pub async fn place_order(&self, order: OrderRequest) -> Result<Transaction> {
-    //     order.valid()?;
-    //     let recv_window = order.recv_window.unwrap_or(self.recv_window);
-    //     let request = build_signed_request_p(order, recv_window)?;
-    //     self.client.post_signed("/api/v3/order", &request).await
-    // }
-    //
-    // pub async fn order_status(&self, osr: OrderStatusRequest) -> Result<Order> {
-    //     let recv_window = osr.recv_window.unwrap_or(self.recv_window);
-    //     let request = build_signed_request_p(osr, recv_window)?;
-    //     self.client.get_signed("/api/v3/order", &request).await
-    // }
-
+    /// Place an order
     pub async fn place_order(&self, order: OrderRequest) -> Result<Transaction> {
-
-        // Simulate creating a transaction
-        let transaction = Transaction {
-            symbol: "BTCUSDT".to_string(),
-            order_id: 123456,
-            client_order_id: "dummy_client_order_id".to_string(),
-            transact_time: 1234567890,
-            price: 50000.0,
-            orig_qty: 0.1,
-            executed_qty: 0.1,
-            cummulative_quote_qty: 5000.0,
-            status: OrderStatus::Filled,
-            time_in_force: TimeInForce::GTC,
-            order_type: OrderType::Limit,
-            side: OrderSide::Buy,
-            fills: vec![], // Add dummy fills if needed
-        };
-
-        // Return the transaction wrapped in Ok
-        Ok(transaction)
+        order.valid()?;
+        let recv_window = order.recv_window.unwrap_or(self.recv_window);
+        self.client
+            .post_signed_p("/api/v3/order", order, recv_window)
+            .await
     }
 
+    /// Get an order
     pub async fn order_status(&self, osr: OrderStatusRequest) -> Result<Order> {
-        let order = Order {
-            symbol: "BTCUSDT".to_string(),
-            order_id: 123456,
-            order_list_id: 0,
-            client_order_id: "dummy_client_order_id".to_string(),
-            price: 50000.0,
-            orig_qty: 0.1,
-            executed_qty: 0.1,
-            cummulative_quote_qty: 5000.0,
-            status: OrderStatus::Filled,
-            time_in_force: TimeInForce::GTC,
-            order_type: OrderType::Limit,
-            side: OrderSide::Buy,
-            stop_price: 0.0,
-            iceberg_qty: 0.0,
-            time: 1234567890,
-            update_time: 1234567890,
-            is_working: true,
-            orig_quote_order_qty: 5000.0,
-        };
-        Ok(order)
+        let recv_window = osr.recv_window.unwrap_or(self.recv_window);
+        self.client
+            .get_signed_p("/api/v3/order", Some(osr), recv_window)
+            .await
+    }
+
+    /// Cancel an active order
+    pub async fn cancel_order(&self, osr: OrderStatusRequest) -> Result<OrderCanceled> {
+        let recv_window = osr.recv_window.unwrap_or(self.recv_window);
+        self.client
+            .delete_signed_p("/api/v3/order", osr, recv_window)
+            .await
     }
 
     /// Get an order
@@ -261,6 +361,16 @@ impl MyApi {
             .await
     }
 
+    /// Cancel an active order
+    pub async fn futures_cancel_order(
+        &self,
+        order: FuturesGetOrderRequest,
+    ) -> Result<FuturesTransaction> {
+        self.futures_client
+            .delete_signed_p("/fapi/v1/order", order, self.recv_window)
+            .await
+    }
+
     /// Get an order
     pub async fn delivery_order_status(
         &self,
@@ -271,6 +381,16 @@ impl MyApi {
             .await
     }
 
+    /// Cancel an active order
+    pub async fn delivery_cancel_order(
+        &self,
+        order: FuturesGetOrderRequest,
+    ) -> Result<FuturesTransaction> {
+        self.delivery_client
+            .delete_signed_p("/dapi/v1/order", order, self.recv_window)
+            .await
+    }
+
     /// Place an order
     pub async fn delivery_place_order(
         &self,
@@ -285,98 +405,220 @@ impl MyApi {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::{MockServer, Script};
+
+    // 本地 mock 服务, 不访问币安
+    async fn mock(script: &str) -> MockServer {
+        let script: Script = toml::from_str(script).unwrap();
+        MockServer::start(script, "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap()
+    }
+
+    fn futures_order(symbol: &str, quantity: f64, price: f64) -> FuturesOrderRequest {
+        FuturesOrderRequest {
+            symbol: symbol.to_string(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: Some(quantity),
+            price: Some(price),
+            time_in_force: Some(TimeInForce::FOK),
+            reduce_only: None,
+            recv_window: None,
+        }
+    }
+
+    fn get_order(symbol: &str, order_id: u64) -> FuturesGetOrderRequest {
+        FuturesGetOrderRequest {
+            symbol: symbol.to_string(),
+            order_id: Some(order_id.to_string()),
+            orig_client_order_id: None,
+        }
+    }
 
     #[tokio::test]
     async fn test_binance_api() {
-        let api = MyApi::new();
-        // let time = api.get_server_time().await;
-        // println!("{:?}", time);
-        /*let transfer = api
+        let server = mock(
+            r#"
+            [[symbols]]
+            market = "spot"
+            symbol = "BNBUSDT"
+            [[klines]]
+            market = "spot"
+            symbol = "BNBUSDT"
+            interval = "1m"
+            start = 1700000000000
+            closes = [500.0, 501.0, 499.5]
+            "#,
+        )
+        .await;
+        let api = server.api();
+        assert!(api.get_server_time().await.unwrap().server_time > 0);
+        let info = api.get_symbol_info("spot", "BNBUSDT").await.unwrap();
+        assert!(info.is_trading());
+        let KlineSummaries::AllKlineSummaries(klines) = api
+            .get_klines("spot", "BNBUSDT", "1m", 2, None, None)
+            .await
+            .unwrap();
+        assert_eq!(klines.len(), 2);
+        assert_eq!(klines[1].open, 501.0);
+        assert_eq!(klines[1].close, 499.5);
+
+        let tran = api
             .universal_transfer("USDT".to_string(), 1.0, UniversalTransferType::MainUmfuture)
-            .await;
-        println!("{:?}", transfer);*/
-
-        /*let x = api
-            .place_order(OrderRequest {
-                symbol: "BNBUSDT".to_string(),
-                quantity: Some(0.02),
-                price: Some(500.0),
-                order_type: OrderType::Limit,
-                side: OrderSide::Buy,
-                time_in_force: Some(TimeInForce::FOK),
-                ..OrderRequest::default()
-            })
-            .await;
-        println!("{:?}", x);*/
-
-        let s = api
-            .order_status(OrderStatusRequest {
-                symbol: "BNBUSDT".to_string(),
-                order_id: Some(5096738273),
-                orig_client_order_id: None,
-                recv_window: None,
-            })
-            .await;
-        println!("{:?}", s);
+            .await
+            .unwrap();
+        assert_eq!(server.transfers()[0].tran_id, tran.tran_id);
+        assert_eq!(server.transfers()[0].transfer_type, "MAIN_UMFUTURE");
+    }
+
+    #[tokio::test]
+    async fn test_binance_signature() {
+        let server = mock("").await;
+        let api =
+            MyApi::new_with_config("mock".to_string(), Signer::hmac("wrong"), &server.config());
+        let err = api
+            .futures_place_order(futures_order("ETHUSDT", 0.1, 3950.0))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BinanceError { response } if response.code == -1022));
+
+        let api =
+            MyApi::new_with_config("wrong".to_string(), Signer::hmac("mock"), &server.config());
+        let err = api
+            .futures_place_order(futures_order("ETHUSDT", 0.1, 3950.0))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Unauthorized));
+        assert!(server.orders().is_empty());
     }
 
     #[tokio::test]
     async fn test_binance_futures_place_order() {
-        let api = MyApi::new();
-        let transaction = api
-            .futures_place_order(FuturesOrderRequest {
-                symbol: "ETHUSDT".to_string(),
-                side: OrderSide::Buy,
-                order_type: OrderType::Limit,
-                quantity: Some(0.1),
-                price: Some(3950.0),
-                time_in_force: Some(TimeInForce::GTC),
-                recv_window: None,
-            })
-            .await;
-        println!("{:?}", transaction);
+        let server = mock(
+            r#"
+            [[orders]]
+            market = "futures"
+            behavior = "expire"
+            times = 1
+            [[orders]]
+            market = "futures"
+            behavior = "error"
+            code = -2019
+            msg = "Margin is insufficient."
+            times = 1
+            "#,
+        )
+        .await;
+        let api = server.api();
+        let expired = api
+            .futures_place_order(futures_order("ETHUSDT", 0.1, 3950.0))
+            .await
+            .unwrap();
+        assert_eq!(expired.status, "EXPIRED");
+        assert_eq!(expired.executed_qty, 0.0);
+
+        let err = api
+            .futures_place_order(futures_order("ETHUSDT", 0.1, 3950.0))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BinanceError { response } if response.code == -2019));
+
+        // 规则用完后全部成交
+        let filled = api
+            .futures_place_order(futures_order("ETHUSDT", 0.1, 3950.0))
+            .await
+            .unwrap();
+        assert_eq!(filled.status, "FILLED");
+        assert_eq!(filled.executed_qty, 0.1);
+        assert_eq!(filled.avg_price, 3950.0);
     }
 
     #[tokio::test]
     async fn test_binance_futures_order_status() {
-        let api = MyApi::new();
+        let server = mock("").await;
+        let api = server.api();
+        let placed = api
+            .futures_place_order(futures_order("ETHUSDT", 0.1, 3950.0))
+            .await
+            .unwrap();
         let transaction = api
-            .futures_order_status(FuturesGetOrderRequest {
-                symbol: "ETHUSDT".to_string(),
-                order_id: Some("8389765661061305976".to_string()),
-                orig_client_order_id: None,
-            })
-            .await;
-        println!("{:?}", transaction);
+            .futures_order_status(get_order("ETHUSDT", placed.order_id))
+            .await
+            .unwrap();
+        assert_eq!(transaction.status, "FILLED");
+
+        let trades = api
+            .get_order_trades("futures", "ETHUSDT", &placed.order_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(trades.len(), 1);
+        assert!(trades[0].buyer);
+        assert!((trades[0].commission - 0.395).abs() < 1e-9);
+
+        let err = api
+            .futures_order_status(get_order("ETHUSDT", placed.order_id + 100))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BinanceError { response } if response.code == -2013));
     }
 
     #[tokio::test]
     async fn test_binance_delivery_place_order() {
-        let api = MyApi::new();
+        let server = mock(
+            r#"
+            [[orders]]
+            market = "delivery"
+            behavior = "partial"
+            fill_ratio = 0.4
+            "#,
+        )
+        .await;
+        let api = server.api();
         let transaction = api
-            .delivery_place_order(FuturesOrderRequest {
-                symbol: "ETHUSD_PERP".to_string(),
-                side: OrderSide::Buy,
-                order_type: OrderType::Limit,
-                quantity: Some(1.0),
-                price: Some(140.0),
-                time_in_force: Some(TimeInForce::GTC),
-                recv_window: None,
-            })
-            .await;
-        println!("{:?}", transaction);
+            .delivery_place_order(futures_order("ETHUSD_PERP", 10.0, 140.0))
+            .await
+            .unwrap();
+        assert_eq!(transaction.status, "PARTIALLY_FILLED");
+        assert_eq!(transaction.executed_qty, 4.0);
+
+        // 币本位手续费按面值折算成币
+        let trades = api
+            .get_order_trades("delivery", "ETHUSD_PERP", &transaction.order_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(trades[0].commission_asset, "ETH");
+        assert!((trades[0].commission - 4.0 * 10.0 / 140.0 * 0.001).abs() < 1e-12);
     }
 
     #[tokio::test]
     async fn test_binance_delivery_order_status() {
-        let api = MyApi::new();
+        let server = mock(
+            r#"
+            [[orders]]
+            behavior = "new"
+            "#,
+        )
+        .await;
+        let api = server.api();
+        let placed = api
+            .delivery_place_order(futures_order("ETHUSD_PERP", 1.0, 140.0))
+            .await
+            .unwrap();
         let transaction = api
-            .delivery_order_status(FuturesGetOrderRequest {
-                symbol: "ETHUSD_PERP".to_string(),
-                order_id: Some("73992692757".to_string()),
-                orig_client_order_id: None,
-            })
-            .await;
-        println!("{:?}", transaction);
+            .delivery_order_status(get_order("ETHUSD_PERP", placed.order_id))
+            .await
+            .unwrap();
+        assert_eq!(transaction.status, "NEW");
+
+        let canceled = api
+            .delivery_cancel_order(get_order("ETHUSD_PERP", placed.order_id))
+            .await
+            .unwrap();
+        assert_eq!(canceled.status, "CANCELED");
+        assert!(api
+            .delivery_cancel_order(get_order("ETHUSD_PERP", placed.order_id))
+            .await
+            .is_err());
     }
 }
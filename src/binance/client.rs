@@ -1,21 +1,21 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use boolinator::Boolinator;
-use hex::encode as hex_encode;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, USER_AGENT};
 use reqwest::Response;
 use reqwest::StatusCode;
-use ring::hmac;
 use serde::de;
 use serde::de::DeserializeOwned;
 
 use crate::binance::errors::*;
+use crate::binance::signer::Signer;
 use crate::binance::util::{build_request_p, build_signed_request_p};
+use crate::helper::metrics;
 
 #[derive(Clone)]
 pub struct Client {
     api_key: String,
-    secret_key: String,
+    signer: Signer,
     inner: reqwest::Client,
     host: String,
 }
@@ -29,15 +29,26 @@ impl Client {
         secret_key: Option<String>,
         host: String,
         timeout: Option<u64>,
+    ) -> Self {
+        // Does it ever make sense for api_key and secret_key to be ""?
+        let signer = Signer::hmac(&secret_key.unwrap_or_default());
+        Self::new_with_signer(api_key, signer, host, timeout)
+    }
+
+    /// Returns a client signing requests with the given HMAC, Ed25519 or RSA signer
+    pub fn new_with_signer(
+        api_key: Option<String>,
+        signer: Signer,
+        host: String,
+        timeout: Option<u64>,
     ) -> Self {
         let mut builder: reqwest::ClientBuilder = reqwest::ClientBuilder::new();
         if let Some(timeout_secs) = timeout {
             builder = builder.timeout(Duration::from_secs(timeout_secs))
         }
         Client {
-            // Does it ever make sense for api_key and secret_key to be ""?
             api_key: api_key.unwrap_or_else(|| "".into()),
-            secret_key: secret_key.unwrap_or_else(|| "".into()),
+            signer,
             inner: builder.build().unwrap(),
             host,
         }
@@ -48,15 +59,9 @@ impl Client {
         endpoint: &str,
         request: &str,
     ) -> Result<T> {
-        let url = self.sign_request(endpoint, request);
-        let response = self
-            .inner
-            .get(&url)
-            .headers(self.build_headers(true)?)
-            .send()
-            .await?;
-
-        self.handler(response).await
+        let url = self.sign_request(endpoint, request)?;
+        let request = self.inner.get(&url).headers(self.build_headers(true)?);
+        self.send(endpoint, request).await
     }
 
     pub async fn get_signed_d<T: de::DeserializeOwned>(
@@ -82,15 +87,9 @@ impl Client {
         endpoint: &str,
         request: &str,
     ) -> Result<T> {
-        let url = self.sign_request(endpoint, request);
-        let response = self
-            .inner
-            .post(&url)
-            .headers(self.build_headers(true)?)
-            .send()
-            .await?;
-
-        self.handler(response).await
+        let url = self.sign_request(endpoint, request)?;
+        let request = self.inner.post(&url).headers(self.build_headers(true)?);
+        self.send(endpoint, request).await
     }
 
     pub async fn post_signed_d<T: de::DeserializeOwned>(
@@ -126,15 +125,9 @@ impl Client {
         endpoint: &str,
         request: &str,
     ) -> Result<T> {
-        let url = self.sign_request(endpoint, request);
-        let response = self
-            .inner
-            .delete(&url)
-            .headers(self.build_headers(true)?)
-            .send()
-            .await?;
-
-        self.handler(response).await
+        let url = self.sign_request(endpoint, request)?;
+        let request = self.inner.delete(&url).headers(self.build_headers(true)?);
+        self.send(endpoint, request).await
     }
 
     pub async fn get<T: DeserializeOwned>(
@@ -146,9 +139,9 @@ impl Client {
             .map(|r| format!("{}{}?{}", self.host, endpoint, r))
             .unwrap_or_else(|| format!("{}{}", self.host, endpoint));
 
-        let response = self.inner.get(&url).send().await?;
+        let request = self.inner.get(&url);
 
-        self.handler(response).await
+        self.send(endpoint, request).await
     }
 
     pub async fn get_p<T: DeserializeOwned>(
@@ -181,14 +174,9 @@ impl Client {
             .map(|s| format!("{}{}?symbol={}", self.host, endpoint, s))
             .unwrap_or_else(|| format!("{}{}", self.host, endpoint));
 
-        let response = self
-            .inner
-            .post(url)
-            .headers(self.build_headers(false)?)
-            .send()
-            .await?;
+        let request = self.inner.post(url).headers(self.build_headers(false)?);
 
-        self.handler(response).await
+        self.send(endpoint, request).await
     }
 
     pub async fn put<T: DeserializeOwned>(
@@ -202,9 +190,8 @@ impl Client {
             .unwrap_or_else(|| format!("listenKey={listen_key}"));
         let headers = self.build_headers(false)?;
         let url = format!("{}{}?{}", self.host, endpoint, data);
-        let response = self.inner.put(&url).headers(headers).send().await?;
-
-        self.handler(response).await
+        let request = self.inner.put(&url).headers(headers);
+        self.send(endpoint, request).await
     }
 
     pub async fn delete<T: DeserializeOwned>(
@@ -217,26 +204,19 @@ impl Client {
             .map(|s| format!("listenKey={listen_key}&symbol={s}"))
             .unwrap_or_else(|| format!("listenKey={listen_key}"));
         let url = format!("{}{}?{}", self.host, endpoint, data);
-        let response = self
-            .inner
-            .delete(url)
-            .headers(self.build_headers(false)?)
-            .send()
-            .await?;
-
-        self.handler(response).await
+        let request = self.inner.delete(url).headers(self.build_headers(false)?);
+        self.send(endpoint, request).await
     }
 
     // Request must be signed
-    fn sign_request(&self, endpoint: &str, request: &str) -> String {
-        let signed_key = hmac::Key::new(hmac::HMAC_SHA256, self.secret_key.as_bytes());
-        let signature = hex_encode(hmac::sign(&signed_key, request.as_bytes()).as_ref());
+    fn sign_request(&self, endpoint: &str, request: &str) -> Result<String> {
+        let signature = self.signer.sign(request)?;
         let url = format!(
             "{}{}?{}&signature={}",
             self.host, endpoint, request, signature
         );
 
-        url
+        Ok(url)
     }
 
     fn build_headers(&self, content_type: bool) -> Result<HeaderMap> {
@@ -262,6 +242,21 @@ impl Client {
         Ok(header)
     }
 
+    // 记录请求耗时及错误数
+    async fn send<T: de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let result = match request.send().await {
+            Ok(response) => self.handler(response).await,
+            Err(e) => Err(e.into()),
+        };
+        metrics::rest(endpoint, start.elapsed(), result.is_ok());
+        result
+    }
+
     async fn handler<T: de::DeserializeOwned>(&self, response: Response) -> Result<T> {
         match response.status() {
             StatusCode::OK => Ok(response.json().await?),
@@ -134,6 +134,24 @@ impl Config {
         self
     }
 
+    pub fn set_delivery_rest_api_endpoint<T: Into<String>>(mut self, delivery_rest_api_endpoint: T) -> Self {
+        self.delivery_rest_api_endpoint = delivery_rest_api_endpoint.into();
+        self
+    }
+
+    pub fn set_delivery_ws_endpoint<T: Into<String>>(mut self, delivery_ws_endpoint: T) -> Self {
+        self.delivery_ws_endpoint = delivery_ws_endpoint.into();
+        self
+    }
+
+    /// Endpoints of `[binance_endpoint]` in config.toml (a local mock server), the public Binance endpoints when not configured
+    pub fn with_endpoints() -> Config {
+        match &crate::conf::C.binance_endpoint {
+            Some(endpoint) => crate::mock::config(&endpoint.rest, &endpoint.ws),
+            None => Config::default(),
+        }
+    }
+
     /// Sets the client timeout
     ///
     /// # Arguments
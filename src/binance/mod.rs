@@ -1,8 +1,10 @@
+pub mod account;
 pub mod api;
 pub mod client;
 pub mod config;
 pub mod errors;
 pub mod rest_model;
+pub mod signer;
 pub mod util;
 pub mod websockets;
 pub mod ws_model;
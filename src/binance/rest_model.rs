@@ -255,6 +255,22 @@ pub struct Order {
     pub orig_quote_order_qty: f64,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderCanceled {
+    pub symbol: String,
+    pub order_id: u64,
+    pub orig_client_order_id: String,
+    #[serde(with = "string_or_float")]
+    pub price: f64,
+    #[serde(with = "string_or_float")]
+    pub orig_qty: f64,
+    #[serde(with = "string_or_float")]
+    pub executed_qty: f64,
+    pub status: OrderStatus,
+    pub side: OrderSide,
+}
+
 pub mod string_or_float {
     use std::fmt;
 
@@ -415,7 +431,39 @@ pub enum KlineSummaries {
     AllKlineSummaries(Vec<KlineSummary>),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Account trade, spot myTrades and USDⓈ-M/COIN-M userTrades
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Trade {
+    pub symbol: String,
+    pub id: u64,
+    pub order_id: u64,
+    #[serde(with = "string_or_float")]
+    pub price: f64,
+    /// Base quantity, contracts for COIN-M
+    #[serde(with = "string_or_float")]
+    pub qty: f64,
+    #[serde(with = "string_or_float")]
+    pub commission: f64,
+    pub commission_asset: String,
+    pub time: u64,
+    #[serde(alias = "isBuyer")]
+    pub buyer: bool,
+}
+
+/// USDⓈ-M/COIN-M income history
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Income {
+    pub symbol: String,
+    pub income_type: String,
+    #[serde(with = "string_or_float")]
+    pub income: f64,
+    pub asset: String,
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct KlineSummary {
     pub open_time: i64,
@@ -430,3 +478,69 @@ pub struct KlineSummary {
     pub taker_buy_base_asset_volume: f64,
     pub taker_buy_quote_asset_volume: f64,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeInformation {
+    pub server_time: u64,
+    pub symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolInfo {
+    pub symbol: String,
+    /// Spot and USDⓈ-M status
+    pub status: Option<String>,
+    /// COIN-M status
+    pub contract_status: Option<String>,
+    /// COIN-M contract size in USD
+    pub contract_size: Option<u64>,
+    /// USDⓈ-M and COIN-M delivery time in ms, far future for perpetuals
+    pub delivery_date: Option<i64>,
+    pub filters: Vec<Filters>,
+}
+
+impl SymbolInfo {
+    pub fn is_trading(&self) -> bool {
+        self.status.as_deref().or(self.contract_status.as_deref()) == Some("TRADING")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "filterType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Filters {
+    #[serde(rename_all = "camelCase")]
+    PriceFilter {
+        #[serde(with = "string_or_float")]
+        min_price: f64,
+        #[serde(with = "string_or_float")]
+        max_price: f64,
+        #[serde(with = "string_or_float")]
+        tick_size: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    LotSize {
+        #[serde(with = "string_or_float")]
+        min_qty: f64,
+        #[serde(with = "string_or_float")]
+        max_qty: f64,
+        #[serde(with = "string_or_float")]
+        step_size: f64,
+    },
+    /// Spot uses `minNotional`, USDⓈ-M uses `notional`
+    #[serde(rename_all = "camelCase")]
+    MinNotional {
+        #[serde(default, with = "string_or_float_opt")]
+        min_notional: Option<f64>,
+        #[serde(default, with = "string_or_float_opt")]
+        notional: Option<f64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Notional {
+        #[serde(default, with = "string_or_float_opt")]
+        min_notional: Option<f64>,
+    },
+    #[serde(other)]
+    Others,
+}
@@ -69,6 +69,8 @@ fn combined_stream(streams: Vec<String>) -> String { streams.join("/") }
 pub struct WebSockets<'a, WE> {
     pub socket: Option<(WebSocketStream<MaybeTlsStream<TcpStream>>, Response)>,
     handler: Box<dyn FnMut(WE) -> Result<()> + 'a + Send>,
+    // 解析前的原始 text 帧
+    raw_handler: Option<Box<dyn FnMut(&str) + 'a + Send>>,
     conf: Config,
 }
 
@@ -93,10 +95,19 @@ impl<'a, WE: serde::de::DeserializeOwned> WebSockets<'a, WE> {
         WebSockets {
             socket: None,
             handler: Box::new(handler),
+            raw_handler: None,
             conf,
         }
     }
 
+    /// Receive every raw text frame before it is parsed, including frames that fail to parse
+    pub fn set_raw_handler<Callback>(&mut self, handler: Callback)
+    where
+        Callback: FnMut(&str) + 'a + Send,
+    {
+        self.raw_handler = Some(Box::new(handler));
+    }
+
     /// Connect to multiple websocket endpoints
     /// N.B: WE has to be CombinedStreamEvent
     pub async fn connect_multiple(&mut self, endpoints: Vec<String>) -> Result<()> {
@@ -122,7 +133,7 @@ impl<'a, WE: serde::de::DeserializeOwned> WebSockets<'a, WE> {
         self.handle_connect(url).await
     }
 
-    /// Connect to a futures websocket endpoint
+    /// Connect to a delivery websocket endpoint
     pub async fn connect_delivery(&mut self, endpoint: &str) -> Result<()> {
         let wss: String = format!("{}/{}/{}", self.conf.delivery_ws_endpoint, WS_ENDPOINT, endpoint);
         let url = Url::parse(&wss)?;
@@ -173,6 +184,9 @@ impl<'a, WE: serde::de::DeserializeOwned> WebSockets<'a, WE> {
                         if msg.is_empty() {
                             return Ok(());
                         }
+                        if let Some(raw) = self.raw_handler.as_mut() {
+                            raw(msg.as_str());
+                        }
                         match from_str::<WE>(msg.as_str()) {
                             Ok(event) => {
                                 if let Err(e) = (self.handler)(event) {
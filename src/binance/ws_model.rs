@@ -1,3 +1,4 @@
+use crate::binance::rest_model::KlineSummary;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -94,6 +95,75 @@ impl From<Box<KucoinTickerMessage>> for BinanceTickerEvent {
     }
 }
 
+/// 组合流 /stream?streams= 的消息
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CombinedStreamEvent<T> {
+    pub stream: String,
+    pub data: T,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KlineEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "k")]
+    pub kline: Kline,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Kline {
+    #[serde(rename = "t")]
+    pub open_time: i64,
+    #[serde(rename = "T")]
+    pub close_time: i64,
+    #[serde(rename = "i")]
+    pub interval: String,
+    #[serde(rename = "o")]
+    pub open: String,
+    #[serde(rename = "c")]
+    pub close: String,
+    #[serde(rename = "h")]
+    pub high: String,
+    #[serde(rename = "l")]
+    pub low: String,
+    #[serde(rename = "v")]
+    pub volume: String,
+    #[serde(rename = "n")]
+    pub number_of_trades: i64,
+    // 是否已收盘
+    #[serde(rename = "x")]
+    pub is_final: bool,
+    #[serde(rename = "q")]
+    pub quote_asset_volume: String,
+    #[serde(rename = "V")]
+    pub taker_buy_base_asset_volume: String,
+    #[serde(rename = "Q")]
+    pub taker_buy_quote_asset_volume: String,
+}
+
+impl From<&Kline> for KlineSummary {
+    fn from(k: &Kline) -> Self {
+        let f = |v: &String| v.parse::<f64>().unwrap_or_default();
+        KlineSummary {
+            open_time: k.open_time,
+            open: f(&k.open),
+            high: f(&k.high),
+            low: f(&k.low),
+            close: f(&k.close),
+            volume: f(&k.volume),
+            close_time: k.close_time,
+            quote_asset_volume: f(&k.quote_asset_volume),
+            number_of_trades: k.number_of_trades,
+            taker_buy_base_asset_volume: f(&k.taker_buy_base_asset_volume),
+            taker_buy_quote_asset_volume: f(&k.taker_buy_quote_asset_volume),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct SubscribeResponse {
     success: bool,
@@ -124,6 +194,8 @@ impl Platform {
 pub enum Market {
     Spot,
     Futures,
+    // 币本位交割合约
+    Delivery,
 }
 
 impl Market {
@@ -131,6 +203,7 @@ impl Market {
         match self {
             Market::Spot => "spot",
             Market::Futures => "futures",
+            Market::Delivery => "delivery",
         }
     }
 }
@@ -1,5 +1,8 @@
 pub mod redis_key;
+pub mod secret;
 
+use crate::binance::signer::KeyType;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -12,6 +15,32 @@ pub struct LogConfig {
     pub dir: String,
     pub prefix: String,
     pub level: String,
+    // json/text, 不配置时控制台为 text, 文件为 json
+    #[serde(default)]
+    pub format: Option<String>,
+    // 单个文件上限(MB), 超过或跨天切分
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    // 保留切分后的文件数
+    #[serde(default = "default_log_keep_files")]
+    pub keep_files: usize,
+}
+
+impl LogConfig {
+    pub fn json(&self) -> bool {
+        match &self.format {
+            Some(format) => format == "json",
+            None => self.pattern != "console",
+        }
+    }
+}
+
+fn default_log_max_size_mb() -> u64 {
+    100
+}
+
+fn default_log_keep_files() -> usize {
+    30
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -22,35 +51,384 @@ pub struct RedisConfig {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RocksDbConfig {
     pub path: String,
+    // K线保留天数, 0 不清理
+    #[serde(default = "default_rocksdb_retention_days")]
+    pub retention_days: u64,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+fn default_rocksdb_retention_days() -> u64 {
+    30
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct MysqlConfig {
     pub url: String,
+    // 启动时自动执行未应用的表结构迁移, 否则拒绝启动
+    #[serde(default)]
+    pub auto_migrate: bool,
 }
 
+// 本地开发及测试: sqlite://arbitrage.db 或 sqlite::memory:
 #[derive(Debug, Deserialize, Serialize)]
+pub struct SqliteConfig {
+    pub url: String,
+}
+
+// api_key/secret_key 支持 `enc:` 加密存储, 使用时在内存中解密
+// key_type 为 ed25519/rsa 时 secret_key 为 PEM 私钥
+#[derive(Deserialize, Serialize)]
 pub struct BinanceApiConfig {
     pub api_key: String,
     pub secret_key: String,
+    #[serde(default)]
+    pub key_type: KeyType,
+}
+
+impl BinanceApiConfig {
+    pub fn api_key(&self) -> anyhow::Result<String> {
+        secret::reveal(&self.api_key)
+    }
+
+    pub fn secret_key(&self) -> anyhow::Result<String> {
+        secret::reveal(&self.secret_key)
+    }
+}
+
+impl fmt::Debug for BinanceApiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BinanceApiConfig")
+            .field("api_key", &secret::redact(&self.api_key))
+            .field("secret_key", &secret::redact(&self.secret_key))
+            .field("key_type", &self.key_type)
+            .finish()
+    }
+}
+
+// 多账户配置, 按 user_id 区分
+#[derive(Deserialize, Serialize)]
+pub struct AccountConfig {
+    pub user_id: i64,
+    pub api_key: String,
+    pub secret_key: String,
+    #[serde(default)]
+    pub key_type: KeyType,
+}
+
+impl AccountConfig {
+    pub fn api_key(&self) -> anyhow::Result<String> {
+        secret::reveal(&self.api_key)
+    }
+
+    pub fn secret_key(&self) -> anyhow::Result<String> {
+        secret::reveal(&self.secret_key)
+    }
+}
+
+impl fmt::Debug for AccountConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountConfig")
+            .field("user_id", &self.user_id)
+            .field("api_key", &secret::redact(&self.api_key))
+            .field("secret_key", &secret::redact(&self.secret_key))
+            .field("key_type", &self.key_type)
+            .finish()
+    }
+}
+
+// 币安地址, 配置后所有市场的 REST 及 websocket 使用该地址, 如本地 mock_binance
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BinanceEndpointConfig {
+    pub rest: String,
+    pub ws: String,
+}
+
+// 管理接口, 请求头 Authorization: Bearer <token>
+#[derive(Deserialize, Serialize)]
+pub struct AdminConfig {
+    pub listen: String,
+    pub token: String,
+}
+
+impl AdminConfig {
+    pub fn token(&self) -> anyhow::Result<String> {
+        secret::reveal(&self.token)
+    }
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("listen", &self.listen)
+            .field("token", &secret::redact(&self.token))
+            .finish()
+    }
+}
+
+// prometheus 指标, GET /metrics
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MetricsConfig {
+    pub listen: String,
+}
+
+// 策略调度
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SchedulerConfig {
+    // 是否执行期现套利策略(下单), 默认关闭
+    #[serde(default)]
+    pub execute_strategies: bool,
+    // 执行策略的 worker 数量
+    #[serde(default = "default_scheduler_workers")]
+    pub workers: usize,
+    // 定时唤醒所有运行中策略的间隔(ms)
+    #[serde(default = "default_scheduler_interval_ms")]
+    pub interval_ms: u64,
+    // 下单后再次唤醒检查订单状态的延迟(ms)
+    #[serde(default = "default_scheduler_order_check_ms")]
+    pub order_check_ms: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            execute_strategies: false,
+            workers: default_scheduler_workers(),
+            interval_ms: default_scheduler_interval_ms(),
+            order_check_ms: default_scheduler_order_check_ms(),
+        }
+    }
+}
+
+fn default_scheduler_workers() -> usize {
+    10
+}
+
+fn default_scheduler_interval_ms() -> u64 {
+    1000
+}
+
+fn default_scheduler_order_check_ms() -> u64 {
+    200
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KlineConfig {
+    // 每个 (market, symbol, interval) 在内存中保留的K线数量
+    #[serde(default = "default_kline_history")]
+    pub history: usize,
+    // 启动时订阅, 策略使用的K线运行时自动订阅
+    #[serde(default)]
+    pub subscriptions: Vec<KlineSubscription>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KlineSubscription {
+    // spot/futures/delivery
+    pub market: String,
+    pub symbol: String,
+    pub interval: String,
+}
+
+impl Default for KlineConfig {
+    fn default() -> Self {
+        KlineConfig {
+            history: default_kline_history(),
+            subscriptions: vec![],
+        }
+    }
+}
+
+fn default_kline_history() -> usize {
+    1000
+}
+
+// 策略盈亏计算
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PnlConfig {
+    // 运行中策略重新计算的间隔(秒), 已完成策略在完成后计算一次
+    #[serde(default = "default_pnl_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for PnlConfig {
+    fn default() -> Self {
+        PnlConfig {
+            interval_secs: default_pnl_interval_secs(),
+        }
+    }
+}
+
+fn default_pnl_interval_secs() -> u64 {
+    300
+}
+
+// 交割合约年化基差
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BasisConfig {
+    // 距交割不足该时间(秒)不计算年化, 临近交割时年化值被放大失去意义
+    #[serde(default = "default_basis_min_expiry_secs")]
+    pub min_expiry_secs: i64,
+}
+
+impl Default for BasisConfig {
+    fn default() -> Self {
+        BasisConfig {
+            min_expiry_secs: default_basis_min_expiry_secs(),
+        }
+    }
+}
+
+fn default_basis_min_expiry_secs() -> i64 {
+    24 * 60 * 60
+}
+
+// Redis 价格缓存, 按平台、市场分 hash 保存最新 ticker
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PriceCacheConfig {
+    // hash 最后一次写入后的过期时间(秒), 行情断开后整个市场的缓存失效
+    #[serde(default = "default_price_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    // 读取时该平台、市场超过该值(ms)未收到任何行情视为过期
+    // 按行情而非交易对判断, !miniTicker@arr 等只推送变化交易对的行情中, 成交稀少的交易对不会被误判
+    #[serde(default = "default_price_cache_max_age_ms")]
+    pub max_age_ms: i64,
+    // 价格更新同时发布到 price:<platform>:<market> 频道
+    #[serde(default = "default_price_cache_publish")]
+    pub publish: bool,
+}
+
+impl Default for PriceCacheConfig {
+    fn default() -> Self {
+        PriceCacheConfig {
+            ttl_secs: default_price_cache_ttl_secs(),
+            max_age_ms: default_price_cache_max_age_ms(),
+            publish: default_price_cache_publish(),
+        }
+    }
+}
+
+fn default_price_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_price_cache_max_age_ms() -> i64 {
+    10_000
+}
+
+fn default_price_cache_publish() -> bool {
+    true
+}
+
+// 原始行情录制, feeds 为 <platform>_<market>, 如 binance_spot, 不配置不录制
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RecorderConfig {
+    #[serde(default = "default_recorder_dir")]
+    pub dir: String,
+    #[serde(default)]
+    pub feeds: Vec<String>,
+    // 单个文件未压缩数据上限(MB), 超过或超过 rotate_secs 切分
+    #[serde(default = "default_recorder_max_file_mb")]
+    pub max_file_mb: u64,
+    #[serde(default = "default_recorder_rotate_secs")]
+    pub rotate_secs: u64,
+    // 录制文件总大小上限(MB), 超过删除最早的文件
+    #[serde(default = "default_recorder_max_total_mb")]
+    pub max_total_mb: u64,
+    // 待写盘的帧数上限, 写盘跟不上时丢弃新帧
+    #[serde(default = "default_recorder_channel_capacity")]
+    pub channel_capacity: usize,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        RecorderConfig {
+            dir: default_recorder_dir(),
+            feeds: vec![],
+            max_file_mb: default_recorder_max_file_mb(),
+            rotate_secs: default_recorder_rotate_secs(),
+            max_total_mb: default_recorder_max_total_mb(),
+            channel_capacity: default_recorder_channel_capacity(),
+        }
+    }
+}
+
+fn default_recorder_channel_capacity() -> usize {
+    10000
+}
+
+fn default_recorder_dir() -> String {
+    "data/raw".to_string()
+}
+
+fn default_recorder_max_file_mb() -> u64 {
+    64
+}
+
+fn default_recorder_rotate_secs() -> u64 {
+    3600
+}
+
+fn default_recorder_max_total_mb() -> u64 {
+    1024
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub redis: RedisConfig,
+    // 配置 sqlite 时不使用 mysql
+    #[serde(default)]
     pub mysql: MysqlConfig,
+    pub sqlite: Option<SqliteConfig>,
     pub rocksdb: RocksDbConfig,
     pub log: LogConfig,
     pub binance_api_config: BinanceApiConfig,
+    pub binance_endpoint: Option<BinanceEndpointConfig>,
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    pub admin: Option<AdminConfig>,
+    pub metrics: Option<MetricsConfig>,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    // 自定义策略模板文件, 不配置使用内置模板
+    pub templates: Option<String>,
+    #[serde(default)]
+    pub kline: KlineConfig,
+    #[serde(default)]
+    pub pnl: PnlConfig,
+    #[serde(default)]
+    pub basis: BasisConfig,
+    #[serde(default)]
+    pub recorder: RecorderConfig,
+    #[serde(default)]
+    pub price_cache: PriceCacheConfig,
 }
 
 lazy_static! {
     pub static ref C: Config = init_config().unwrap();
 }
 
-pub fn init_config() -> anyhow::Result<Config> {
+// 环境变量 ARB_CONFIG 指定配置文件, 如回放时使用单独的数据库
+pub fn config_path() -> String {
+    if let Ok(path) = std::env::var("ARB_CONFIG") {
+        return path;
+    }
+    // 单元测试不依赖本地 config.toml
+    if cfg!(test) {
+        return env!("CARGO_MANIFEST_DIR").to_string() + "/src/conf/config.test.toml";
+    }
+    default_config_path()
+}
+
+/// 未设置 ARB_CONFIG 时使用的配置文件, 即实盘配置
+pub fn default_config_path() -> String {
     let config_path = env!("CARGO_MANIFEST_DIR");
-    let path = config_path.to_string() + "/config.toml";
+    config_path.to_string() + "/config.toml"
+}
+
+pub fn init_config() -> anyhow::Result<Config> {
+    load_config(&config_path())
+}
+
+pub fn load_config(path: &str) -> anyhow::Result<Config> {
     let mut file = File::open(path)?;
     let mut str_val = String::new();
     file.read_to_string(&mut str_val)?;
@@ -65,9 +443,29 @@ mod tests {
     #[test]
     fn test_get_config() {
         let c = init_config().unwrap();
-        println!("{:#?}", c.redis.url);
-        println!("{:#?}", c.mysql.url);
-        println!("{:#?}", c.binance_api_config);
-        println!("{:?}", c.rocksdb.path);
+        // 不打印连接串, Debug 输出中不包含密钥
+        let debug = format!("{:?} {:?}", c.binance_api_config, c.accounts);
+        let secrets = std::iter::once(&c.binance_api_config)
+            .flat_map(|b| [&b.api_key, &b.secret_key])
+            .chain(c.accounts.iter().flat_map(|a| [&a.api_key, &a.secret_key]));
+        for secret in secrets.filter(|s| !s.is_empty()) {
+            assert!(!debug.contains(secret.as_str()));
+        }
+        assert!(!c.rocksdb.path.is_empty());
+    }
+
+    #[test]
+    fn test_log_format() {
+        let log = |pattern: &str, format: &str| {
+            let c: LogConfig = toml::from_str(&format!(
+                "pattern = \"{pattern}\"\ndir = \"logs\"\nprefix = \"arb.log\"\nlevel = \"INFO\"\n{format}"
+            ))
+            .unwrap();
+            c.json()
+        };
+        assert!(!log("console", ""));
+        assert!(log("file", ""));
+        assert!(log("console", "format = \"json\""));
+        assert!(!log("file", "format = \"text\""));
     }
 }
@@ -1,4 +1,26 @@
+// 价格缓存 hash: <market>_<platform>_price_v2, field 为交易对
+pub const PRICE_KEY_SUFFIX: &str = "_price_v2";
+// 价格缓存 hash 中记录该行情最后一帧接收时间(ms)的 field, 不与交易对冲突
+pub const PRICE_FEED_UPDATED_FIELD: &str = "_updated";
+// 价格更新频道: price:<platform>:<market>
+pub const PRICE_CHANNEL_PREFIX: &str = "price";
 
+pub fn price_key(platform: &str, market: &str) -> String {
+    format!("{}_{}{}", market, platform, PRICE_KEY_SUFFIX)
+}
 
-pub const BINANCE_PRICE_KEY: &str = "_binance_price_v1";
-pub const BYBIT_PRICE_KEY: &str = "_bybit_price_v1";
\ No newline at end of file
+pub fn price_channel(platform: &str, market: &str) -> String {
+    format!("{}:{}:{}", PRICE_CHANNEL_PREFIX, platform, market)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_key() {
+        assert_eq!(price_key("binance", "delivery"), "delivery_binance_price_v2");
+        assert_eq!(price_key("bybit", "futures"), "futures_bybit_price_v2");
+        assert_eq!(price_channel("kucoin", "futures"), "price:kucoin:futures");
+    }
+}
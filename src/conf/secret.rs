@@ -0,0 +1,182 @@
+use anyhow::anyhow;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fs;
+use toml_edit::{Document, Item, Value};
+
+// 加密值前缀, config.toml 中 `secret_key = "enc:..."`
+pub const ENC_PREFIX: &str = "enc:";
+// 主密钥, 32字节 hex
+pub const MASTER_KEY_ENV: &str = "ARB_MASTER_KEY";
+// 主密钥文件, 内容为 32字节 hex
+pub const MASTER_KEY_FILE_ENV: &str = "ARB_MASTER_KEY_FILE";
+
+pub type MasterKey = [u8; 32];
+
+/// Loads the master key from `ARB_MASTER_KEY`, falling back to the file named by `ARB_MASTER_KEY_FILE`.
+pub fn master_key() -> anyhow::Result<MasterKey> {
+    let hex_key = match std::env::var(MASTER_KEY_ENV) {
+        Ok(k) => k,
+        Err(_) => {
+            let path = std::env::var(MASTER_KEY_FILE_ENV).map_err(|_| {
+                anyhow!("master key not set, use {MASTER_KEY_ENV} or {MASTER_KEY_FILE_ENV}")
+            })?;
+            fs::read_to_string(path)?
+        }
+    };
+    parse_key(hex_key.trim())
+}
+
+pub fn parse_key(hex_key: &str) -> anyhow::Result<MasterKey> {
+    let bytes = hex::decode(hex_key)?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("master key must be 32 bytes hex"))
+}
+
+pub fn generate_key() -> anyhow::Result<String> {
+    let mut key = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| anyhow!("generate master key err"))?;
+    Ok(hex::encode(key))
+}
+
+/// Encrypts `plain` with AES-256-GCM, returns `enc:` + hex(nonce || ciphertext || tag).
+pub fn encrypt(key: &MasterKey, plain: &str) -> anyhow::Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("generate nonce err"))?;
+
+    let mut in_out = plain.as_bytes().to_vec();
+    sealing_key(key)?
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut in_out)
+        .map_err(|_| anyhow!("encrypt err"))?;
+
+    let mut data = nonce.to_vec();
+    data.extend(in_out);
+    Ok(format!("{}{}", ENC_PREFIX, hex::encode(data)))
+}
+
+pub fn decrypt(key: &MasterKey, value: &str) -> anyhow::Result<String> {
+    let data = hex::decode(
+        value
+            .strip_prefix(ENC_PREFIX)
+            .ok_or(anyhow!("value is not encrypted"))?,
+    )?;
+    if data.len() < NONCE_LEN {
+        return Err(anyhow!("encrypted value too short"));
+    }
+
+    let (nonce, cipher) = data.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("invalid nonce"))?;
+    let mut in_out = cipher.to_vec();
+    let plain = sealing_key(key)?
+        .open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map_err(|_| anyhow!("decrypt err, wrong master key or corrupted value"))?;
+    Ok(String::from_utf8(plain.to_vec())?)
+}
+
+/// Returns the plaintext of a config value, decrypting it when it carries the `enc:` prefix.
+pub fn reveal(value: &str) -> anyhow::Result<String> {
+    if value.starts_with(ENC_PREFIX) {
+        decrypt(&master_key()?, value)
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+// 支持 `enc:` 的配置项: (表, 字段), accounts 为表数组
+const SECRET_FIELDS: &[(&str, &str)] = &[
+    ("binance_api_config", "api_key"),
+    ("binance_api_config", "secret_key"),
+    ("accounts", "api_key"),
+    ("accounts", "secret_key"),
+    ("admin", "token"),
+];
+
+/// Re-encrypts the `enc:` values of the known secret fields in the config `text` from `old` key to `new` key,
+/// comments and other values are kept untouched.
+pub fn rotate(old: &MasterKey, new: &MasterKey, text: &str) -> anyhow::Result<(String, usize)> {
+    let mut doc = text.parse::<Document>()?;
+    let mut count = 0;
+    for (table, field) in SECRET_FIELDS {
+        let Some(item) = doc.get_mut(table) else {
+            continue;
+        };
+        let mut values: Vec<&mut Value> = Vec::new();
+        match item {
+            Item::ArrayOfTables(tables) => values.extend(
+                tables
+                    .iter_mut()
+                    .filter_map(|t| t.get_mut(field).and_then(Item::as_value_mut)),
+            ),
+            Item::Value(Value::Array(tables)) => values.extend(
+                tables
+                    .iter_mut()
+                    .filter_map(|t| t.as_inline_table_mut())
+                    .filter_map(|t| t.get_mut(field)),
+            ),
+            item => values.extend(item.get_mut(field).and_then(Item::as_value_mut)),
+        }
+        for value in values {
+            let Some(enc) = value.as_str().filter(|v| v.starts_with(ENC_PREFIX)) else {
+                continue;
+            };
+            let rotated = encrypt(new, &decrypt(old, enc)?)?;
+            let decor = value.decor().clone();
+            *value = Value::from(rotated);
+            *value.decor_mut() = decor;
+            count += 1;
+        }
+    }
+    Ok((doc.to_string(), count))
+}
+
+/// Masks a secret for Debug/log output.
+pub fn redact(value: &str) -> String {
+    if value.is_empty() {
+        return "".to_string();
+    }
+    "******".to_string()
+}
+
+fn sealing_key(key: &MasterKey) -> anyhow::Result<LessSafeKey> {
+    let unbound = UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow!("invalid master key"))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_rotate() {
+        let old = parse_key(&generate_key().unwrap()).unwrap();
+        let new = parse_key(&generate_key().unwrap()).unwrap();
+
+        let enc = encrypt(&old, "my-secret").unwrap();
+        assert!(enc.starts_with(ENC_PREFIX));
+        assert_eq!(decrypt(&old, &enc).unwrap(), "my-secret");
+        assert!(decrypt(&new, &enc).is_err());
+
+        // 只轮换已知的密钥字段, 其余 `enc:` 文本及注释保持不变
+        let other = encrypt(&old, "other").unwrap();
+        let text = format!(
+            "# api_key = \"{other}\"\n[binance_api_config]\napi_key = \"{enc}\" # key\nsecret_key = \"plain\"\n\n\
+            [redis]\nurl = \"{other}\"\n\n[[accounts]]\nuser_id = 1\nsecret_key = \"{enc}\"\n"
+        );
+        let (rotated, count) = rotate(&old, &new, &text).unwrap();
+        assert_eq!(count, 2);
+        assert!(rotated.starts_with(&format!("# api_key = \"{other}\"\n")));
+        assert!(rotated.contains("secret_key = \"plain\"\n"));
+        assert!(rotated.contains(&format!("url = \"{other}\"\n")));
+        let c: toml::Value = toml::from_str(&rotated).unwrap();
+        let api_key = c["binance_api_config"]["api_key"].as_str().unwrap();
+        assert!(rotated.contains(&format!("api_key = \"{api_key}\" # key\n")));
+        assert_eq!(decrypt(&new, api_key).unwrap(), "my-secret");
+        let secret_key = c["accounts"][0]["secret_key"].as_str().unwrap();
+        assert_eq!(decrypt(&new, secret_key).unwrap(), "my-secret");
+    }
+}
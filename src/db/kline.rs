@@ -0,0 +1,86 @@
+use crate::binance::rest_model::KlineSummary;
+use crate::db::get_db;
+use anyhow::anyhow;
+use rocksdb::{
+    BoundColumnFamily, DBWithThreadMode, IteratorMode, MultiThreaded, Options, WriteBatch,
+};
+use std::sync::Arc;
+
+// K线按 (market, symbol, interval) 分列族存储, key 为 open_time 大端字节, 按时间有序追加
+// 未收盘的K线 open_time 不变, 再次写入时覆盖
+
+fn cf_name(market: &str, symbol: &str, interval: &str) -> String {
+    format!("kline_{}_{}_{}", market, symbol, interval)
+}
+
+fn key(open_time: i64) -> [u8; 8] {
+    (open_time.max(0) as u64).to_be_bytes()
+}
+
+fn with_cf<R>(
+    market: &str,
+    symbol: &str,
+    interval: &str,
+    f: impl FnOnce(&DBWithThreadMode<MultiThreaded>, &Arc<BoundColumnFamily>) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let db = get_db()?.rocksdb()?;
+    let name = cf_name(market, symbol, interval);
+    if db.cf_handle(&name).is_none() {
+        // 并发创建时另一方已创建成功
+        if let Err(e) = db.create_cf(&name, &Options::default()) {
+            if db.cf_handle(&name).is_none() {
+                return Err(e.into());
+            }
+        }
+    }
+    let cf = db
+        .cf_handle(&name)
+        .ok_or(anyhow!("column family {} not found", name))?;
+    f(db, &cf)
+}
+
+/// 写入K线, 已存在的 open_time 覆盖
+pub fn append(
+    market: &str,
+    symbol: &str,
+    interval: &str,
+    klines: &[KlineSummary],
+) -> anyhow::Result<()> {
+    if klines.is_empty() {
+        return Ok(());
+    }
+    with_cf(market, symbol, interval, |db, cf| {
+        let mut batch = WriteBatch::default();
+        for k in klines {
+            batch.put_cf(cf, key(k.open_time), bincode::serialize(k)?);
+        }
+        db.write(batch)?;
+        Ok(())
+    })
+}
+
+/// 最近 n 条K线, 按 open_time 升序
+pub fn last(
+    market: &str,
+    symbol: &str,
+    interval: &str,
+    n: usize,
+) -> anyhow::Result<Vec<KlineSummary>> {
+    with_cf(market, symbol, interval, |db, cf| {
+        let mut klines = Vec::with_capacity(n);
+        for item in db.iterator_cf(cf, IteratorMode::End).take(n) {
+            let (_, value) = item?;
+            klines.push(bincode::deserialize::<KlineSummary>(&value)?);
+        }
+        klines.reverse();
+        Ok(klines)
+    })
+}
+
+/// 删除 open_time 早于 before 的K线
+pub fn trim(market: &str, symbol: &str, interval: &str, before: i64) -> anyhow::Result<()> {
+    with_cf(market, symbol, interval, |db, cf| {
+        db.delete_range_cf(cf, key(0), key(before))?;
+        Ok(())
+    })
+}
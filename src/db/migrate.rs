@@ -0,0 +1,313 @@
+use crate::conf::C;
+use crate::db::{get_db, Pool};
+use anyhow::anyhow;
+use log::{info, warn};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{Database, Pool as SqlxPool};
+
+// 表结构迁移, 编译时嵌入 migrations 目录, mysql/sqlite 各一套
+static MYSQL_MIGRATOR: Migrator = sqlx::migrate!("./migrations/mysql");
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+// 初始表结构, 使用 create table if not exists, 已手动建表的数据库会跳过建表
+const INIT_VERSION: i64 = 20240601000001;
+
+/// 当前后端的迁移
+pub fn migrator() -> anyhow::Result<&'static Migrator> {
+    Ok(match get_db()?.pool() {
+        Pool::MySql(_) => &MYSQL_MIGRATOR,
+        Pool::Sqlite(_) => &SQLITE_MIGRATOR,
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn sqlite_migrator() -> &'static Migrator {
+    &SQLITE_MIGRATOR
+}
+
+/// 执行未应用的迁移, 返回本次应用的版本
+pub async fn migrate() -> anyhow::Result<Vec<i64>> {
+    let pending = pending(&embedded()?, &applied().await?)?;
+    check_init_tables(&pending).await?;
+    run().await?;
+    for version in pending.iter() {
+        warn!(version = *version; "migration applied");
+    }
+    Ok(pending)
+}
+
+/// 返回 (已应用版本, 未应用版本)
+pub async fn status() -> anyhow::Result<(Vec<i64>, Vec<i64>)> {
+    let applied = applied().await?;
+    let pending = pending(&embedded()?, &applied)?;
+    Ok((applied.into_iter().map(|(v, _)| v).collect(), pending))
+}
+
+/// 启动时检查表结构版本, 有未应用的迁移时按 mysql.auto_migrate 执行, 否则拒绝启动
+/// sqlite 用于本地开发, 总是自动执行
+pub async fn check_schema() -> anyhow::Result<()> {
+    let pending = pending(&embedded()?, &applied().await?)?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    if !C.mysql.auto_migrate && C.sqlite.is_none() {
+        return Err(anyhow!(
+            "database schema out of date, pending migrations: {:?}, run `arbctl migrate` or set mysql.auto_migrate = true",
+            pending
+        ));
+    }
+    check_init_tables(&pending).await?;
+    run().await?;
+    info!(versions:? = pending; "migrations applied");
+    Ok(())
+}
+
+fn embedded() -> anyhow::Result<Vec<(i64, Vec<u8>)>> {
+    Ok(migrator()?
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| (m.version, m.checksum.to_vec()))
+        .collect())
+}
+
+// 初始迁移未应用时, 已存在的表(手动建表)必须与初始迁移的列一致, 否则后续迁移基于错误的表结构
+async fn check_init_tables(pending: &[i64]) -> anyhow::Result<()> {
+    if !pending.contains(&INIT_VERSION) {
+        return Ok(());
+    }
+    let init = migrator()?
+        .iter()
+        .find(|m| m.version == INIT_VERSION && !m.migration_type.is_down_migration())
+        .ok_or(anyhow!("migration {} not found", INIT_VERSION))?;
+    check_tables(get_db()?.pool(), &init.sql).await
+}
+
+async fn check_tables(pool: &Pool, sql: &str) -> anyhow::Result<()> {
+    for (table, columns) in tables(sql) {
+        let existing: Vec<(String,)> = match pool {
+            Pool::MySql(p) => {
+                sqlx::query_as(
+                    "select column_name from information_schema.columns where table_schema = database() and table_name = ?",
+                )
+                .bind(&table)
+                .fetch_all(p)
+                .await?
+            }
+            Pool::Sqlite(p) => {
+                sqlx::query_as("select name from pragma_table_info(?)")
+                    .bind(&table)
+                    .fetch_all(p)
+                    .await?
+            }
+        };
+        // 表不存在时由迁移创建
+        if existing.is_empty() {
+            continue;
+        }
+        let existing: Vec<String> = existing.into_iter().map(|(c,)| c.to_lowercase()).collect();
+        let missing: Vec<&String> = columns.iter().filter(|c| !existing.contains(c)).collect();
+        let unexpected: Vec<&String> = existing.iter().filter(|c| !columns.contains(c)).collect();
+        if !missing.is_empty() || !unexpected.is_empty() {
+            return Err(anyhow!(
+                "table {} does not match migration {}, missing columns: {:?}, unexpected columns: {:?}",
+                table,
+                INIT_VERSION,
+                missing,
+                unexpected
+            ));
+        }
+    }
+    Ok(())
+}
+
+// 从建表语句中解析 (表名, 列名), 每行一个列定义
+fn tables(sql: &str) -> Vec<(String, Vec<String>)> {
+    let mut tables: Vec<(String, Vec<String>)> = Vec::new();
+    let mut in_table = false;
+    for line in strip_literals(sql).lines().map(str::trim) {
+        let lower = line.to_lowercase();
+        if let Some(name) = lower.strip_prefix("create table if not exists ") {
+            tables.push((name.trim().trim_matches('`').to_string(), vec![]));
+            in_table = true;
+            continue;
+        }
+        if !in_table || line.is_empty() || line == "(" {
+            continue;
+        }
+        if line.starts_with(')') {
+            in_table = false;
+            continue;
+        }
+        let word = lower.split_whitespace().next().unwrap_or_default();
+        if ["primary", "key", "unique", "index", "constraint"].contains(&word) {
+            continue;
+        }
+        if let Some((_, columns)) = tables.last_mut() {
+            columns.push(word.trim_matches('`').to_string());
+        }
+    }
+    tables
+}
+
+// 去掉字符串及 -- 注释, 列注释可能跨行
+fn strip_literals(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => quoted = !quoted,
+            '-' if !quoted && chars.peek() == Some(&'-') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            _ if !quoted => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+async fn run() -> anyhow::Result<()> {
+    let migrator = migrator()?;
+    match get_db()?.pool() {
+        Pool::MySql(p) => migrator.run(p).await?,
+        Pool::Sqlite(p) => migrator.run(p).await?,
+    }
+    Ok(())
+}
+
+async fn applied() -> anyhow::Result<Vec<(i64, Vec<u8>)>> {
+    // 未迁移过的数据库没有 _sqlx_migrations 表, 检查时不创建
+    match get_db()?.pool() {
+        Pool::MySql(p) => {
+            let (exists,): (i64,) = sqlx::query_as(
+                "select count(*) from information_schema.tables where table_schema = database() and table_name = '_sqlx_migrations'",
+            )
+            .fetch_one(p)
+            .await?;
+            list_applied(p, exists > 0).await
+        }
+        Pool::Sqlite(p) => {
+            let (exists,): (i64,) = sqlx::query_as(
+                "select count(*) from sqlite_master where type = 'table' and name = '_sqlx_migrations'",
+            )
+            .fetch_one(p)
+            .await?;
+            list_applied(p, exists > 0).await
+        }
+    }
+}
+
+async fn list_applied<DB>(pool: &SqlxPool<DB>, exists: bool) -> anyhow::Result<Vec<(i64, Vec<u8>)>>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    if !exists {
+        return Ok(vec![]);
+    }
+    let mut conn = pool.acquire().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(anyhow!(
+            "migration {} failed halfway, fix the database manually",
+            version
+        ));
+    }
+    let applied = conn.list_applied_migrations().await?;
+    Ok(applied
+        .into_iter()
+        .map(|m| (m.version, m.checksum.to_vec()))
+        .collect())
+}
+
+// 比较嵌入与已应用的迁移, 返回未应用的版本; 数据库版本更新或迁移文件被修改时报错
+fn pending(
+    embedded: &[(i64, Vec<u8>)],
+    applied: &[(i64, Vec<u8>)],
+) -> anyhow::Result<Vec<i64>> {
+    for (version, checksum) in applied {
+        match embedded.iter().find(|(v, _)| v == version) {
+            None => {
+                return Err(anyhow!(
+                    "database schema version {} is newer than this binary",
+                    version
+                ))
+            }
+            Some((_, c)) if c != checksum => {
+                return Err(anyhow!(
+                    "migration {} was modified after being applied",
+                    version
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(embedded
+        .iter()
+        .filter(|(v, _)| !applied.iter().any(|(a, _)| a == v))
+        .map(|(v, _)| *v)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending() {
+        let list = vec![(1, vec![1]), (2, vec![2])];
+        assert_eq!(pending(&list, &[]).unwrap(), vec![1, 2]);
+        assert_eq!(pending(&list, &[(1, vec![1])]).unwrap(), vec![2]);
+        assert!(pending(&list, &[(1, vec![9])]).is_err());
+        assert!(pending(&list, &[(1, vec![1]), (2, vec![2]), (3, vec![3])]).is_err());
+        assert_eq!(MYSQL_MIGRATOR.iter().count(), SQLITE_MIGRATOR.iter().count());
+    }
+
+    fn init_sql(migrator: &Migrator) -> String {
+        migrator
+            .iter()
+            .find(|m| m.version == INIT_VERSION)
+            .unwrap()
+            .sql
+            .to_string()
+    }
+
+    #[test]
+    fn test_init_tables() {
+        let mysql = tables(&init_sql(&MYSQL_MIGRATOR));
+        assert_eq!(mysql.len(), 8);
+        assert_eq!(mysql[0].0, "arb_diff_rate");
+        assert_eq!(mysql[0].1.first().unwrap(), "id");
+        assert_eq!(mysql[0].1.last().unwrap(), "bak");
+        assert_eq!(mysql, tables(&init_sql(&SQLITE_MIGRATOR)));
+    }
+
+    #[tokio::test]
+    async fn test_check_tables() {
+        let sql = init_sql(&SQLITE_MIGRATOR);
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let pool = Pool::Sqlite(pool);
+        let Pool::Sqlite(p) = &pool else { unreachable!() };
+
+        // 未建表
+        check_tables(&pool, &sql).await.unwrap();
+
+        // 手动建表缺少列
+        sqlx::query("create table arb_diff_rate (id integer primary key, coin text, extra text)")
+            .execute(p)
+            .await
+            .unwrap();
+        let err = check_tables(&pool, &sql).await.unwrap_err().to_string();
+        assert!(err.contains("arb_diff_rate"));
+        assert!(err.contains("\"platform\""));
+        assert!(err.contains("\"extra\""));
+
+        // 与初始迁移一致
+        sqlx::query("drop table arb_diff_rate").execute(p).await.unwrap();
+        sqlx::Executor::execute(p, sql.as_str()).await.unwrap();
+        check_tables(&pool, &sql).await.unwrap();
+    }
+}
@@ -1,23 +1,48 @@
+pub mod kline;
+mod migrate;
+mod query;
+
+pub use migrate::{check_schema, migrate, migrator, status as migrate_status};
+pub use query::{begin, query, query_as, Arg, Done, Query, QueryAs, Record, Tx};
+
 use crate::conf;
 use anyhow::anyhow;
 use once_cell::sync::OnceCell;
-use rocksdb::{DBWithThreadMode, MultiThreaded, DB};
+use rocksdb::{DBWithThreadMode, MultiThreaded, Options, DB};
 use sqlx::mysql::MySqlPoolOptions;
-use sqlx::MySqlPool;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{MySqlPool, SqlitePool};
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::fs::remove_dir_all;
+use tokio::fs::create_dir_all;
+
+// 配置 sqlite 时使用 sqlite, 用于本地开发及测试
+#[derive(Debug, Clone)]
+pub enum Pool {
+    MySql(MySqlPool),
+    Sqlite(SqlitePool),
+}
 
 #[derive(Debug, Clone)]
 pub struct Db {
-    db_pool: MySqlPool,
+    db_pool: Pool,
     redis: redis::Client,
-    rocksdb: Arc<DBWithThreadMode<MultiThreaded>>,
+    rocksdb: Option<Arc<DBWithThreadMode<MultiThreaded>>>,
 }
 
 pub static DBV1: OnceCell<Db> = OnceCell::new();
 
 pub async fn init_env() -> anyhow::Result<()> {
-    let db = Db::new().await?;
+    let db = Db::new(true).await?;
+    if let Err(e) = DBV1.set(db) {
+        panic!("{:?}", e);
+    }
+    Ok(())
+}
+
+// 命令行工具使用, 不打开 rocksdb, 避免与运行中的进程冲突
+pub async fn init_env_without_rocksdb() -> anyhow::Result<()> {
+    let db = Db::new(false).await?;
     if let Err(e) = DBV1.set(db) {
         panic!("{:?}", e);
     }
@@ -32,15 +57,23 @@ pub fn get_db<'a>() -> anyhow::Result<&'a Db> {
 }
 
 impl Db {
-    pub async fn new() -> anyhow::Result<Self> {
-        let db_pool = MySqlPoolOptions::new()
-            .max_connections(5)
-            .connect(conf::C.mysql.url.as_str())
-            .await?;
+    pub async fn new(with_rocksdb: bool) -> anyhow::Result<Self> {
+        let db_pool = match conf::C.sqlite.as_ref() {
+            Some(sqlite) => Pool::Sqlite(connect_sqlite(sqlite.url.as_str()).await?),
+            None => Pool::MySql(
+                MySqlPoolOptions::new()
+                    .max_connections(5)
+                    .connect(conf::C.mysql.url.as_str())
+                    .await?,
+            ),
+        };
         let redis = redis::Client::open(conf::C.redis.url.as_str())?;
 
-        remove_dir_all(&conf::C.rocksdb.path).await?;
-        let rocksdb = Arc::new(DB::open_default(&conf::C.rocksdb.path).unwrap());
+        let rocksdb = if with_rocksdb {
+            Some(Arc::new(open_rocksdb(&conf::C.rocksdb.path).await?))
+        } else {
+            None
+        };
         Ok(Self {
             db_pool,
             redis,
@@ -48,11 +81,11 @@ impl Db {
         })
     }
 
-    pub fn rocksdb(&self) -> &Arc<DBWithThreadMode<MultiThreaded>> {
-        &self.rocksdb
+    pub fn rocksdb(&self) -> anyhow::Result<&Arc<DBWithThreadMode<MultiThreaded>>> {
+        self.rocksdb.as_ref().ok_or(anyhow!("rocksdb is none."))
     }
 
-    pub fn database(&self) -> &MySqlPool {
+    pub fn pool(&self) -> &Pool {
         &self.db_pool
     }
 
@@ -63,3 +96,55 @@ impl Db {
             .map_err(|e| e.into())
     }
 }
+
+// 保留已有数据, 打开时需列出已存在的列族
+async fn open_rocksdb(path: &str) -> anyhow::Result<DB> {
+    create_dir_all(path).await?;
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    opts.create_missing_column_families(true);
+    let cfs = DB::list_cf(&opts, path).unwrap_or_default();
+    Ok(DB::open_cf(&opts, path, cfs)?)
+}
+
+// sqlite::memory: 每个连接是独立的库, 只保留一个连接且不回收
+async fn connect_sqlite(url: &str) -> anyhow::Result<SqlitePool> {
+    let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+    let pool = if url.contains(":memory:") {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?
+    } else {
+        SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await?
+    };
+    Ok(pool)
+}
+
+/// 测试使用: 临时 sqlite 文件并执行迁移, 不读取配置
+///
+/// 各测试运行在不同的 runtime, 内存库在连接重建后会丢失, 使用文件
+#[cfg(test)]
+pub async fn init_test_env() -> anyhow::Result<&'static Db> {
+    static INIT: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+    INIT.get_or_try_init(|| async {
+        let path = std::env::temp_dir().join(format!("arb-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = connect_sqlite(&format!("sqlite://{}", path.display())).await?;
+        migrate::sqlite_migrator().run(&pool).await?;
+        let db = Db {
+            db_pool: Pool::Sqlite(pool),
+            redis: redis::Client::open("redis://127.0.0.1/")?,
+            rocksdb: None,
+        };
+        DBV1.set(db).map_err(|_| anyhow!("db already initialized"))?;
+        anyhow::Ok(())
+    })
+    .await?;
+    get_db()
+}
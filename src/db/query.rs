@@ -0,0 +1,256 @@
+use crate::db::{get_db, Pool};
+use anyhow::anyhow;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::query::Query as SqlxQuery;
+use sqlx::sqlite::{SqliteArguments, SqliteRow};
+use sqlx::{Column, FromRow, MySql, Row, Sqlite, TypeInfo, ValueRef};
+use std::marker::PhantomData;
+
+// 同一条 sql 在 mysql/sqlite 上执行, 参数先收集再按后端绑定
+// sqlite 不支持 Decimal, 以 TEXT 存储, 查询结果经 serde 转换为 model
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Int(i64),
+    Text(String),
+    Decimal(Decimal),
+    Null,
+}
+
+macro_rules! arg_from_int {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Arg {
+            fn from(v: $t) -> Self {
+                Arg::Int(v as i64)
+            }
+        })*
+    };
+}
+
+arg_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<String> for Arg {
+    fn from(v: String) -> Self {
+        Arg::Text(v)
+    }
+}
+
+impl From<&String> for Arg {
+    fn from(v: &String) -> Self {
+        Arg::Text(v.clone())
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(v: &str) -> Self {
+        Arg::Text(v.to_string())
+    }
+}
+
+impl From<Decimal> for Arg {
+    fn from(v: Decimal) -> Self {
+        Arg::Decimal(v)
+    }
+}
+
+impl<T: Into<Arg>> From<Option<T>> for Arg {
+    fn from(v: Option<T>) -> Self {
+        v.map(Into::into).unwrap_or(Arg::Null)
+    }
+}
+
+/// 可从两种后端读取的记录
+pub trait Record: for<'r> FromRow<'r, MySqlRow> + DeserializeOwned + Send + Unpin {}
+
+impl<T> Record for T where T: for<'r> FromRow<'r, MySqlRow> + DeserializeOwned + Send + Unpin {}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Done {
+    rows_affected: u64,
+    last_insert_id: u64,
+}
+
+impl Done {
+    pub fn rows_affected(&self) -> u64 {
+        self.rows_affected
+    }
+
+    pub fn last_insert_id(&self) -> u64 {
+        self.last_insert_id
+    }
+}
+
+pub enum Tx {
+    MySql(sqlx::Transaction<'static, MySql>),
+    Sqlite(sqlx::Transaction<'static, Sqlite>),
+}
+
+/// 开启事务
+pub async fn begin() -> anyhow::Result<Tx> {
+    Ok(match get_db()?.pool() {
+        Pool::MySql(p) => Tx::MySql(p.begin().await?),
+        Pool::Sqlite(p) => Tx::Sqlite(p.begin().await?),
+    })
+}
+
+impl Tx {
+    pub async fn commit(self) -> anyhow::Result<()> {
+        match self {
+            Tx::MySql(tx) => tx.commit().await?,
+            Tx::Sqlite(tx) => tx.commit().await?,
+        }
+        Ok(())
+    }
+}
+
+pub struct Query {
+    sql: String,
+    args: Vec<Arg>,
+}
+
+pub struct QueryAs<T> {
+    query: Query,
+    _t: PhantomData<T>,
+}
+
+pub fn query(sql: impl Into<String>) -> Query {
+    Query {
+        sql: sql.into(),
+        args: vec![],
+    }
+}
+
+pub fn query_as<T: Record>(sql: impl Into<String>) -> QueryAs<T> {
+    QueryAs {
+        query: query(sql),
+        _t: PhantomData,
+    }
+}
+
+impl Query {
+    pub fn bind(mut self, v: impl Into<Arg>) -> Self {
+        self.args.push(v.into());
+        self
+    }
+
+    fn mysql(&self) -> SqlxQuery<'_, MySql, MySqlArguments> {
+        let mut q = sqlx::query(&self.sql);
+        for arg in self.args.iter() {
+            q = match arg {
+                Arg::Int(v) => q.bind(*v),
+                Arg::Text(v) => q.bind(v.as_str()),
+                Arg::Decimal(v) => q.bind(*v),
+                Arg::Null => q.bind(None::<String>),
+            };
+        }
+        q
+    }
+
+    fn sqlite(&self) -> SqlxQuery<'_, Sqlite, SqliteArguments<'_>> {
+        let mut q = sqlx::query(&self.sql);
+        for arg in self.args.iter() {
+            q = match arg {
+                Arg::Int(v) => q.bind(*v),
+                Arg::Text(v) => q.bind(v.as_str()),
+                Arg::Decimal(v) => q.bind(v.to_string()),
+                Arg::Null => q.bind(None::<String>),
+            };
+        }
+        q
+    }
+
+    pub async fn execute(self) -> anyhow::Result<Done> {
+        Ok(match get_db()?.pool() {
+            Pool::MySql(p) => {
+                let r = self.mysql().execute(p).await?;
+                Done {
+                    rows_affected: r.rows_affected(),
+                    last_insert_id: r.last_insert_id(),
+                }
+            }
+            Pool::Sqlite(p) => {
+                let r = self.sqlite().execute(p).await?;
+                Done {
+                    rows_affected: r.rows_affected(),
+                    last_insert_id: r.last_insert_rowid() as u64,
+                }
+            }
+        })
+    }
+
+    pub async fn execute_in(self, tx: &mut Tx) -> anyhow::Result<Done> {
+        Ok(match tx {
+            Tx::MySql(tx) => {
+                let r = self.mysql().execute(&mut **tx).await?;
+                Done {
+                    rows_affected: r.rows_affected(),
+                    last_insert_id: r.last_insert_id(),
+                }
+            }
+            Tx::Sqlite(tx) => {
+                let r = self.sqlite().execute(&mut **tx).await?;
+                Done {
+                    rows_affected: r.rows_affected(),
+                    last_insert_id: r.last_insert_rowid() as u64,
+                }
+            }
+        })
+    }
+}
+
+impl<T: Record> QueryAs<T> {
+    pub fn bind(mut self, v: impl Into<Arg>) -> Self {
+        self.query = self.query.bind(v);
+        self
+    }
+
+    pub async fn fetch_all(self) -> anyhow::Result<Vec<T>> {
+        match get_db()?.pool() {
+            Pool::MySql(p) => Ok(self
+                .query
+                .mysql()
+                .fetch_all(p)
+                .await?
+                .iter()
+                .map(T::from_row)
+                .collect::<Result<_, _>>()?),
+            Pool::Sqlite(p) => self
+                .query
+                .sqlite()
+                .fetch_all(p)
+                .await?
+                .iter()
+                .map(from_sqlite_row)
+                .collect(),
+        }
+    }
+
+    pub async fn fetch_one(self) -> anyhow::Result<T> {
+        match get_db()?.pool() {
+            Pool::MySql(p) => Ok(T::from_row(&self.query.mysql().fetch_one(p).await?)?),
+            Pool::Sqlite(p) => from_sqlite_row(&self.query.sqlite().fetch_one(p).await?),
+        }
+    }
+}
+
+fn from_sqlite_row<T: DeserializeOwned>(row: &SqliteRow) -> anyhow::Result<T> {
+    let mut obj = Map::new();
+    for (i, column) in row.columns().iter().enumerate() {
+        let raw = row.try_get_raw(i)?;
+        let v = if raw.is_null() {
+            Value::Null
+        } else {
+            match raw.type_info().name() {
+                "INTEGER" | "BOOLEAN" => Value::from(row.try_get::<i64, _>(i)?),
+                "REAL" => Value::from(row.try_get::<f64, _>(i)?),
+                "TEXT" => Value::from(row.try_get::<String, _>(i)?),
+                t => return Err(anyhow!("unsupported sqlite type {} of {}", t, column.name())),
+            }
+        };
+        obj.insert(column.name().to_string(), v);
+    }
+    Ok(serde_json::from_value(Value::Object(obj))?)
+}
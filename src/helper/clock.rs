@@ -0,0 +1,34 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+// 行情处理使用的时钟, 回放时由回放帧的接收时间驱动, 否则为本地时间
+
+// 0 为未启用
+static VIRTUAL_MS: AtomicI64 = AtomicI64::new(0);
+
+/// 启用虚拟时钟并设置为 ms
+pub fn set(ms: i64) {
+    VIRTUAL_MS.store(ms, Ordering::Relaxed);
+}
+
+pub fn is_virtual() -> bool {
+    VIRTUAL_MS.load(Ordering::Relaxed) != 0
+}
+
+pub fn now_millis() -> i64 {
+    match VIRTUAL_MS.load(Ordering::Relaxed) {
+        0 => Local::now().timestamp_millis(),
+        ms => ms,
+    }
+}
+
+pub fn now() -> DateTime<Local> {
+    Local
+        .timestamp_millis_opt(now_millis())
+        .single()
+        .unwrap_or_else(Local::now)
+}
+
+pub fn now_utc() -> DateTime<Utc> {
+    now().with_timezone(&Utc)
+}
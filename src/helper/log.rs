@@ -1,53 +1,180 @@
 use crate::conf::C;
-use chrono::Local;
-use env_logger::fmt::Color;
-use env_logger::Env;
-use log::LevelFilter;
-use std::fs::File;
+use flexi_logger::{
+    style, Age, Cleanup, Criterion, DeferredNow, FileSpec, FormatFunction, Logger, LoggerHandle,
+    Naming,
+};
+use log::kv::{Error, Key, Value, VisitSource};
+use log::Record;
+use once_cell::sync::OnceCell;
+use serde_json::{Map, Number};
 use std::io::Write;
-use std::str::FromStr;
+use std::path::Path;
 
-pub fn init_log() {
-    let env = Env::default().filter_or("MY_LOG_LEVEL", "debug");
+// 文件日志需要保持 handle, 否则停止写入
+static LOGGER: OnceCell<LoggerHandle> = OnceCell::new();
 
-    // 输出到控制台
-    let target = if C.log.pattern == "console" {
-        env_logger::Target::Stdout
+// 日志字段通过 key-value 传入, 例: warn!(strategy_id = s.id, symbol = s.to_symbol.as_str(); "place order")
+// 环境变量 MY_LOG_LEVEL 优先于配置的 level, 例: MY_LOG_LEVEL=info,arbitrage::service=debug
+pub fn init_log() {
+    let format: FormatFunction = if C.log.json() {
+        json_format
     } else {
-        // 输出到文件
-        let log_path = format!("{}/{}", C.log.dir, C.log.prefix);
-        let t = Box::new(File::create(log_path).expect("Can't create file"));
-        env_logger::Target::Pipe(t)
+        text_format
     };
 
-    let level = LevelFilter::from_str(C.log.level.as_str()).unwrap();
-
-    env_logger::Builder::from_env(env)
-        .format(|buf, record| {
-            let level_color = match record.level() {
-                log::Level::Error => Color::Red,
-                log::Level::Warn => Color::Yellow,
-                log::Level::Info => Color::Green,
-                log::Level::Debug | log::Level::Trace => Color::Cyan,
-            };
-
-            let mut level_style = buf.style();
-            level_style.set_color(level_color).set_bold(true);
-
-            let mut style = buf.style();
-            style.set_color(Color::White).set_dimmed(true);
-
-            writeln!(
-                buf,
-                "{} {} [ {}:{} ] {}",
-                Local::now().format("%Y-%m-%d %H:%M:%S%.f"),
-                level_style.value(record.level()),
-                style.value(record.module_path().unwrap_or("<unnamed>")),
-                record.line().unwrap_or(0),
-                record.args()
+    let level = std::env::var("MY_LOG_LEVEL").unwrap_or_else(|_| C.log.level.clone());
+    let logger = Logger::try_with_str(level.as_str()).unwrap();
+    let logger = if C.log.pattern == "console" {
+        // 输出到控制台
+        let stdout_format = if C.log.json() {
+            json_format
+        } else {
+            colored_text_format
+        };
+        logger.log_to_stdout().format_for_stdout(stdout_format)
+    } else {
+        // 输出到文件, 按天或大小切分, 保留 keep_files 个
+        let prefix = Path::new(&C.log.prefix);
+        let mut spec = FileSpec::default()
+            .directory(&C.log.dir)
+            .basename(prefix.file_stem().and_then(|s| s.to_str()).unwrap_or("arb"))
+            .suppress_timestamp();
+        if let Some(suffix) = prefix.extension().and_then(|s| s.to_str()) {
+            spec = spec.suffix(suffix);
+        }
+        logger
+            .log_to_file(spec)
+            .format_for_files(format)
+            .append()
+            .rotate(
+                Criterion::AgeOrSize(Age::Day, C.log.max_size_mb * 1024 * 1024),
+                Naming::Timestamps,
+                Cleanup::KeepLogFiles(C.log.keep_files),
             )
-        })
-        .target(target)
-        .filter(None, level)
-        .init();
+    };
+
+    let handle = logger.start().expect("Can't start logger");
+    let _ = LOGGER.set(handle);
+}
+
+// 退出前写出缓冲的日志
+pub fn flush() {
+    if let Some(handle) = LOGGER.get() {
+        handle.flush();
+    }
+}
+
+struct JsonFields<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let v = if let Some(v) = value.to_i64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_u64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_f64().and_then(Number::from_f64) {
+            serde_json::Value::Number(v)
+        } else if let Some(v) = value.to_bool() {
+            serde_json::Value::Bool(v)
+        } else {
+            serde_json::Value::String(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), v);
+        Ok(())
+    }
+}
+
+struct TextFields<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for TextFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        self.0.push_str(&format!(" {}={}", key, value));
+        Ok(())
+    }
+}
+
+pub fn json_format(
+    w: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> std::io::Result<()> {
+    let mut obj = Map::new();
+    obj.insert(
+        "ts".to_string(),
+        now.format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string().into(),
+    );
+    obj.insert("level".to_string(), record.level().as_str().into());
+    obj.insert(
+        "target".to_string(),
+        record.module_path().unwrap_or("<unnamed>").into(),
+    );
+    obj.insert("line".to_string(), record.line().unwrap_or(0).into());
+    obj.insert("msg".to_string(), record.args().to_string().into());
+    let _ = record.key_values().visit(&mut JsonFields(&mut obj));
+    write!(w, "{}", serde_json::Value::Object(obj))
+}
+
+fn text_line(now: &mut DeferredNow, record: &Record) -> (String, String) {
+    let mut fields = String::new();
+    let _ = record.key_values().visit(&mut TextFields(&mut fields));
+    let head = now.format("%Y-%m-%d %H:%M:%S%.f").to_string();
+    let tail = format!(
+        "[ {}:{} ] {}{}",
+        record.module_path().unwrap_or("<unnamed>"),
+        record.line().unwrap_or(0),
+        record.args(),
+        fields
+    );
+    (head, tail)
+}
+
+pub fn text_format(
+    w: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> std::io::Result<()> {
+    let (head, tail) = text_line(now, record);
+    write!(w, "{} {} {}", head, record.level(), tail)
+}
+
+pub fn colored_text_format(
+    w: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> std::io::Result<()> {
+    let (head, tail) = text_line(now, record);
+    write!(
+        w,
+        "{} {} {}",
+        head,
+        style(record.level()).bold().paint(record.level().to_string()),
+        tail
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_format() {
+        let kvs: &[(&str, Value)] = &[
+            ("strategy_id", Value::from(7i64)),
+            ("symbol", Value::from("BTCUSDT")),
+        ];
+        let record = Record::builder()
+            .args(format_args!("place order"))
+            .level(log::Level::Warn)
+            .module_path(Some("arbitrage::service"))
+            .line(Some(1))
+            .key_values(&kvs)
+            .build();
+        let mut buf = Vec::new();
+        json_format(&mut buf, &mut DeferredNow::new(), &record).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["level"], "WARN");
+        assert_eq!(v["msg"], "place order");
+        assert_eq!(v["strategy_id"], 7);
+        assert_eq!(v["symbol"], "BTCUSDT");
+    }
 }
@@ -0,0 +1,157 @@
+use crate::conf::C;
+use crate::helper::shutdown;
+use crate::sql;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// 行情
+pub static WS_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "arb_ws_messages_total",
+        "websocket messages received",
+        &["platform", "market"]
+    )
+    .unwrap()
+});
+
+pub static WS_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "arb_ws_latency_seconds",
+        "exchange event time to local receive time",
+        &["platform", "market"],
+        exponential_buckets(0.005, 2.0, 12).unwrap()
+    )
+    .unwrap()
+});
+
+pub static PRICE_BACKLOG: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "arb_price_channel_backlog",
+        "price streams sent but not yet consumed"
+    )
+    .unwrap()
+});
+
+pub static RECORDER_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "arb_recorder_dropped_frames_total",
+        "raw frames dropped because the recorder channel was full",
+        &["venue", "market"]
+    )
+    .unwrap()
+});
+
+// 信号
+pub static SIGNALS_OPENED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "arb_signals_opened_total",
+        "new diff signals",
+        &["symbol", "from_compare", "to_compare"]
+    )
+    .unwrap()
+});
+
+// 执行, status: placed/filled/expired
+pub static ORDERS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "arb_orders_total",
+        "orders by option_type and status",
+        &["option_type", "status"]
+    )
+    .unwrap()
+});
+
+pub static REST_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "arb_rest_latency_seconds",
+        "binance rest request latency",
+        &["endpoint"],
+        exponential_buckets(0.01, 2.0, 12).unwrap()
+    )
+    .unwrap()
+});
+
+pub static REST_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "arb_rest_errors_total",
+        "binance rest request errors",
+        &["endpoint"]
+    )
+    .unwrap()
+});
+
+pub static STRATEGIES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "arb_strategies",
+        "arb_strategy count by doing_status",
+        &["doing_status"]
+    )
+    .unwrap()
+});
+
+pub fn order(option_type: &str, status: &str) {
+    ORDERS.with_label_values(&[option_type, status]).inc();
+}
+
+pub fn rest(endpoint: &str, elapsed: Duration, ok: bool) {
+    REST_LATENCY
+        .with_label_values(&[endpoint])
+        .observe(elapsed.as_secs_f64());
+    if !ok {
+        REST_ERRORS.with_label_values(&[endpoint]).inc();
+    }
+}
+
+pub async fn serve() {
+    let Some(metrics) = C.metrics.as_ref() else {
+        return;
+    };
+    let addr = match metrics.listen.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(e) => {
+            error!("metrics listen {} err: {:?}", metrics.listen, e);
+            return;
+        }
+    };
+
+    let app = Router::new().route("/metrics", get(render));
+    info!("metrics listening on {}", addr);
+    if let Err(e) = axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown::token().cancelled_owned())
+        .await
+    {
+        error!("metrics err: {:?}", e);
+    }
+}
+
+async fn render() -> Result<String, StatusCode> {
+    // 策略数量在抓取时统计
+    match sql::get_arb_strategy_count_by_doing_status().await {
+        Ok(counts) => {
+            STRATEGIES.reset();
+            for (doing_status, count) in counts {
+                STRATEGIES
+                    .with_label_values(&[&doing_status.to_string()])
+                    .set(count);
+            }
+        }
+        Err(e) => error!("metrics strategy count err: {:?}", e),
+    }
+
+    let mut buf = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buf)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    String::from_utf8(buf).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
@@ -1 +1,4 @@
+pub mod clock;
 pub mod log;
+pub mod metrics;
+pub mod shutdown;
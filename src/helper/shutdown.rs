@@ -0,0 +1,40 @@
+use log::warn;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+// 全局退出信号, 长期运行的任务监听 token, 需要等待完成的任务通过 spawn 登记
+static TOKEN: Lazy<CancellationToken> = Lazy::new(CancellationToken::new);
+static TRACKER: Lazy<TaskTracker> = Lazy::new(TaskTracker::new);
+
+pub fn token() -> CancellationToken {
+    TOKEN.clone()
+}
+
+pub fn is_shutdown() -> bool {
+    TOKEN.is_cancelled()
+}
+
+pub fn spawn<F>(task: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    TRACKER.spawn(task)
+}
+
+/// 发出退出信号并等待登记的任务完成, 超时返回 false
+pub async fn shutdown(timeout: Duration) -> bool {
+    TOKEN.cancel();
+    TRACKER.close();
+    match tokio::time::timeout(timeout, TRACKER.wait()).await {
+        Ok(_) => true,
+        Err(_) => {
+            warn!(tasks = TRACKER.len(); "shutdown timeout, tasks still running");
+            false
+        }
+    }
+}
@@ -7,10 +7,12 @@ extern crate lazy_static;
 extern crate serde;
 extern crate serde_qs as qs;
 
+pub mod admin;
 pub mod binance;
 pub mod conf;
 pub mod db;
 pub mod helper;
+pub mod mock;
 pub mod model;
 pub mod service;
 pub mod sql;
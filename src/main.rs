@@ -1,14 +1,14 @@
-#[macro_use]
-extern crate tokio;
-
 use arbitrage::conf;
 use arbitrage::service::PriceStream;
-use arbitrage::{db, helper, service};
+use arbitrage::{admin, db, helper, service};
 use futures::future::BoxFuture;
 use log::warn;
-use std::collections::HashMap;
+use std::time::Duration;
 use arbitrage::binance::ws_model::WebSocketEvent;
 
+// 收到退出信号后等待进行中的任务完成的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // 初始化配置文件
@@ -17,26 +17,12 @@ async fn main() -> anyhow::Result<()> {
     db::init_env().await?;
     // 初始化日志
     helper::log::init_log();
+    // 检查表结构版本
+    db::check_schema().await?;
 
-    let (close_tx, mut close_rx) = tokio::sync::mpsc::unbounded_channel::<bool>();
     let (price_tx, price_rx) = tokio::sync::mpsc::unbounded_channel::<PriceStream>();
-
-    let wait_loop = tokio::spawn(async move {
-        'hello: loop {
-            select! {
-                _ = close_rx.recv() => break 'hello
-            }
-        }
-    });
-
-    // 线程池通道
-    let mut txs = HashMap::new();
-    let mut rxs = HashMap::new();
-    for i in 0..10 {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        txs.insert(i, tx.clone());
-        rxs.insert(i, rx);
-    }
+    let (signal_tx, signal_rx) = tokio::sync::mpsc::unbounded_channel::<PriceStream>();
+    let (book_tx, book_rx) = tokio::sync::mpsc::unbounded_channel::<PriceStream>();
 
     // let funding_rate_json = r#"{"topic":"/contract/instrument:ALL","type":"message","subject":"funding.rate","data":{"symbol":"IDUSDTM","granularity":60000,"fundingRate":-0.000880,"timestamp":1722976560000}}"#;
     // match serde_json::from_str::<WebSocketEvent>(funding_rate_json) {
@@ -49,31 +35,47 @@ async fn main() -> anyhow::Result<()> {
     //     },
     //     Err(e) => println!("Failed to deserialize JSON: {}", e),
     // }
-    let streams: Vec<BoxFuture<'static, ()>> = vec![
-        Box::pin(service::set_price_data(price_rx)),
+    let mut streams: Vec<BoxFuture<'static, ()>> = vec![
+        Box::pin(service::fan_out_price(price_rx, signal_tx, book_tx)),
+        Box::pin(service::set_price_data(signal_rx)),
+        Box::pin(service::set_price_cache(book_rx)), // 各平台价格缓存
         Box::pin(service::binance_all_ticker(price_tx.clone())),
         Box::pin(service::bybit_all_ticker(price_tx.clone())),
         Box::pin(service::kucoin_all_ticker(price_tx.clone())),
         Box::pin(service::get_diff_signal()),
+        Box::pin(service::set_binance_diff_rate()), // 计算差价比率, 变化时唤醒关联策略
+        Box::pin(admin::serve()),
+        Box::pin(helper::metrics::serve()),
+        Box::pin(service::kline::start()), // 订阅配置的K线
+        Box::pin(service::pnl::start()), // 计算策略盈亏
+        Box::pin(service::recorder::start()), // 录制原始行情
         // Box::pin(service::range_new_strategy()), //根据arb_strategy表创建arb_strategy_ex表
-        // Box::pin(service::inspect_strategy(txs.clone())), // 轮训策略
     ];
+    // 策略执行会下单, 需在配置中显式开启
+    let execute_strategies = conf::C.scheduler.execute_strategies;
+    if execute_strategies {
+        streams.push(Box::pin(service::schedule_strategy())); // 定时唤醒策略
+    } else {
+        warn!("strategy execution disabled, set [scheduler] execute_strategies = true to run strategies");
+    }
 
     for stream in streams {
-        tokio::spawn(stream);
+        helper::shutdown::spawn(stream);
     }
+    // 行情连接全部退出后 set_price_data/set_price_cache 消费完剩余数据再退出
+    drop(price_tx);
 
-    //开始线程池
-    service::event_start(rxs).await;
+    // 开始策略调度
+    if execute_strategies {
+        service::scheduler::start();
+    }
 
-    select! {
-        _ = wait_loop => { warn!("Finished!") }
-        _ = tokio::signal::ctrl_c() => {
-            warn!("Closing websocket stream...");
-            close_tx.send(true).unwrap();
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
+    tokio::signal::ctrl_c().await?;
+    warn!("Shutting down, waiting for in-flight tasks...");
+    if helper::shutdown::shutdown(SHUTDOWN_TIMEOUT).await {
+        warn!("Finished!");
     }
+    helper::log::flush();
 
     Ok(())
 }
@@ -0,0 +1,732 @@
+pub mod script;
+
+pub use script::{Behavior, Script};
+
+use crate::binance::config::Config;
+use crate::binance::signer::Signer;
+use crate::binance::MyApi;
+use crate::helper::shutdown;
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use futures::{SinkExt, StreamExt};
+use log::{error, info, warn};
+use ring::hmac;
+use script::OrderRule;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response as WsResponse};
+use tokio_tungstenite::tungstenite::Message;
+
+// 本地模拟币安服务, 用于离线集成测试
+//
+// REST: MyApi 使用的 /api/v3、/fapi/v1、/dapi/v1 接口及 /sapi/v1/asset/transfer,
+//       签名接口校验 X-MBX-APIKEY、HMAC 签名及 recvWindow, 下单结果按脚本规则返回
+// WS:   /<market>/ws/!miniTicker@arr 按脚本推送价格
+
+const DEFAULT_RECV_WINDOW: u64 = 5000;
+// 币本位合约默认面值(USD)
+const DEFAULT_CONTRACT_SIZE: u64 = 10;
+
+/// 模拟服务记录的订单
+#[derive(Debug, Clone)]
+pub struct MockOrder {
+    pub market: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    pub time_in_force: String,
+    pub reduce_only: bool,
+    pub price: f64,
+    pub qty: f64,
+    pub executed: f64,
+    pub status: String,
+    pub time: u64,
+}
+
+/// 模拟服务记录的划转
+#[derive(Debug, Clone)]
+pub struct MockTransfer {
+    pub tran_id: u64,
+    pub transfer_type: String,
+    pub asset: String,
+    pub amount: f64,
+}
+
+struct Mock {
+    script: Script,
+    key: hmac::Key,
+    rules: Mutex<Vec<OrderRule>>,
+    orders: Mutex<Vec<MockOrder>>,
+    transfers: Mutex<Vec<MockTransfer>>,
+}
+
+/// 模拟服务的币安地址, REST 共用一个地址, websocket 路径按市场区分
+pub fn config(rest: &str, ws: &str) -> Config {
+    Config::default()
+        .set_rest_api_endpoint(rest)
+        .set_futures_rest_api_endpoint(rest)
+        .set_delivery_rest_api_endpoint(rest)
+        .set_ws_endpoint(format!("{ws}/spot"))
+        .set_futures_ws_endpoint(format!("{ws}/futures"))
+        .set_delivery_ws_endpoint(format!("{ws}/delivery"))
+}
+
+// 同一进程内多个模拟服务的订单、划转ID不重复, 测试共用一个数据库时按 order_id 查询不会串
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+pub struct MockServer {
+    pub rest_addr: SocketAddr,
+    pub ws_addr: SocketAddr,
+    mock: Arc<Mock>,
+}
+
+impl MockServer {
+    /// 绑定 rest/ws 地址并启动, 端口为 0 时随机分配, 收到退出信号后停止
+    pub async fn start(script: Script, rest: &str, ws: &str) -> anyhow::Result<MockServer> {
+        let mock = Arc::new(Mock {
+            key: hmac::Key::new(hmac::HMAC_SHA256, script.secret_key.as_bytes()),
+            rules: Mutex::new(script.orders.clone()),
+            orders: Mutex::new(vec![]),
+            transfers: Mutex::new(vec![]),
+            script,
+        });
+
+        let rest_listener = std::net::TcpListener::bind(rest)?;
+        rest_listener.set_nonblocking(true)?;
+        let rest_addr = rest_listener.local_addr()?;
+        let app = Router::new().fallback(handle).with_state(mock.clone());
+        let server = axum::Server::from_tcp(rest_listener)?
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(shutdown::token().cancelled_owned());
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                error!(err:? = e; "mock rest server err");
+            }
+        });
+
+        let ws_listener = TcpListener::bind(ws).await?;
+        let ws_addr = ws_listener.local_addr()?;
+        tokio::spawn(serve_ws(ws_listener, mock.clone()));
+
+        info!(rest:% = rest_addr, ws:% = ws_addr; "mock binance listening");
+        Ok(MockServer {
+            rest_addr,
+            ws_addr,
+            mock,
+        })
+    }
+
+    /// 指向本服务的币安地址
+    pub fn config(&self) -> Config {
+        config(
+            &format!("http://{}", self.rest_addr),
+            &format!("ws://{}", self.ws_addr),
+        )
+    }
+
+    /// 使用脚本 key 的 api
+    pub fn api(&self) -> MyApi {
+        MyApi::new_with_config(
+            self.mock.script.api_key.clone(),
+            Signer::hmac(&self.mock.script.secret_key),
+            &self.config(),
+        )
+    }
+
+    pub fn orders(&self) -> Vec<MockOrder> {
+        self.mock.orders.lock().unwrap().clone()
+    }
+
+    pub fn transfers(&self) -> Vec<MockTransfer> {
+        self.mock.transfers.lock().unwrap().clone()
+    }
+}
+
+// 与币安一致的错误响应 {"code": -1022, "msg": "..."}
+struct MockError {
+    status: StatusCode,
+    code: i32,
+    msg: String,
+}
+
+impl MockError {
+    fn bad_request(code: i32, msg: &str) -> Self {
+        MockError {
+            status: StatusCode::BAD_REQUEST,
+            code,
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for MockError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(json!({ "code": self.code, "msg": self.msg })),
+        )
+            .into_response()
+    }
+}
+
+type MockResult = Result<Value, MockError>;
+
+async fn handle(
+    State(mock): State<Arc<Mock>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let query = uri.query().unwrap_or_default();
+    let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let mut segments = uri.path().trim_start_matches('/').split('/');
+    let market = match segments.next() {
+        Some("api") | Some("sapi") => "spot",
+        Some("fapi") => "futures",
+        Some("dapi") => "delivery",
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let endpoint = segments.next_back().unwrap_or_default();
+
+    let signed = matches!(
+        endpoint,
+        "order" | "myTrades" | "userTrades" | "income" | "transfer"
+    );
+    if signed {
+        if let Err(e) = mock.verify(&headers, query, &params) {
+            return e.into_response();
+        }
+    }
+
+    let result = match (method, endpoint) {
+        (Method::GET, "time") => Ok(json!({ "serverTime": now() })),
+        (Method::GET, "exchangeInfo") => Ok(mock.exchange_info(market)),
+        (Method::GET, "klines") => mock.klines(market, &params),
+        (Method::POST, "order") => mock.place(market, &params),
+        (Method::GET, "order") => mock.order(market, &params),
+        (Method::DELETE, "order") => mock.cancel(market, &params),
+        (Method::GET, "myTrades") | (Method::GET, "userTrades") => mock.trades(market, &params),
+        (Method::GET, "income") => Ok(json!([])),
+        (Method::POST, "transfer") => mock.transfer(&params),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    match result {
+        Ok(body) => Json(body).into_response(),
+        Err(e) => {
+            warn!(path = uri.path(), code = e.code, msg = e.msg.as_str(); "mock binance err response");
+            e.into_response()
+        }
+    }
+}
+
+impl Mock {
+    // 签名为 query 最后一个参数, 对其之前的内容签名
+    fn verify(
+        &self,
+        headers: &HeaderMap,
+        query: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<(), MockError> {
+        let api_key = headers
+            .get("x-mbx-apikey")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if api_key != self.script.api_key {
+            return Err(MockError {
+                status: StatusCode::UNAUTHORIZED,
+                code: -2015,
+                msg: "Invalid API-key, IP, or permissions for action.".to_string(),
+            });
+        }
+        let invalid = || MockError::bad_request(-1022, "Signature for this request is not valid.");
+        let (payload, signature) = query.rsplit_once("&signature=").ok_or_else(invalid)?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        hmac::verify(&self.key, payload.as_bytes(), &signature).map_err(|_| invalid())?;
+
+        let timestamp: u64 = param(params, "timestamp").ok_or_else(|| {
+            MockError::bad_request(-1102, "Mandatory parameter 'timestamp' was not sent.")
+        })?;
+        let recv_window = param(params, "recvWindow").unwrap_or(DEFAULT_RECV_WINDOW);
+        if now().abs_diff(timestamp) > recv_window {
+            return Err(MockError::bad_request(
+                -1021,
+                "Timestamp for this request is outside of the recvWindow.",
+            ));
+        }
+        Ok(())
+    }
+
+    fn exchange_info(&self, market: &str) -> Value {
+        let symbols: Vec<Value> = self
+            .script
+            .symbols
+            .iter()
+            .filter(|s| s.market == market)
+            .map(|s| {
+                let notional = match market {
+                    "spot" => json!({ "filterType": "NOTIONAL", "minNotional": s.min_notional.to_string() }),
+                    _ => json!({ "filterType": "MIN_NOTIONAL", "notional": s.min_notional.to_string() }),
+                };
+                json!({
+                    "symbol": s.symbol,
+                    "status": "TRADING",
+                    "contractStatus": "TRADING",
+                    "contractSize": s.contract_size,
+                    "deliveryDate": s.delivery_date,
+                    "filters": [
+                        {
+                            "filterType": "PRICE_FILTER",
+                            "minPrice": s.tick_size.to_string(),
+                            "maxPrice": "1000000",
+                            "tickSize": s.tick_size.to_string(),
+                        },
+                        {
+                            "filterType": "LOT_SIZE",
+                            "minQty": s.min_qty.to_string(),
+                            "maxQty": "1000000",
+                            "stepSize": s.step_size.to_string(),
+                        },
+                        notional,
+                    ],
+                })
+            })
+            .collect();
+        json!({ "serverTime": now(), "symbols": symbols })
+    }
+
+    fn klines(&self, market: &str, params: &HashMap<String, String>) -> MockResult {
+        let symbol = params.get("symbol").map(String::as_str).unwrap_or_default();
+        let interval = params
+            .get("interval")
+            .map(String::as_str)
+            .unwrap_or_default();
+        let step = interval_ms(interval)
+            .ok_or_else(|| MockError::bad_request(-1120, "Invalid interval."))?;
+        let start: Option<i64> = param(params, "startTime");
+        let end: Option<i64> = param(params, "endTime");
+        let limit: usize = param(params, "limit").unwrap_or(500);
+
+        let mut rows = Vec::new();
+        let script = self
+            .script
+            .klines
+            .iter()
+            .find(|k| k.market == market && k.symbol == symbol && k.interval == interval);
+        if let Some(k) = script {
+            let mut open = k.closes.first().copied().unwrap_or_default();
+            for (i, close) in k.closes.iter().enumerate() {
+                let open_time = k.start + i as i64 * step;
+                let (high, low) = (open.max(*close), open.min(*close));
+                if start.is_none_or(|t| open_time >= t) && end.is_none_or(|t| open_time <= t) {
+                    rows.push(json!([
+                        open_time,
+                        open.to_string(),
+                        high.to_string(),
+                        low.to_string(),
+                        close.to_string(),
+                        "1",
+                        open_time + step - 1,
+                        close.to_string(),
+                        1,
+                        "0.5",
+                        (close / 2.0).to_string(),
+                    ]));
+                }
+                open = *close;
+            }
+        }
+        // 指定开始时间从前取, 否则取最近的
+        if start.is_some() {
+            rows.truncate(limit);
+        } else if rows.len() > limit {
+            rows.drain(..rows.len() - limit);
+        }
+        Ok(Value::Array(rows))
+    }
+
+    fn place(&self, market: &str, params: &HashMap<String, String>) -> MockResult {
+        let symbol = params.get("symbol").cloned().unwrap_or_default();
+        let side = params.get("side").cloned().unwrap_or_default();
+        let order_type = params.get("type").cloned().unwrap_or_default();
+        if !self.script.symbols.is_empty()
+            && !self
+                .script
+                .symbols
+                .iter()
+                .any(|s| s.market == market && s.symbol == symbol)
+        {
+            return Err(MockError::bad_request(-1121, "Invalid symbol."));
+        }
+        let qty: f64 = param(params, "quantity")
+            .filter(|q| *q > 0.0)
+            .ok_or_else(|| {
+                MockError::bad_request(
+                    -1102,
+                    "Mandatory parameter 'quantity' was not sent, was empty/null, or malformed.",
+                )
+            })?;
+        let price: f64 = match order_type.as_str() {
+            "MARKET" => self.last_price(market, &symbol),
+            _ => param(params, "price").ok_or_else(|| {
+                MockError::bad_request(
+                    -1102,
+                    "Mandatory parameter 'price' was not sent, was empty/null, or malformed.",
+                )
+            })?,
+        };
+
+        let rule = {
+            let mut rules = self.rules.lock().unwrap();
+            rules
+                .iter_mut()
+                .find(|r| r.matches(market, &symbol, &side))
+                .map(|r| {
+                    if let Some(times) = r.times.as_mut() {
+                        *times -= 1;
+                    }
+                    r.clone()
+                })
+        };
+        let behavior = rule.as_ref().map_or(Behavior::Fill, |r| r.behavior);
+        let (executed, status) = match behavior {
+            Behavior::Fill => (qty, "FILLED"),
+            Behavior::Expire => (0.0, "EXPIRED"),
+            Behavior::Partial => {
+                let ratio = rule.as_ref().map_or(0.5, |r| r.fill_ratio);
+                ((qty * ratio * 1e8).round() / 1e8, "PARTIALLY_FILLED")
+            }
+            Behavior::New => (0.0, "NEW"),
+            Behavior::Error => {
+                let rule = rule.unwrap();
+                return Err(MockError::bad_request(rule.code, &rule.msg));
+            }
+        };
+
+        let order_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let order = MockOrder {
+            market: market.to_string(),
+            order_id,
+            client_order_id: params
+                .get("newClientOrderId")
+                .cloned()
+                .unwrap_or_else(|| format!("mock{}", order_id)),
+            symbol,
+            side,
+            order_type,
+            time_in_force: params
+                .get("timeInForce")
+                .cloned()
+                .unwrap_or_else(|| "GTC".to_string()),
+            reduce_only: params.get("reduceOnly").is_some_and(|v| v == "true"),
+            price,
+            qty,
+            executed,
+            status: status.to_string(),
+            time: now(),
+        };
+        let body = order_json(&order, self.cum_base(&order));
+        self.orders.lock().unwrap().push(order);
+        Ok(body)
+    }
+
+    fn order(&self, market: &str, params: &HashMap<String, String>) -> MockResult {
+        let order_id: u64 = param(params, "orderId").unwrap_or_default();
+        self.orders
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|o| o.market == market && o.order_id == order_id)
+            .map(|o| order_json(o, self.cum_base(o)))
+            .ok_or_else(|| MockError::bad_request(-2013, "Order does not exist."))
+    }
+
+    fn cancel(&self, market: &str, params: &HashMap<String, String>) -> MockResult {
+        let order_id: u64 = param(params, "orderId").unwrap_or_default();
+        let mut orders = self.orders.lock().unwrap();
+        let order = orders
+            .iter_mut()
+            .find(|o| {
+                o.market == market
+                    && o.order_id == order_id
+                    && (o.status == "NEW" || o.status == "PARTIALLY_FILLED")
+            })
+            .ok_or_else(|| MockError::bad_request(-2011, "Unknown order sent."))?;
+        order.status = "CANCELED".to_string();
+        let cum_base = self.cum_base(order);
+        Ok(order_json(order, cum_base))
+    }
+
+    // 每个有成交的订单一笔成交, 手续费: 现货/U本位按成交额计 USDT, 币本位按张数面值折算成币
+    fn trades(&self, market: &str, params: &HashMap<String, String>) -> MockResult {
+        let order_id: Option<u64> = param(params, "orderId");
+        let symbol = params.get("symbol").map(String::as_str).unwrap_or_default();
+        let trades: Vec<Value> = self
+            .orders
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|o| o.market == market && o.symbol == symbol && o.executed > 0.0)
+            .filter(|o| order_id.is_none_or(|id| o.order_id == id))
+            .map(|o| {
+                let (commission, asset) = match market {
+                    "delivery" => {
+                        let size = self.contract_size(&o.symbol) as f64;
+                        let asset = o.symbol.split("USD").next().unwrap_or_default();
+                        (
+                            o.executed * size / o.price * self.script.fee_rate,
+                            asset.to_string(),
+                        )
+                    }
+                    _ => (
+                        o.executed * o.price * self.script.fee_rate,
+                        "USDT".to_string(),
+                    ),
+                };
+                // 现货为 isBuyer, 合约为 buyer
+                let buyer = if market == "spot" { "isBuyer" } else { "buyer" };
+                json!({
+                    "symbol": o.symbol,
+                    "id": o.order_id,
+                    "orderId": o.order_id,
+                    "price": o.price.to_string(),
+                    "qty": o.executed.to_string(),
+                    "commission": commission.to_string(),
+                    "commissionAsset": asset,
+                    "time": o.time,
+                    buyer: o.side == "BUY",
+                })
+            })
+            .collect();
+        Ok(Value::Array(trades))
+    }
+
+    fn transfer(&self, params: &HashMap<String, String>) -> MockResult {
+        let tran_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        self.transfers.lock().unwrap().push(MockTransfer {
+            tran_id,
+            transfer_type: params.get("type").cloned().unwrap_or_default(),
+            asset: params.get("asset").cloned().unwrap_or_default(),
+            amount: param(params, "amount").unwrap_or_default(),
+        });
+        Ok(json!({ "tranId": tran_id }))
+    }
+
+    // 币本位成交的币数量 = 张数 * 面值 / 价格, 其余市场为成交数量
+    fn cum_base(&self, o: &MockOrder) -> f64 {
+        if o.market != "delivery" || o.price <= 0.0 {
+            return o.executed;
+        }
+        let size = self.contract_size(&o.symbol) as f64;
+        (o.executed * size / o.price * 1e8).round() / 1e8
+    }
+
+    fn contract_size(&self, symbol: &str) -> u64 {
+        self.script
+            .symbols
+            .iter()
+            .find(|s| s.market == "delivery" && s.symbol == symbol)
+            .and_then(|s| s.contract_size)
+            .unwrap_or(DEFAULT_CONTRACT_SIZE)
+    }
+
+    // 市价单按推送的第一个价格成交
+    fn last_price(&self, market: &str, symbol: &str) -> f64 {
+        self.script
+            .tickers
+            .iter()
+            .find(|t| t.market == market && t.symbol == symbol)
+            .and_then(|t| t.prices.first().copied())
+            .unwrap_or_default()
+    }
+}
+
+// 现货及合约订单字段的并集, 现货 Transaction/Order/OrderCanceled 及合约 FuturesTransaction 均可解析
+fn order_json(o: &MockOrder, cum_base: f64) -> Value {
+    json!({
+        "symbol": o.symbol,
+        "orderId": o.order_id,
+        "orderListId": -1,
+        "clientOrderId": o.client_order_id,
+        "origClientOrderId": o.client_order_id,
+        "transactTime": o.time,
+        "price": o.price.to_string(),
+        "avgPrice": if o.executed > 0.0 { o.price.to_string() } else { "0".to_string() },
+        "origQty": o.qty.to_string(),
+        "executedQty": o.executed.to_string(),
+        "cumQty": o.executed.to_string(),
+        "cumBase": cum_base.to_string(),
+        "cumQuote": (o.executed * o.price).to_string(),
+        "cummulativeQuoteQty": (o.executed * o.price).to_string(),
+        "status": o.status,
+        "timeInForce": o.time_in_force,
+        "type": o.order_type,
+        "origType": o.order_type,
+        "side": o.side,
+        "positionSide": "BOTH",
+        "reduceOnly": o.reduce_only,
+        "closePosition": false,
+        "stopPrice": "0",
+        "icebergQty": "0",
+        "origQuoteOrderQty": "0",
+        "workingType": "CONTRACT_PRICE",
+        "priceProtect": false,
+        "isWorking": true,
+        "time": o.time,
+        "updateTime": o.time,
+        "fills": [],
+    })
+}
+
+async fn serve_ws(listener: TcpListener, mock: Arc<Mock>) {
+    let token = shutdown::token();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn(ws_connection(stream, mock.clone()));
+                }
+                Err(e) => error!(err:? = e; "mock ws accept err"),
+            },
+            _ = token.cancelled() => return,
+        }
+    }
+}
+
+// 路径 /<market>/ws/..., 每个间隔推送该市场所有脚本交易对的 miniTicker 数组
+// 握手回调的错误类型由 tungstenite 决定
+#[allow(clippy::result_large_err)]
+async fn ws_connection(stream: TcpStream, mock: Arc<Mock>) {
+    let mut path = String::new();
+    let callback = |req: &Request, resp: WsResponse| {
+        path = req.uri().path().to_string();
+        Ok(resp)
+    };
+    let mut socket = match tokio_tungstenite::accept_hdr_async(stream, callback).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!(err:? = e; "mock ws handshake err");
+            return;
+        }
+    };
+    let market = path
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let tickers: Vec<_> = mock
+        .script
+        .tickers
+        .iter()
+        .filter(|t| t.market == market && !t.prices.is_empty())
+        .collect();
+    let interval = tickers.iter().map(|t| t.interval_ms).min().unwrap_or(1000);
+    let token = shutdown::token();
+    let mut interval = tokio::time::interval(Duration::from_millis(interval));
+    let mut tick: usize = 0;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if tickers.is_empty() {
+                    continue;
+                }
+                let events: Vec<Value> = tickers
+                    .iter()
+                    .map(|t| {
+                        let price = t.prices[tick % t.prices.len()].to_string();
+                        json!({
+                            "e": "24hrMiniTicker",
+                            "E": now(),
+                            "s": t.symbol,
+                            "c": price,
+                            "o": price,
+                            "h": price,
+                            "l": price,
+                            "v": "0",
+                            "q": "0",
+                        })
+                    })
+                    .collect();
+                tick += 1;
+                if socket.send(Message::Text(Value::Array(events).to_string())).await.is_err() {
+                    return;
+                }
+            }
+            msg = socket.next() => match msg {
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => return,
+                _ => {}
+            },
+            _ = token.cancelled() => {
+                let _ = socket.close(None).await;
+                return;
+            }
+        }
+    }
+}
+
+fn param<T: std::str::FromStr>(params: &HashMap<String, String>, key: &str) -> Option<T> {
+    params.get(key).and_then(|v| v.parse().ok())
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+// 1m/1h/1d/1w
+fn interval_ms(interval: &str) -> Option<i64> {
+    let (n, unit) = interval.split_at(interval.len().checked_sub(1)?);
+    let unit = match unit {
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        "w" => 7 * 86_400_000,
+        _ => return None,
+    };
+    Some(n.parse::<i64>().ok()? * unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binance::ws_model::WebSocketEvent;
+
+    #[tokio::test]
+    async fn test_ws_tickers() {
+        let script: Script = toml::from_str(
+            r#"
+            [[tickers]]
+            market = "futures"
+            symbol = "ETHUSDT"
+            prices = [3785.0, 3786.5]
+            interval_ms = 10
+            "#,
+        )
+        .unwrap();
+        let server = MockServer::start(script, "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap();
+        let url = format!("{}/ws/!miniTicker@arr", server.config().futures_ws_endpoint);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut prices = vec![];
+        while prices.len() < 3 {
+            let Some(Ok(Message::Text(text))) = socket.next().await else {
+                panic!("mock ws closed");
+            };
+            let Ok(WebSocketEvent::MiniTicker(events)) = serde_json::from_str(&text) else {
+                panic!("unexpected event {}", text);
+            };
+            prices.push(events[0].current_close.clone().unwrap());
+        }
+        assert_eq!(prices, vec!["3785", "3786.5", "3785"]);
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+// 模拟服务脚本, 见 doc/mock_binance.toml
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Script {
+    // 签名接口校验的 X-MBX-APIKEY 及 HMAC secret
+    #[serde(default = "default_key")]
+    pub api_key: String,
+    #[serde(default = "default_key")]
+    pub secret_key: String,
+    // 成交手续费率, 按成交额计
+    #[serde(default = "default_fee_rate")]
+    pub fee_rate: f64,
+    // 下单按顺序匹配第一条规则, 没有匹配时全部成交
+    #[serde(default)]
+    pub orders: Vec<OrderRule>,
+    #[serde(default)]
+    pub symbols: Vec<SymbolScript>,
+    #[serde(default)]
+    pub klines: Vec<KlineScript>,
+    #[serde(default)]
+    pub tickers: Vec<TickerScript>,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Script {
+            api_key: default_key(),
+            secret_key: default_key(),
+            fee_rate: default_fee_rate(),
+            orders: vec![],
+            symbols: vec![],
+            klines: vec![],
+            tickers: vec![],
+        }
+    }
+}
+
+fn default_key() -> String {
+    "mock".to_string()
+}
+
+fn default_fee_rate() -> f64 {
+    0.001
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Behavior {
+    // 全部成交
+    Fill,
+    // FOK 未成交过期
+    Expire,
+    // 按 fill_ratio 部分成交
+    Partial,
+    // 挂单不成交
+    New,
+    // 返回 code/msg 错误
+    Error,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OrderRule {
+    // spot/futures/delivery, 不配置匹配全部
+    pub market: Option<String>,
+    pub symbol: Option<String>,
+    // BUY/SELL
+    pub side: Option<String>,
+    pub behavior: Behavior,
+    #[serde(default = "default_fill_ratio")]
+    pub fill_ratio: f64,
+    #[serde(default = "default_error_code")]
+    pub code: i32,
+    #[serde(default)]
+    pub msg: String,
+    // 生效次数, 用完后匹配后续规则, 不配置一直生效
+    pub times: Option<u32>,
+}
+
+fn default_fill_ratio() -> f64 {
+    0.5
+}
+
+fn default_error_code() -> i32 {
+    -2010
+}
+
+impl OrderRule {
+    pub fn matches(&self, market: &str, symbol: &str, side: &str) -> bool {
+        self.times != Some(0)
+            && self.market.as_deref().is_none_or(|m| m == market)
+            && self.symbol.as_deref().is_none_or(|s| s == symbol)
+            && self.side.as_deref().is_none_or(|s| s == side)
+    }
+}
+
+// exchangeInfo 中的交易对规则
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SymbolScript {
+    pub market: String,
+    pub symbol: String,
+    #[serde(default = "default_tick")]
+    pub tick_size: f64,
+    #[serde(default = "default_tick")]
+    pub step_size: f64,
+    #[serde(default)]
+    pub min_qty: f64,
+    #[serde(default)]
+    pub min_notional: f64,
+    // 币本位合约面值(USD)
+    pub contract_size: Option<u64>,
+    pub delivery_date: Option<i64>,
+}
+
+fn default_tick() -> f64 {
+    0.01
+}
+
+// 按收盘价生成的K线, 开盘价为上一根收盘价
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KlineScript {
+    pub market: String,
+    pub symbol: String,
+    pub interval: String,
+    // 第一根K线开盘时间(ms)
+    pub start: i64,
+    pub closes: Vec<f64>,
+}
+
+// websocket 按间隔依次推送的价格, 推送完最后一个价格后循环
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TickerScript {
+    pub market: String,
+    pub symbol: String,
+    pub prices: Vec<f64>,
+    #[serde(default = "default_ticker_interval_ms")]
+    pub interval_ms: u64,
+}
+
+fn default_ticker_interval_ms() -> u64 {
+    1000
+}
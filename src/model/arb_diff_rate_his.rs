@@ -7,6 +7,8 @@ pub struct ArbDiffRateHis {
     pub diff_rate_id: i64,
     pub diff_price: Decimal,
     pub diff_rate: Decimal,
+    // 年化差价比率, 无交割日期时为空
+    pub annualized_rate: Option<Decimal>,
     pub created: Option<i64>,
     pub updated: Option<i64>,
     pub bak: Option<String>,
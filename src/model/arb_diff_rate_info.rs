@@ -16,6 +16,10 @@ pub struct ArbDiffRateInfo {
     pub to_price: Decimal,
     pub diff_price: Decimal,
     pub diff_rate: Decimal,
+    // 年化差价比率, 无交割日期时为空
+    pub annualized_rate: Option<Decimal>,
+    // 交割时间(毫秒)
+    pub delivery_time: Option<i64>,
     pub created: Option<i64>,
     pub updated: Option<i64>,
     pub bak: Option<String>,
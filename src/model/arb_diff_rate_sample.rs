@@ -0,0 +1,11 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default, sqlx::FromRow)]
+pub struct ArbDiffRateSample {
+    pub id: i64,
+    pub diff_rate_id: i64,
+    // 不截断的差价比率
+    pub diff_rate: Decimal,
+    pub created: i64,
+}
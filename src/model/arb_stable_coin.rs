@@ -6,6 +6,7 @@ pub const DOING_STATUS_RUN: i8 = 1;
 pub const DOING_STATUS_DONE: i8 = 2;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default, sqlx::FromRow)]
+#[serde(default)]
 pub struct ArbStableCoin {
     pub id: i64,
     pub user_id: i64,
@@ -16,6 +17,8 @@ pub struct ArbStableCoin {
     pub price_truncate: i8,
     pub amt_truncate: i8,
     pub strategy: String,
+    // 指标及开平仓规则(JSON), 为空时按 strategy 使用预设, 见 service::indicator
+    pub signal_config: String,
     pub option_open: Decimal,
     pub option_close: Decimal,
     pub option_amt: Decimal,
@@ -5,7 +5,15 @@ pub const DOING_STATUS_UN_RUN: i8 = 0;
 pub const DOING_STATUS_RUN: i8 = 1;
 pub const DOING_STATUS_DONE: i8 = 2;
 
+// 开平仓信号: 差价比率与 option_open/option_close 比较
+pub const SIGNAL_MODE_THRESHOLD: &str = "threshold";
+// 差价比率最近 lookback 条逐秒采样的 z-score 与 option_open/option_close 比较
+pub const SIGNAL_MODE_ZSCORE: &str = "zscore";
+// 年化差价比率与 option_open/option_close 比较, 需要交割合约
+pub const SIGNAL_MODE_ANNUALIZED: &str = "annualized";
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default, sqlx::FromRow)]
+#[serde(default)]
 pub struct ArbStrategy {
     pub id: i64,
     pub diff_rate_id: i64,
@@ -25,6 +33,8 @@ pub struct ArbStrategy {
     pub to_from_desc: String,
     pub option_open: Decimal,
     pub option_close: Decimal,
+    pub signal_mode: String,
+    pub lookback: i64,
     pub option_amt: Decimal,
     pub contract_mul: i64,
     pub margin_mul: i64,
@@ -0,0 +1,21 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default, sqlx::FromRow)]
+pub struct ArbStrategyPnl {
+    pub id: i64,
+    pub arb_strategy_id: i64,
+    pub user_id: i64,
+    pub coin: String,
+    // 已扣手续费、含资金费
+    pub realized_usdt: Decimal,
+    pub unrealized_usdt: Decimal,
+    pub realized_coin: Decimal,
+    pub unrealized_coin: Decimal,
+    pub fee_usdt: Decimal,
+    pub funding_usdt: Decimal,
+    // 计算时币种现货价格
+    pub coin_price: Decimal,
+    pub created: Option<i64>,
+    pub updated: Option<i64>,
+}
@@ -1,11 +1,13 @@
 pub mod arb_diff_rate;
 pub mod arb_diff_rate_his;
 pub mod arb_diff_rate_info;
+pub mod arb_diff_rate_sample;
 pub mod arb_stable_coin;
 pub mod arb_stable_coin_info;
 pub mod arb_strategy;
 pub mod arb_strategy_ex;
 pub mod arb_strategy_ex_info;
+pub mod arb_strategy_pnl;
 pub mod arb_coin_price;
 pub mod arb_diff_signal;
 
@@ -14,8 +16,10 @@ pub use arb_diff_signal::ArbDiffSignal;
 pub use arb_diff_rate::ArbDiffRate;
 pub use arb_diff_rate_his::ArbDiffRateHis;
 pub use arb_diff_rate_info::ArbDiffRateInfo;
+pub use arb_diff_rate_sample::ArbDiffRateSample;
 pub use arb_stable_coin::ArbStableCoin;
 pub use arb_stable_coin_info::ArbStableCoinInfo;
 pub use arb_strategy::ArbStrategy;
 pub use arb_strategy_ex::ArbStrategyEx;
 pub use arb_strategy_ex_info::ArbStrategyExInfo;
+pub use arb_strategy_pnl::ArbStrategyPnl;
@@ -0,0 +1,109 @@
+use crate::binance::account;
+use chrono::{NaiveDate, NaiveTime};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+
+// 交割合约年化基差: 差价比率 * 365 / 距交割天数
+//
+// 交割时间优先从交易对后缀解析(币本位 BTCUSD_240628、U本位 BTCUSDT_240628, 交割日 08:00 UTC),
+// 否则取 exchangeInfo 的 deliveryDate, 永续合约及现货没有交割时间
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+// 永续合约 deliveryDate 为 2100 年之后
+const PERPETUAL_DELIVERY_MS: i64 = 4_102_444_800_000;
+const ANNUALIZED_SCALE: u32 = 4;
+
+// (market, symbol) -> 交割时间
+static DELIVERY: Lazy<DashMap<(String, String), Option<i64>>> = Lazy::new(DashMap::new);
+
+/// 从交易对后缀 _YYMMDD 解析交割时间(毫秒)
+pub fn parse_delivery_time(symbol: &str) -> Option<i64> {
+    let (_, suffix) = symbol.rsplit_once('_')?;
+    if suffix.len() != 6 {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(suffix, "%y%m%d").ok()?;
+    let time = date.and_time(NaiveTime::from_hms_opt(8, 0, 0)?);
+    Some(time.and_utc().timestamp_millis())
+}
+
+/// 交易对的交割时间, 永续合约及现货为 None; 查询结果缓存, 查询失败不缓存下次重试
+pub async fn delivery_time(market: &str, symbol: &str) -> anyhow::Result<Option<i64>> {
+    if market == "spot" {
+        return Ok(None);
+    }
+    let key = (market.to_string(), symbol.to_string());
+    if let Some(t) = DELIVERY.get(&key) {
+        return Ok(*t);
+    }
+    let t = match parse_delivery_time(symbol) {
+        Some(t) => Some(t),
+        None => account::public_api()
+            .get_symbol_info(market, symbol)
+            .await?
+            .delivery_date
+            .filter(|d| *d < PERPETUAL_DELIVERY_MS),
+    };
+    DELIVERY.insert(key, t);
+    Ok(t)
+}
+
+/// from/to 中较早的交割时间
+pub async fn pair_delivery_time(
+    from_market: &str,
+    from_symbol: &str,
+    to_market: &str,
+    to_symbol: &str,
+) -> anyhow::Result<Option<i64>> {
+    let from = delivery_time(from_market, from_symbol).await?;
+    let to = delivery_time(to_market, to_symbol).await?;
+    Ok(from.into_iter().chain(to).min())
+}
+
+/// 年化差价比率, 距交割不足 min_expiry_ms 返回 None
+pub fn annualize(
+    rate: Decimal,
+    now_ms: i64,
+    delivery_ms: i64,
+    min_expiry_ms: i64,
+) -> Option<Decimal> {
+    let remaining = delivery_ms - now_ms;
+    if remaining <= 0 || remaining < min_expiry_ms {
+        return None;
+    }
+    let mut annualized = rate.checked_mul(Decimal::from(365 * DAY_MS))? / Decimal::from(remaining);
+    annualized.rescale(ANNUALIZED_SCALE);
+    Some(annualized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annualize() {
+        let t = parse_delivery_time("BTCUSD_240628").unwrap();
+        assert_eq!(t, 1_719_561_600_000);
+        assert_eq!(parse_delivery_time("BTCUSDT_240628"), Some(t));
+        assert_eq!(parse_delivery_time("BTCUSD_PERP"), None);
+        assert_eq!(parse_delivery_time("BTCUSDT"), None);
+
+        // 2% 基差, 距交割 7 天与 91.25 天
+        let rate = Decimal::new(2, 2);
+        assert_eq!(
+            annualize(rate, t - 7 * DAY_MS, t, DAY_MS),
+            Some(Decimal::new(10429, 4))
+        );
+        assert_eq!(
+            annualize(rate, t - 365 * DAY_MS / 4, t, DAY_MS),
+            Some(Decimal::new(800, 4))
+        );
+        assert_eq!(annualize(rate, t, t, 0), None);
+
+        // 距交割不足 1 天不年化
+        assert_eq!(annualize(rate, t - DAY_MS, t, DAY_MS), Some(Decimal::new(73, 1)));
+        assert_eq!(annualize(rate, t - DAY_MS + 1, t, DAY_MS), None);
+        assert!(annualize(rate, t - DAY_MS + 1, t, 0).is_some());
+    }
+}
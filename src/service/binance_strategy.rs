@@ -4,54 +4,51 @@ use crate::binance::api::{
 use crate::binance::rest_model::{
     OrderSide, OrderStatus, OrderType, TimeInForce, UniversalTransferType,
 };
-use crate::binance::MyApi;
-use crate::service::common;
+use crate::binance::{account, MyApi};
+use crate::conf::C;
+use crate::helper::{metrics, shutdown};
+use crate::service::{scheduler, signal};
+use crate::service::template::{self, LegMarket, Source, Template};
 use crate::{model, sql};
 use anyhow::anyhow;
 use chrono::Local;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
 use rust_decimal::Decimal;
-use std::collections::HashMap;
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Mul, Sub};
+use std::time::Duration;
 use tokio::select;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-
-pub async fn event_start(rxs: HashMap<i64, UnboundedReceiver<model::ArbStrategy>>) {
-    for (_, mut rx) in rxs {
-        let api = MyApi::new();
-        tokio::spawn(async move {
-            loop {
-                select! {
-                    Some(strategy) = rx.recv() => {
-                        match strategy.option_choose.as_str() {
-                            // 逻辑处理 正向positive， 差价比率 >= 0.05 现货spot买入 -> transfer到币本期货 -> delivery卖出，
-                            // 差价比率 <= 0 delivery买入 -> transfer到现货 -> 现货spot卖出
-                            "positive" => {
-                                if let Err(e) = positive(api.clone(), strategy).await {
-                                    error!("positive err: {:?}", e);
-                                }
-                            },
-                            // 逻辑处理 反向reverse, 差价比率 <= -0.05 U本位: 远期futures买入 -> futures永续卖出 -> 差价比率 >= 0.0 futures永续买入 -> 远期futures卖出,
-                            // 币本位: 远期delivery买入 -> delivery永续卖出 -> 差价比率 >= 0.0 delivery永续买入 -> 远期delivery卖出
-                            "reverse" => {
-                                if let Err(e) = reverse(api.clone(), strategy).await {
-                                    error!("positive err: {:?}", e);
-                                }
-                            },
-                            _ => {
-
-                            }
-                        }
-                    }
-                }
-            }
-        });
+
+/// 执行策略的一个步骤, 由 scheduler 保证同一策略不会并发执行
+pub(crate) async fn run_strategy(strategy: model::ArbStrategy) {
+    // 按策略所属账户获取 api
+    let api = match account::get_api(strategy.user_id) {
+        Ok(api) => api,
+        Err(e) => {
+            error!(strategy_id = strategy.id, user_id = strategy.user_id, err:? = e; "get api err");
+            return;
+        }
+    };
+    let (strategy_id, user_id) = (strategy.id, strategy.user_id);
+    let template = match template::find(&strategy) {
+        Ok(t) => t,
+        Err(e) => {
+            error!(strategy_id = strategy_id, err:? = e; "get template err");
+            return;
+        }
+    };
+    if let Err(e) = execute(api, strategy, template).await {
+        error!(strategy_id = strategy_id, user_id = user_id, template = template.name.as_str(), err:? = e; "execute err");
     }
 }
 
-async fn positive(api: MyApi, strategy: model::ArbStrategy) -> anyhow::Result<()> {
-    info!("positive: {:?}", strategy.id);
+// 按模板顺序执行下一个未完成的 leg, 每次只执行一步
+async fn execute(
+    api: MyApi,
+    strategy: model::ArbStrategy,
+    template: &Template,
+) -> anyhow::Result<()> {
+    info!(strategy_id = strategy.id, template = template.name.as_str(); "execute");
     // 获取执行策略列表
     let arb_ex_list = sql::get_arb_strategy_ex_list_by_strategy_id(strategy.id).await?;
     if arb_ex_list.is_empty() {
@@ -60,482 +57,123 @@ async fn positive(api: MyApi, strategy: model::ArbStrategy) -> anyhow::Result<()
             strategy.id
         ));
     }
-    // 检查策略数量 >6 错误
-    if arb_ex_list.len() != 6 {
+    if arb_ex_list.len() != template.legs.len() {
         return Err(anyhow!("arb count err: {:?}", arb_ex_list.len()));
     }
 
-    let mut arb_ex_map = HashMap::new();
-    let mut arb_strategy_done_count = 0;
-    for ex in arb_ex_list {
-        arb_ex_map.insert(ex.clone().option_type, ex.clone());
-        if ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE {
-            arb_strategy_done_count += 1;
-        }
-    }
+    let done: Vec<bool> = arb_ex_list
+        .iter()
+        .map(|ex| ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE)
+        .collect();
     // 判断当前策略是否已经完成
-    if arb_strategy_done_count == 6 {
+    let Some(next) = template::next_leg(&done)? else {
         let _ =
             sql::update_strategy_by_id(strategy.id, model::arb_strategy::DOING_STATUS_DONE).await?;
         return Err(anyhow!(
             "current strategy already done, strategy_id: {:?}",
             strategy.id
         ));
-    }
+    };
 
-    // 开仓
+    let leg = &template.legs[next];
+    let ex = &arb_ex_list[next];
     let diff_rate_info = sql::get_arb_diff_rate_info_by_diff_rate_id(strategy.diff_rate_id).await?;
-    if diff_rate_info.diff_rate >= strategy.option_open {
-        // 1、from market buy 买入现货
-        let from_market_buy_ex = arb_ex_map
-            .get("spot_buy")
-            .ok_or(anyhow!("get arb_ex_map spot_buy error"))?;
-        if from_market_buy_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-            // 判断执行顺序
-            if arb_strategy_done_count != 0 {
-                return Err(anyhow!(
-                    "done count err, spot buy, count: {}",
-                    arb_strategy_done_count
-                ));
-            }
-
-            let mut price = diff_rate_info.from_price.add(strategy.fok_diff);
-            price.rescale(strategy.from_price_truncate as u32);
-            let mut amount = from_market_buy_ex.option_amount;
-            amount.rescale(strategy.from_amt_truncate as u32);
-            let _ = spot_order_update(
-                api,
-                strategy.from_symbol.clone(),
-                OrderSide::Buy,
-                OrderType::Limit,
-                "spot_buy".to_string(),
-                price,
-                amount,
-                &strategy,
-                from_market_buy_ex,
-            )
-            .await?;
-
-            return Ok(());
-        }
-
-        // transfer spot to delivery 划转币现货到币本位交割期货
-        let transfer_spot_to_delivery_ex = arb_ex_map
-            .get("transfer_spot_to_delivery")
-            .ok_or(anyhow!("get arb_ex_map transfer_spot_to_delivery error"))?;
-        if transfer_spot_to_delivery_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE
-        {
-            // 判断执行顺序
-            if arb_strategy_done_count != 1 {
-                return Err(anyhow!(
-                    "done count err, transfer_spot_to_delivery, count: {}",
-                    arb_strategy_done_count
-                ));
-            }
-
-            let mut amount = from_market_buy_ex
-                .option_executed_amt
-                .mul(Decimal::from(1).sub(strategy.spot_fee));
-            amount.rescale(strategy.from_amt_truncate as u32);
-            let _ = transfer_coin(
-                api,
-                strategy.coin.clone(),
-                UniversalTransferType::MainCmfuture,
-                amount,
-                "transfer_spot_to_delivery".to_string(),
-                &strategy,
-                transfer_spot_to_delivery_ex,
-            )
-            .await?;
-
-            return Ok(());
-        }
-
-        // to market sell 卖出币本位交割
-        let to_market_sell_ex = arb_ex_map
-            .get("delivery_sell")
-            .ok_or(anyhow!("get arb_ex_map delivery_sell error"))?;
-        if to_market_sell_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-            // 判断执行顺序
-            if arb_strategy_done_count != 2 {
-                return Err(anyhow!(
-                    "done count err, delivery sell, count: {}",
-                    arb_strategy_done_count
-                ));
-            }
-
-            // 计算可开张数
-            let cont = transfer_spot_to_delivery_ex
-                .option_executed_amt
-                .mul(diff_rate_info.to_price)
-                .div(Decimal::from(strategy.contract_mul));
-            let contract_num = cont.ceil().sub(Decimal::from(1));
-            let mut price = diff_rate_info.to_price.sub(strategy.fok_diff);
-            price.rescale(strategy.to_price_truncate as u32);
-            let _ = delivery_order_update(
-                api,
-                strategy.to_symbol.clone(),
-                OrderSide::Sell,
-                OrderType::Limit,
-                "delivery_sell".to_string(),
-                price,
-                contract_num,
-                &strategy,
-                to_market_sell_ex,
-            )
-            .await?;
-
-            return Ok(());
-        }
+    let Some(value) = signal::value(&strategy, &diff_rate_info).await? else {
+        debug!(strategy_id = strategy.id, signal_mode = strategy.signal_mode.as_str(); "signal warming up");
+        return Ok(());
+    };
+    if !template.triggered(leg.phase, value, &strategy) {
+        return Ok(());
     }
 
-    // 平仓
-    if diff_rate_info.diff_rate <= strategy.option_close {
-        // to market buy 买入币本位远期期货
-        let to_market_buy_ex = arb_ex_map
-            .get("delivery_buy")
-            .ok_or(anyhow!("get arb_ex_map delivery_buy error"))?;
-        if to_market_buy_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-            // 判断执行顺序
-            if arb_strategy_done_count != 3 {
-                return Err(anyhow!(
-                    "done count err, delivery buy, count: {}",
-                    arb_strategy_done_count
-                ));
-            }
-
-            // 获取卖出张数
-            let to_market_sell_ex = arb_ex_map
-                .get("delivery_sell")
-                .ok_or(anyhow!("get arb_ex_map delivery_sell error"))?;
-
-            let mut price = diff_rate_info.to_price.add(strategy.fok_diff);
-            price.rescale(strategy.to_price_truncate as u32);
-            let _ = delivery_order_update(
-                api,
-                strategy.to_symbol.clone(),
-                OrderSide::Buy,
-                OrderType::Limit,
-                "delivery_buy".to_string(),
-                price,
-                to_market_sell_ex.option_executed_amt,
-                &strategy,
-                to_market_buy_ex,
-            )
-            .await?;
-
-            return Ok(());
-        }
-
-        // transfer delivery to spot  划转币本位交割期货到现货
-        let transfer_delivery_to_spot_ex = arb_ex_map
-            .get("transfer_delivery_to_spot")
-            .ok_or(anyhow!("get arb_ex_map transfer_delivery_to_spot error"))?;
-        if transfer_delivery_to_spot_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE
-        {
-            // 判断执行顺序
-            if arb_strategy_done_count != 4 {
-                return Err(anyhow!(
-                    "done count err, transfer_delivery_to_spot, count: {}",
-                    arb_strategy_done_count
-                ));
-            }
-
-            // 计算可划转数量
+    let (market, price, price_truncate) = match leg.market {
+        LegMarket::From => (
+            strategy.from_market.as_str(),
+            diff_rate_info.from_price,
+            strategy.from_price_truncate,
+        ),
+        LegMarket::To => (
+            strategy.to_market.as_str(),
+            diff_rate_info.to_price,
+            strategy.to_price_truncate,
+        ),
+        LegMarket::Transfer => ("transfer", Decimal::ZERO, 0),
+    };
+
+    // 数量来源
+    let q = &leg.quantity;
+    let base = match (q.source, q.leg) {
+        (Source::Executed, Some(i)) => arb_ex_list[i].option_executed_amt,
+        (Source::Contracts, Some(i)) => arb_ex_list[i].option_executed_amt,
+        (Source::ExchangeBase, Some(i)) => {
             let order = api
                 .delivery_order_status(FuturesGetOrderRequest {
-                    symbol: strategy.to_symbol.clone(),
-                    order_id: Some(to_market_buy_ex.current_order_id.clone()),
+                    symbol: arb_ex_list[i].symbol.clone(),
+                    order_id: Some(arb_ex_list[i].current_order_id.clone()),
                     orig_client_order_id: None,
                 })
                 .await?;
-            let mut amount = Decimal::from_f64(order.cum_base)
-                .ok_or(anyhow!(""))?
-                .mul(Decimal::from(1).sub(strategy.delivery_fee));
-            amount.rescale(strategy.to_amt_truncate as u32);
-            let _ = transfer_coin(
+            Decimal::from_f64(order.cum_base).ok_or(anyhow!(""))?
+        }
+        _ => ex.option_amount,
+    };
+    let amount = leg.amount(&strategy, base, price);
+
+    if leg.market == LegMarket::Transfer {
+        let transfer_type = leg
+            .transfer
+            .clone()
+            .ok_or(anyhow!("template transfer required"))?;
+        return transfer_coin(
+            api,
+            strategy.coin.clone(),
+            transfer_type,
+            amount,
+            leg.option_type.clone(),
+            &strategy,
+            ex,
+        )
+        .await;
+    }
+
+    let side = leg.side.clone().ok_or(anyhow!("template side required"))?;
+    let mut price = match side {
+        OrderSide::Buy => price.add(strategy.fok_diff),
+        OrderSide::Sell => price.sub(strategy.fok_diff),
+    };
+    price.rescale(price_truncate as u32);
+    match market {
+        "spot" => {
+            spot_order_update(
                 api,
-                strategy.coin.clone(),
-                UniversalTransferType::CmfutureMain,
+                ex.symbol.clone(),
+                side,
+                leg.order_type.clone(),
+                leg.option_type.clone(),
+                price,
                 amount,
-                "transfer_spot_to_delivery".to_string(),
                 &strategy,
-                transfer_delivery_to_spot_ex,
+                ex,
             )
-            .await?;
-
-            return Ok(());
+            .await
         }
-
-        // from market sell 卖出现货
-        let from_market_sell_ex = arb_ex_map
-            .get("spot_sell")
-            .ok_or(anyhow!("get arb_ex_map spot_sell error"))?;
-        if from_market_sell_ex
-            .option_status
-            // transfer spot to delivery 划转币现货到币本位交割期货
-            != model::arb_strategy_ex::OPTION_STATUS_DONE
-        {
-            // 判断执行顺序
-            if arb_strategy_done_count != 5 {
-                return Err(anyhow!(
-                    "done count err, spot_sell, count: {}",
-                    arb_strategy_done_count
-                ));
-            }
-
-            let mut price = diff_rate_info.from_price.sub(strategy.fok_diff);
-            price.rescale(strategy.from_price_truncate as u32);
-            let mut amount = transfer_delivery_to_spot_ex.option_executed_amt;
-            amount.rescale(strategy.from_amt_truncate as u32);
-            let _ = spot_order_update(
+        "futures" | "delivery" => {
+            contract_order_update(
                 api,
-                strategy.from_symbol.clone(),
-                OrderSide::Sell,
-                OrderType::Limit,
-                "spot_sell".to_string(),
+                market,
+                ex.symbol.clone(),
+                side,
+                leg.order_type.clone(),
+                leg.option_type.clone(),
                 price,
                 amount,
                 &strategy,
-                from_market_sell_ex,
+                ex,
             )
-            .await?;
-
-            return Ok(());
-        }
-    }
-
-    Ok(())
-}
-
-async fn reverse(api: MyApi, strategy: model::ArbStrategy) -> anyhow::Result<()> {
-    info!("reverse: {:?}", strategy.id);
-    // 获取执行策略列表
-    let arb_ex_list = sql::get_arb_strategy_ex_list_by_strategy_id(strategy.id).await?;
-    if arb_ex_list.is_empty() {
-        return Err(anyhow!(
-            "get arb_strategy_ex list err, by strategy_id: {:?}",
-            strategy.id
-        ));
-    }
-    // 检查策略数量 >6 错误
-    if arb_ex_list.len() != 4 {
-        return Err(anyhow!("arb count err: {:?}", arb_ex_list.len()));
-    }
-
-    let mut arb_ex_map = HashMap::new();
-    let mut arb_strategy_done_count = 0;
-    for ex in arb_ex_list {
-        let key = format!("{}-{}", ex.option_type, ex.symbol);
-        arb_ex_map.insert(key, ex.clone());
-        if ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE {
-            arb_strategy_done_count += 1;
-        }
-    }
-    info!("{:?}", arb_ex_map.keys());
-    // 判断当前策略是否已经完成
-    if arb_strategy_done_count == 4 {
-        let _ =
-            sql::update_strategy_by_id(strategy.id, model::arb_strategy::DOING_STATUS_DONE).await?;
-        return Err(anyhow!(
-            "current strategy already done, strategy_id: {:?}",
-            strategy.id
-        ));
-    }
-
-    let diff_rate_info = sql::get_arb_diff_rate_info_by_diff_rate_id(strategy.diff_rate_id).await?;
-    // 判断反向套利，是币本位还是U本位
-    // USDM U本位
-    if strategy.from_market == "futures" && strategy.to_market == "futures" {
-        // 开仓
-        if diff_rate_info.diff_rate <= strategy.option_open {
-            // from market buy 买入远期
-            let from_market_buy = format!("{}_buy-{}", strategy.from_market, strategy.from_symbol);
-            // to market sell 卖出永续
-            let to_market_sell = format!("{}_sell-{}", strategy.to_market, strategy.to_symbol);
-            info!("open: {}, {}", from_market_buy, to_market_sell);
-        }
-        // 平仓
-        if diff_rate_info.diff_rate >= strategy.option_close {
-            // to market buy 买入永续
-            let to_market_buy = format!("{}_buy-{}", strategy.to_market, strategy.to_symbol);
-            // from market sell 卖出远期
-            let from_market_sell =
-                format!("{}_sell-{}", strategy.from_market, strategy.from_symbol);
-            info!("close: {}, {}", to_market_buy, from_market_sell);
-        }
-    } else if strategy.from_market == "delivery" && strategy.to_market == "delivery" {
-        // COINM 币本位
-        // 开仓
-        if diff_rate_info.diff_rate <= strategy.option_open {
-            // from market buy 买入远期
-            let from_market_buy_key =
-                format!("{}_buy-{}", strategy.from_market, strategy.from_symbol);
-            let from_market_buy_ex = arb_ex_map
-                .get(&from_market_buy_key)
-                .ok_or(anyhow!("get arb_ex_map delivery buy error"))?;
-            if from_market_buy_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-                // 判断执行顺序
-                if arb_strategy_done_count != 0 {
-                    return Err(anyhow!(
-                        "done count err, {:?}, count: {}",
-                        from_market_buy_key,
-                        arb_strategy_done_count
-                    ));
-                }
-                // 计算可开张数
-                let cont = from_market_buy_ex
-                    .option_amount
-                    .mul(diff_rate_info.from_price)
-                    .div(Decimal::from(strategy.contract_mul));
-                let contract_num = cont.ceil().sub(Decimal::from(1));
-                let mut price = diff_rate_info.from_price.add(strategy.fok_diff);
-                price.rescale(strategy.from_price_truncate as u32);
-                let _ = delivery_order_update(
-                    api,
-                    strategy.to_symbol.clone(),
-                    OrderSide::Buy,
-                    OrderType::Limit,
-                    "delivery_buy".to_string(),
-                    price,
-                    contract_num,
-                    &strategy,
-                    from_market_buy_ex,
-                )
-                .await?;
-
-                return Ok(());
-            }
-
-            // to market sell 卖出永续
-            let to_market_sell_key = format!("{}_sell-{}", strategy.to_market, strategy.to_symbol);
-            let to_market_sell_ex = arb_ex_map
-                .get(&to_market_sell_key)
-                .ok_or(anyhow!("get arb_ex_map delivery sell error"))?;
-            if to_market_sell_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-                // 判断执行顺序
-                if arb_strategy_done_count != 1 {
-                    return Err(anyhow!(
-                        "done count err, {:?}, count: {}",
-                        to_market_sell_key,
-                        arb_strategy_done_count
-                    ));
-                }
-                // 计算可开张数
-                let cont = to_market_sell_ex
-                    .option_amount
-                    .mul(diff_rate_info.to_price)
-                    .div(Decimal::from(strategy.contract_mul));
-                let contract_num = cont.ceil().sub(Decimal::from(1));
-                let mut price = diff_rate_info.from_price.sub(strategy.fok_diff);
-                price.rescale(strategy.to_price_truncate as u32);
-                let _ = delivery_order_update(
-                    api,
-                    strategy.to_symbol.clone(),
-                    OrderSide::Sell,
-                    OrderType::Limit,
-                    "delivery_sell".to_string(),
-                    price,
-                    contract_num,
-                    &strategy,
-                    to_market_sell_ex,
-                )
-                .await?;
-
-                return Ok(());
-            }
-
-            // info!("open: {}, {}", from_market_buy_key, to_market_sell_key);
-        }
-        // 平仓
-        if diff_rate_info.diff_rate >= strategy.option_close {
-            // to market buy 买入永续
-            let to_market_buy_key = format!("{}_buy-{}", strategy.to_market, strategy.to_symbol);
-            let to_market_buy_ex = arb_ex_map
-                .get(&to_market_buy_key)
-                .ok_or(anyhow!("get arb_ex_map delivery buy error"))?;
-            if to_market_buy_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-                // 判断执行顺序
-                if arb_strategy_done_count != 2 {
-                    return Err(anyhow!(
-                        "done count err, {:?}, count: {}",
-                        to_market_buy_key,
-                        arb_strategy_done_count
-                    ));
-                }
-
-                // 获取永续卖出的张数
-                let contract_num = arb_ex_map
-                    .get(&format!(
-                        "{}_sell-{}",
-                        strategy.to_market, strategy.to_symbol
-                    ))
-                    .ok_or(anyhow!("get arb_ex_map delivery sell error"))?
-                    .option_executed_amt;
-                let mut price = diff_rate_info.to_price.add(strategy.fok_diff);
-                price.rescale(strategy.to_price_truncate as u32);
-                let _ = delivery_order_update(
-                    api,
-                    strategy.to_symbol.clone(),
-                    OrderSide::Buy,
-                    OrderType::Limit,
-                    "delivery_buy".to_string(),
-                    price,
-                    contract_num,
-                    &strategy,
-                    to_market_buy_ex,
-                )
-                .await?;
-
-                return Ok(());
-            }
-
-            // from market sell 卖出远期
-            let from_market_sell_key =
-                format!("{}_sell-{}", strategy.from_market, strategy.from_symbol);
-            let from_market_sell_ex = arb_ex_map
-                .get(&from_market_sell_key)
-                .ok_or(anyhow!("get arb_ex_map delivery sell error"))?;
-            if from_market_sell_ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
-                // 判断执行顺序
-                if arb_strategy_done_count != 3 {
-                    return Err(anyhow!(
-                        "done count err, {:?}, count: {}",
-                        from_market_sell_key,
-                        arb_strategy_done_count
-                    ));
-                }
-
-                // 获取远期买入的张数
-                let contract_num = arb_ex_map
-                    .get(&format!(
-                        "{}_buy-{}",
-                        strategy.from_market, strategy.from_symbol
-                    ))
-                    .ok_or(anyhow!("get arb_ex_map delivery buy error"))?
-                    .option_executed_amt;
-                let mut price = diff_rate_info.from_price.add(strategy.fok_diff);
-                price.rescale(strategy.from_price_truncate as u32);
-                let _ = delivery_order_update(
-                    api,
-                    strategy.from_symbol.clone(),
-                    OrderSide::Sell,
-                    OrderType::Limit,
-                    "delivery_sell".to_string(),
-                    price,
-                    contract_num,
-                    &strategy,
-                    from_market_sell_ex,
-                )
-                .await?;
-
-                return Ok(());
-            }
-            // info!("close: {}, {}", to_market_buy_key, from_market_sell_key);
+            .await
         }
+        _ => Err(anyhow!("unsupported market: {}", market)),
     }
-
-    Ok(())
 }
 
 async fn spot_order_update(
@@ -563,37 +201,48 @@ async fn spot_order_update(
                 ..OrderRequest::default()
             })
             .await?;
-        warn!("strategy_id: {}, {} place order, symbol: {}, side: {:?}, order_type: {:?}, amount: {}, price: {}, order_id: {}",
-			strategy.id, option_type.clone(), symbol.clone(), order_side.clone(), order_type.clone(), amount, price, transaction.order_id);
-        // 更新订单ID
-        let mut data = HashMap::new();
-        data.insert(
-            "current_order_id".to_string(),
-            transaction.order_id.to_string(),
+        warn!(
+            strategy_id = strategy.id,
+            ex_id = ex.id,
+            option_type = option_type.as_str(),
+            symbol = symbol.as_str(),
+            side:? = order_side,
+            order_type:? = order_type,
+            amount:% = amount,
+            price:% = price,
+            order_id = transaction.order_id;
+            "place order"
         );
-        let _ = sql::update_strategy_ex_by_id(ex.id, data).await?;
-
-        // 插入详情表
-        let _ = sql::insert_arb_strategy_ex_info(model::ArbStrategyExInfo {
-            id: 0,
-            user_id: strategy.user_id,
-            platform: strategy.platform.clone(),
-            option_choose: strategy.option_choose.clone(),
-            arb_strategy_id: strategy.id,
-            arb_strategy_ex_id: ex.id,
-            coin: strategy.coin.clone(),
-            market: ex.market.clone(),
-            symbol: ex.symbol.clone(),
-            option_type: option_type,
-            price,
-            amount,
-            executed_amt: Decimal::ZERO,
-            order_id: transaction.order_id.to_string(),
-            is_ok: model::arb_strategy_ex_info::IS_OK_UN_DONE,
-            created: Some(Local::now().timestamp()),
-            updated: None,
-            bak: None,
-        })
+        metrics::order(&ex.option_type, "placed");
+        // 稍后检查订单状态
+        scheduler::wake_after(
+            strategy.id,
+            Duration::from_millis(C.scheduler.order_check_ms),
+        );
+        // 更新订单ID, 插入详情表
+        let _ = sql::leg_order_placed(
+            ex.id,
+            model::ArbStrategyExInfo {
+                id: 0,
+                user_id: strategy.user_id,
+                platform: strategy.platform.clone(),
+                option_choose: strategy.option_choose.clone(),
+                arb_strategy_id: strategy.id,
+                arb_strategy_ex_id: ex.id,
+                coin: strategy.coin.clone(),
+                market: ex.market.clone(),
+                symbol: ex.symbol.clone(),
+                option_type: option_type,
+                price,
+                amount,
+                executed_amt: Decimal::ZERO,
+                order_id: transaction.order_id.to_string(),
+                is_ok: model::arb_strategy_ex_info::IS_OK_UN_DONE,
+                created: Some(Local::now().timestamp()),
+                updated: None,
+                bak: None,
+            },
+        )
         .await?;
     } else {
         // 已经下单处理
@@ -611,46 +260,29 @@ async fn spot_order_update(
         // info!("{:?} {:?}", order, ex_info);
 
         if order.status == OrderStatus::Filled {
-            let mut ex_data = HashMap::new();
-            ex_data.insert("option_amount".to_string(), order.executed_qty.to_string());
-            ex_data.insert(
-                "option_executed_amt".to_string(),
-                order.executed_qty.to_string(),
-            );
-            ex_data.insert(
-                "option_status".to_string(),
-                model::arb_strategy_ex::OPTION_STATUS_DONE.to_string(),
-            );
-            let _ = sql::update_strategy_ex_by_id(ex.id, ex_data).await?;
-
-            let mut ex_info_data = HashMap::new();
-            ex_info_data.insert("executed_amt".to_string(), order.executed_qty.to_string());
-            ex_info_data.insert(
-                "is_ok".to_string(),
-                model::arb_strategy_ex_info::IS_OK_DONE.to_string(),
-            );
-            let _ = sql::update_strategy_ex_info_by_id(ex_info.id, ex_info_data).await?;
+            let executed_amt = Decimal::from_f64(order.executed_qty).ok_or(anyhow!(""))?;
+            sql::leg_filled(ex.id, ex_info.id, executed_amt).await?;
+            metrics::order(&ex.option_type, "filled");
         } else {
             // 订单未立即全部成交，取消
-            info!("order not filled, canceled");
-            let mut ex_data = HashMap::new();
-            ex_data.insert("current_order_id".to_string(), "".to_string());
-            let _ = sql::update_strategy_ex_by_id(ex.id, ex_data).await?;
-
-            let mut ex_info_data = HashMap::new();
-            ex_info_data.insert(
-                "is_ok".to_string(),
-                model::arb_strategy_ex_info::IS_OK_EXPIRED.to_string(),
+            info!(
+                strategy_id = strategy.id,
+                ex_id = ex.id,
+                order_id = ex.current_order_id.as_str();
+                "order not filled, canceled"
             );
-            let _ = sql::update_strategy_ex_info_by_id(ex_info.id, ex_info_data).await?;
+            sql::leg_expired(ex.id, ex_info.id).await?;
+            metrics::order(&ex.option_type, "expired");
         }
     }
 
     Ok(())
 }
 
-async fn delivery_order_update(
+// U本位(futures)、币本位(delivery)合约下单及订单状态
+async fn contract_order_update(
     api: MyApi,
+    market: &str,
     symbol: String,
     order_side: OrderSide,
     order_type: OrderType,
@@ -663,97 +295,94 @@ async fn delivery_order_update(
     // 下单处理
     if ex.current_order_id.is_empty() {
         // 下单
-        let transaction = api
-            .delivery_place_order(FuturesOrderRequest {
-                symbol: symbol.clone(),
-                side: order_side.clone(),
-                order_type: order_type.clone(),
-                quantity: Some(amount.to_f64().ok_or(anyhow!(""))?),
-                price: Some(price.to_f64().ok_or(anyhow!(""))?),
-                time_in_force: Some(TimeInForce::FOK),
-                recv_window: None,
-            })
-            .await?;
-
-        warn!("strategy_id: {}, {} place order, symbol: {}, side: {:?}, order_type: {:?}, amount: {}, price: {}, order_id: {}",
-			strategy.id, option_type.clone(), symbol.clone(), order_side.clone(), order_type.clone(), amount, price, transaction.order_id);
-        // 更新订单ID
-        let mut data = HashMap::new();
-        data.insert(
-            "current_order_id".to_string(),
-            transaction.order_id.to_string(),
+        let request = FuturesOrderRequest {
+            symbol: symbol.clone(),
+            side: order_side.clone(),
+            order_type: order_type.clone(),
+            quantity: Some(amount.to_f64().ok_or(anyhow!(""))?),
+            price: Some(price.to_f64().ok_or(anyhow!(""))?),
+            time_in_force: Some(TimeInForce::FOK),
+            reduce_only: None,
+            recv_window: None,
+        };
+        let transaction = match market {
+            "futures" => api.futures_place_order(request).await?,
+            _ => api.delivery_place_order(request).await?,
+        };
+
+        warn!(
+            strategy_id = strategy.id,
+            ex_id = ex.id,
+            option_type = option_type.as_str(),
+            symbol = symbol.as_str(),
+            side:? = order_side,
+            order_type:? = order_type,
+            amount:% = amount,
+            price:% = price,
+            order_id = transaction.order_id;
+            "place order"
         );
-        let _ = sql::update_strategy_ex_by_id(ex.id, data).await?;
-
-        // 插入详情表
-        let _ = sql::insert_arb_strategy_ex_info(model::ArbStrategyExInfo {
-            id: 0,
-            user_id: strategy.user_id,
-            platform: strategy.platform.clone(),
-            option_choose: strategy.option_choose.clone(),
-            arb_strategy_id: strategy.id,
-            arb_strategy_ex_id: ex.id,
-            coin: strategy.coin.clone(),
-            market: ex.market.clone(),
-            symbol: ex.symbol.clone(),
-            option_type: option_type,
-            price,
-            amount,
-            executed_amt: Decimal::ZERO,
-            order_id: transaction.order_id.to_string(),
-            is_ok: model::arb_strategy_ex_info::IS_OK_UN_DONE,
-            created: Some(Local::now().timestamp()),
-            updated: None,
-            bak: None,
-        })
+        metrics::order(&ex.option_type, "placed");
+        // 稍后检查订单状态
+        scheduler::wake_after(
+            strategy.id,
+            Duration::from_millis(C.scheduler.order_check_ms),
+        );
+        // 更新订单ID, 插入详情表
+        let _ = sql::leg_order_placed(
+            ex.id,
+            model::ArbStrategyExInfo {
+                id: 0,
+                user_id: strategy.user_id,
+                platform: strategy.platform.clone(),
+                option_choose: strategy.option_choose.clone(),
+                arb_strategy_id: strategy.id,
+                arb_strategy_ex_id: ex.id,
+                coin: strategy.coin.clone(),
+                market: ex.market.clone(),
+                symbol: ex.symbol.clone(),
+                option_type: option_type,
+                price,
+                amount,
+                executed_amt: Decimal::ZERO,
+                order_id: transaction.order_id.to_string(),
+                is_ok: model::arb_strategy_ex_info::IS_OK_UN_DONE,
+                created: Some(Local::now().timestamp()),
+                updated: None,
+                bak: None,
+            },
+        )
         .await?;
     } else {
         // 已经下单处理
-        let order = api
-            .delivery_order_status(FuturesGetOrderRequest {
-                symbol: symbol,
-                order_id: Some(ex.current_order_id.clone()),
-                orig_client_order_id: None,
-            })
-            .await?;
+        let request = FuturesGetOrderRequest {
+            symbol,
+            order_id: Some(ex.current_order_id.clone()),
+            orig_client_order_id: None,
+        };
+        let order = match market {
+            "futures" => api.futures_order_status(request).await?,
+            _ => api.delivery_order_status(request).await?,
+        };
 
         let ex_info =
             sql::get_arb_strategy_ex_info_by_order_id(ex.current_order_id.clone()).await?;
         // info!("{:?} {:?}", order, ex_info);
 
         if order.status == "FILLED".to_string() {
-            let mut ex_data = HashMap::new();
-            ex_data.insert("option_amount".to_string(), order.executed_qty.to_string());
-            ex_data.insert(
-                "option_executed_amt".to_string(),
-                order.executed_qty.to_string(),
-            );
-            ex_data.insert(
-                "option_status".to_string(),
-                model::arb_strategy_ex::OPTION_STATUS_DONE.to_string(),
-            );
-            let _ = sql::update_strategy_ex_by_id(ex.id, ex_data).await?;
-
-            let mut ex_info_data = HashMap::new();
-            ex_info_data.insert("executed_amt".to_string(), order.executed_qty.to_string());
-            ex_info_data.insert(
-                "is_ok".to_string(),
-                model::arb_strategy_ex_info::IS_OK_DONE.to_string(),
-            );
-            let _ = sql::update_strategy_ex_info_by_id(ex_info.id, ex_info_data).await?;
+            let executed_amt = Decimal::from_f64(order.executed_qty).ok_or(anyhow!(""))?;
+            sql::leg_filled(ex.id, ex_info.id, executed_amt).await?;
+            metrics::order(&ex.option_type, "filled");
         } else {
             // 订单未立即全部成交，取消
-            info!("order not filled, canceled");
-            let mut ex_data = HashMap::new();
-            ex_data.insert("current_order_id".to_string(), "".to_string());
-            let _ = sql::update_strategy_ex_by_id(ex.id, ex_data).await?;
-
-            let mut ex_info_data = HashMap::new();
-            ex_info_data.insert(
-                "is_ok".to_string(),
-                model::arb_strategy_ex_info::IS_OK_EXPIRED.to_string(),
+            info!(
+                strategy_id = strategy.id,
+                ex_id = ex.id,
+                order_id = ex.current_order_id.as_str();
+                "order not filled, canceled"
             );
-            let _ = sql::update_strategy_ex_info_by_id(ex_info.id, ex_info_data).await?;
+            sql::leg_expired(ex.id, ex_info.id).await?;
+            metrics::order(&ex.option_type, "expired");
         }
     }
     Ok(())
@@ -776,49 +405,51 @@ async fn transfer_coin(
         )
         .await?;
     warn!(
-        "strategy_id: {}, {}, coin: {}, amount: {}, transfer_id: {}",
-        strategy.id, option_type, coin, amount, transfer.tran_id
+        strategy_id = strategy.id,
+        ex_id = ex.id,
+        option_type = option_type.as_str(),
+        coin = coin.as_str(),
+        amount:% = amount,
+        order_id = transfer.tran_id;
+        "transfer"
     );
 
-    // 插入详情表
-    let _ = sql::insert_arb_strategy_ex_info(model::ArbStrategyExInfo {
-        id: 0,
-        user_id: strategy.user_id,
-        platform: strategy.platform.clone(),
-        option_choose: strategy.option_choose.clone(),
-        arb_strategy_id: strategy.id,
-        arb_strategy_ex_id: ex.id,
-        coin: strategy.coin.clone(),
-        market: ex.market.clone(),
-        symbol: ex.symbol.clone(),
-        option_type: option_type,
-        price: Decimal::ZERO,
-        amount,
-        executed_amt: amount,
-        order_id: transfer.tran_id.to_string(),
-        is_ok: model::arb_strategy_ex_info::IS_OK_DONE,
-        created: Some(Local::now().timestamp()),
-        updated: Some(Local::now().timestamp()),
-        bak: None,
-    })
+    // 插入详情表, leg 完成
+    let _ = sql::leg_transferred(
+        ex.id,
+        model::ArbStrategyExInfo {
+            id: 0,
+            user_id: strategy.user_id,
+            platform: strategy.platform.clone(),
+            option_choose: strategy.option_choose.clone(),
+            arb_strategy_id: strategy.id,
+            arb_strategy_ex_id: ex.id,
+            coin: strategy.coin.clone(),
+            market: ex.market.clone(),
+            symbol: ex.symbol.clone(),
+            option_type: option_type,
+            price: Decimal::ZERO,
+            amount,
+            executed_amt: amount,
+            order_id: transfer.tran_id.to_string(),
+            is_ok: model::arb_strategy_ex_info::IS_OK_DONE,
+            created: Some(Local::now().timestamp()),
+            updated: Some(Local::now().timestamp()),
+            bak: None,
+        },
+    )
     .await?;
 
-    let mut ex_data = HashMap::new();
-    ex_data.insert("current_order_id".to_string(), transfer.tran_id.to_string());
-    ex_data.insert(
-        "option_status".to_string(),
-        model::arb_strategy_ex::OPTION_STATUS_DONE.to_string(),
-    );
-    ex_data.insert("option_amount".to_string(), amount.to_string());
-    ex_data.insert("option_executed_amt".to_string(), amount.to_string());
-    let _ = sql::update_strategy_ex_by_id(ex.id, ex_data).await?;
-
     Ok(())
 }
 
 pub async fn range_new_strategy() {
+    let token = shutdown::token();
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(2)) => {}
+        }
 
         match sql::get_arb_strategy_list_by_doing_status(model::arb_strategy::DOING_STATUS_RUN)
             .await
@@ -835,58 +466,16 @@ pub async fn range_new_strategy() {
                         }
                     }
 
-                    let mut ex_desc_map = HashMap::new();
-                    let mut count = 0;
-                    if strategy.option_choose == "positive" {
-                        if let Ok(ex_desc) = common::new_positive_desc(strategy.clone()) {
-                            ex_desc_map = ex_desc;
-                            count = 6;
-                        }
-                    } else if strategy.option_choose == "reverse" {
-                        if strategy.from_market == "delivery" && strategy.to_market == "delivery" {
-                            if let Ok(ex_desc) = common::new_reverse_coinm_desc(strategy.clone()) {
-                                ex_desc_map = ex_desc;
-                                count = 4;
-                            }
-                        } else if strategy.from_market == "futures"
-                            && strategy.to_market == "futures"
-                        {
-                            if let Ok(ex_desc) = common::new_reverse_usdm_desc(strategy.clone()) {
-                                ex_desc_map = ex_desc;
-                                count = 4;
-                            }
+                    let template = match template::find(&strategy) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error!(strategy_id = strategy.id, err:? = e; "get template err");
+                            continue;
                         }
-                    }
+                    };
 
-                    for i in 0..count {
-                        if let Some(ex) = ex_desc_map.get(&i) {
-                            // ex
-                            if let Ok(last_id) = sql::insert_arb_strategy_ex(model::ArbStrategyEx {
-                                id: 0,
-                                user_id: strategy.user_id.clone(),
-                                platform: strategy.platform.clone(),
-                                option_choose: strategy.option_choose.clone(),
-                                arb_strategy_id: strategy.id.clone(),
-                                coin: strategy.coin.clone(),
-                                market: ex.clone().market,
-                                symbol: ex.clone().symbol,
-                                option_type: ex.clone().option_type,
-                                option_status: model::arb_strategy_ex::OPTION_STATUS_UN_DONE,
-                                option_amount: strategy
-                                    .option_amt
-                                    .clone()
-                                    .mul(Decimal::from(strategy.margin_mul.clone())),
-                                option_executed_amt: Decimal::ZERO,
-                                current_order_id: "".to_string(),
-                                created: Some(Local::now().timestamp()),
-                                updated: Some(Local::now().timestamp()),
-                                bak: None,
-                            })
-                            .await
-                            {
-                                info!("insert arb_strategy_ex id: {:?}", last_id);
-                            }
-                        }
+                    if let Err(e) = create_strategy_ex(&strategy, template).await {
+                        error!(strategy_id = strategy.id, err:? = e; "create arb_strategy_ex err");
                     }
                 }
             }
@@ -897,26 +486,346 @@ pub async fn range_new_strategy() {
     }
 }
 
-pub async fn inspect_strategy(txs: HashMap<i64, UnboundedSender<model::ArbStrategy>>) {
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+// 按模板 legs 顺序生成 arb_strategy_ex
+pub(crate) async fn create_strategy_ex(
+    strategy: &model::ArbStrategy,
+    template: &Template,
+) -> anyhow::Result<()> {
+    for leg in template.legs.iter() {
+        let (market, symbol) = leg.ex_market_symbol(strategy);
+        let last_id = sql::insert_arb_strategy_ex(model::ArbStrategyEx {
+            id: 0,
+            user_id: strategy.user_id,
+            platform: strategy.platform.clone(),
+            option_choose: strategy.option_choose.clone(),
+            arb_strategy_id: strategy.id,
+            coin: strategy.coin.clone(),
+            market,
+            symbol,
+            option_type: leg.option_type.clone(),
+            option_status: model::arb_strategy_ex::OPTION_STATUS_UN_DONE,
+            option_amount: strategy
+                .option_amt
+                .mul(Decimal::from(strategy.margin_mul)),
+            option_executed_amt: Decimal::ZERO,
+            current_order_id: "".to_string(),
+            created: Some(Local::now().timestamp()),
+            updated: Some(Local::now().timestamp()),
+            bak: None,
+        })
+        .await?;
+        info!(strategy_id = strategy.id, ex_id = last_id; "insert arb_strategy_ex");
+    }
+    Ok(())
+}
 
-        match sql::get_arb_strategy_list_by_doing_status(model::arb_strategy::DOING_STATUS_RUN)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::mock::{MockServer, Script};
+
+    #[tokio::test]
+    async fn test_positive_legs_on_sqlite() {
+        db::init_test_env().await.unwrap();
+        let strategy = model::ArbStrategy {
+            diff_rate_id: 1,
+            user_id: 1,
+            platform: "binance".to_string(),
+            option_choose: "positive".to_string(),
+            coin: "BTC".to_string(),
+            from_market: "spot".to_string(),
+            from_symbol: "BTCUSDT".to_string(),
+            to_market: "delivery".to_string(),
+            to_symbol: "BTCUSD_240628".to_string(),
+            option_open: Decimal::new(5, 2),
+            option_amt: Decimal::new(12345, 4),
+            contract_mul: 100,
+            margin_mul: 1,
+            spot_fee: Decimal::new(1, 3),
+            doing_status: model::arb_strategy::DOING_STATUS_RUN,
+            ..Default::default()
+        };
+        let id = sql::insert_arb_strategy(strategy).await.unwrap() as i64;
+        let strategy = sql::get_arb_strategy_by_id(id).await.unwrap();
+        assert_eq!(strategy.option_amt, Decimal::new(12345, 4));
+
+        let templates = template::builtin().unwrap();
+        let positive = templates.iter().find(|t| t.name == "positive").unwrap();
+        create_strategy_ex(&strategy, positive).await.unwrap();
+        let ex_list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        assert_eq!(ex_list.len(), positive.legs.len());
+        assert_eq!(ex_list[1].market, "transfer");
+
+        // 第一个 leg 下单并成交
+        let info_id = sql::leg_order_placed(
+            ex_list[0].id,
+            model::ArbStrategyExInfo {
+                arb_strategy_id: id,
+                arb_strategy_ex_id: ex_list[0].id,
+                order_id: "1001".to_string(),
+                price: Decimal::new(60000, 0),
+                amount: ex_list[0].option_amount,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap() as i64;
+        sql::leg_filled(ex_list[0].id, info_id, Decimal::new(12345, 4))
             .await
-        {
-            Ok(strategy_list) => {
-                for s in strategy_list {
-                    let sharding = s.id % 10;
-                    if let Some(tx) = txs.get(&sharding) {
-                        if let Err(e) = tx.send(s.clone()) {
-                            error!("send error: {:?}", e.to_string());
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                error!("{:?}", e);
-            }
+            .unwrap();
+
+        let ex_list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let done: Vec<bool> = ex_list
+            .iter()
+            .map(|ex| ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE)
+            .collect();
+        assert_eq!(template::next_leg(&done).unwrap(), Some(1));
+        assert_eq!(ex_list[0].option_executed_amt, Decimal::new(12345, 4));
+        let info = sql::get_arb_strategy_ex_info_by_order_id("1001".to_string())
+            .await
+            .unwrap();
+        assert_eq!(info.is_ok, model::arb_strategy_ex_info::IS_OK_DONE);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_usdm_on_mock() {
+        db::init_test_env().await.unwrap();
+        let server = MockServer::start(Script::default(), "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap();
+        let diff_rate_id = 35_001;
+        sql::insert_arb_diff_rate_info(model::ArbDiffRateInfo {
+            diff_rate_id,
+            from_price: Decimal::new(61000, 0),
+            to_price: Decimal::new(60000, 0),
+            diff_rate: Decimal::new(-20, 3),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let strategy = model::ArbStrategy {
+            diff_rate_id,
+            user_id: 1,
+            platform: "binance".to_string(),
+            option_choose: "reverse".to_string(),
+            coin: "BTC".to_string(),
+            from_market: "futures".to_string(),
+            from_symbol: "BTCUSDT_240628".to_string(),
+            from_amt_truncate: 3,
+            to_market: "futures".to_string(),
+            to_symbol: "BTCUSDT".to_string(),
+            to_amt_truncate: 3,
+            option_open: Decimal::new(-10, 3),
+            option_close: Decimal::ZERO,
+            option_amt: Decimal::new(12, 3),
+            margin_mul: 1,
+            fok_diff: Decimal::ONE,
+            doing_status: model::arb_strategy::DOING_STATUS_RUN,
+            ..Default::default()
+        };
+        let id = sql::insert_arb_strategy(strategy).await.unwrap() as i64;
+        let strategy = sql::get_arb_strategy_by_id(id).await.unwrap();
+        let templates = template::builtin().unwrap();
+        let usdm = templates.iter().find(|t| t.name == "reverse_usdm").unwrap();
+        create_strategy_ex(&strategy, usdm).await.unwrap();
+
+        // 远期买入: 下单 -> 查询成交
+        execute(server.api(), strategy.clone(), usdm).await.unwrap();
+        let orders = server.orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].market, "futures");
+        assert_eq!(orders[0].symbol, "BTCUSDT_240628");
+        assert_eq!(orders[0].side, "BUY");
+        assert_eq!(orders[0].price, 61001.0);
+        assert_eq!(orders[0].qty, 0.012);
+        execute(server.api(), strategy.clone(), usdm).await.unwrap();
+
+        // 永续卖出, FOK 价格向下偏移
+        execute(server.api(), strategy.clone(), usdm).await.unwrap();
+        let orders = server.orders();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[1].symbol, "BTCUSDT");
+        assert_eq!(orders[1].side, "SELL");
+        assert_eq!(orders[1].price, 59999.0);
+        execute(server.api(), strategy.clone(), usdm).await.unwrap();
+
+        let ex_list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let done: Vec<bool> = ex_list
+            .iter()
+            .map(|ex| ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE)
+            .collect();
+        assert_eq!(done, vec![true, true, false, false]);
+        assert_eq!(ex_list[1].option_executed_amt, Decimal::new(12, 3));
+    }
+
+    #[tokio::test]
+    async fn test_positive_spot_on_mock() {
+        db::init_test_env().await.unwrap();
+        let server = MockServer::start(Script::default(), "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap();
+        let diff_rate_id = 48_001;
+        sql::insert_arb_diff_rate_info(model::ArbDiffRateInfo {
+            diff_rate_id,
+            from_price: Decimal::new(60000, 0),
+            to_price: Decimal::new(61000, 0),
+            diff_rate: Decimal::new(20, 3),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let strategy = model::ArbStrategy {
+            diff_rate_id,
+            user_id: 1,
+            platform: "binance".to_string(),
+            option_choose: "positive".to_string(),
+            coin: "BTC".to_string(),
+            from_market: "spot".to_string(),
+            from_symbol: "BTCUSDT".to_string(),
+            from_amt_truncate: 3,
+            to_market: "delivery".to_string(),
+            to_symbol: "BTCUSD_240628".to_string(),
+            option_open: Decimal::new(10, 3),
+            option_amt: Decimal::new(12, 3),
+            contract_mul: 100,
+            margin_mul: 1,
+            spot_fee: Decimal::new(1, 3),
+            fok_diff: Decimal::ONE,
+            doing_status: model::arb_strategy::DOING_STATUS_RUN,
+            ..Default::default()
+        };
+        let id = sql::insert_arb_strategy(strategy).await.unwrap() as i64;
+        let strategy = sql::get_arb_strategy_by_id(id).await.unwrap();
+        let templates = template::builtin().unwrap();
+        let positive = templates.iter().find(|t| t.name == "positive").unwrap();
+        create_strategy_ex(&strategy, positive).await.unwrap();
+
+        // 现货买入: 下单 -> 查询成交
+        execute(server.api(), strategy.clone(), positive).await.unwrap();
+        let orders = server.orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].market, "spot");
+        assert_eq!(orders[0].symbol, "BTCUSDT");
+        assert_eq!(orders[0].side, "BUY");
+        assert_eq!(orders[0].time_in_force, "FOK");
+        assert_eq!(orders[0].price, 60001.0);
+        assert_eq!(orders[0].qty, 0.012);
+        execute(server.api(), strategy.clone(), positive).await.unwrap();
+
+        // 扣除现货手续费后划转到币本位
+        execute(server.api(), strategy.clone(), positive).await.unwrap();
+        let transfers = server.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].transfer_type, "MAIN_CMFUTURE");
+        assert_eq!(transfers[0].asset, "BTC");
+        assert_eq!(transfers[0].amount, 0.011);
+
+        let ex_list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        assert_eq!(ex_list[0].option_executed_amt, Decimal::new(12, 3));
+        assert_eq!(ex_list[1].option_executed_amt, Decimal::new(11, 3));
+        let done: Vec<bool> = ex_list
+            .iter()
+            .map(|ex| ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE)
+            .collect();
+        assert_eq!(template::next_leg(&done).unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_positive_cycle_on_mock() {
+        db::init_test_env().await.unwrap();
+        let server = MockServer::start(Script::default(), "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap();
+        let diff_rate_id = 38_001;
+        let info_id = sql::insert_arb_diff_rate_info(model::ArbDiffRateInfo {
+            diff_rate_id,
+            from_price: Decimal::new(3000, 0),
+            to_price: Decimal::new(3050, 0),
+            diff_rate: Decimal::new(166, 4),
+            ..Default::default()
+        })
+        .await
+        .unwrap() as i64;
+        let strategy = model::ArbStrategy {
+            diff_rate_id,
+            user_id: 1,
+            platform: "binance".to_string(),
+            option_choose: "positive".to_string(),
+            coin: "ETH".to_string(),
+            from_market: "spot".to_string(),
+            from_symbol: "ETHUSDT".to_string(),
+            from_amt_truncate: 3,
+            to_market: "delivery".to_string(),
+            to_symbol: "ETHUSD_240628".to_string(),
+            to_amt_truncate: 3,
+            option_open: Decimal::new(10, 3),
+            option_close: Decimal::ZERO,
+            option_amt: Decimal::new(5, 1),
+            contract_mul: 10,
+            margin_mul: 1,
+            spot_fee: Decimal::new(1, 3),
+            delivery_fee: Decimal::new(5, 4),
+            fok_diff: Decimal::ONE,
+            doing_status: model::arb_strategy::DOING_STATUS_RUN,
+            ..Default::default()
+        };
+        let id = sql::insert_arb_strategy(strategy).await.unwrap() as i64;
+        let strategy = sql::get_arb_strategy_by_id(id).await.unwrap();
+        let templates = template::builtin().unwrap();
+        let positive = templates.iter().find(|t| t.name == "positive").unwrap();
+        create_strategy_ex(&strategy, positive).await.unwrap();
+
+        // 开仓: 现货买入 -> 划转 -> 交割卖出, 下单及查询成交各执行一次
+        for _ in 0..5 {
+            execute(server.api(), strategy.clone(), positive).await.unwrap();
+        }
+        let orders = server.orders();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[1].market, "delivery");
+        assert_eq!(orders[1].symbol, "ETHUSD_240628");
+        assert_eq!(orders[1].side, "SELL");
+        assert_eq!(orders[1].price, 3049.0);
+        // 0.499 ETH * 3050 / 10, 取整后少开一张
+        assert_eq!(orders[1].qty, 152.0);
+
+        // 差价未回落, 不平仓
+        execute(server.api(), strategy.clone(), positive).await.unwrap();
+        assert_eq!(server.orders().len(), 2);
+
+        // 平仓: 交割买入 -> 按成交币数量扣除手续费划转 -> 现货卖出
+        sql::update_arb_diff_rate_info_by_id(
+            info_id,
+            Decimal::new(3020, 0),
+            Decimal::new(3010, 0),
+            Decimal::new(-10, 0),
+            Decimal::new(-33, 4),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        for _ in 0..5 {
+            execute(server.api(), strategy.clone(), positive).await.unwrap();
         }
+        let orders = server.orders();
+        assert_eq!(orders.len(), 4);
+        assert_eq!(orders[2].side, "BUY");
+        assert_eq!(orders[2].qty, 152.0);
+        assert_eq!(orders[2].price, 3011.0);
+        let transfers = server.transfers();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[1].transfer_type, "CMFUTURE_MAIN");
+        // 152 * 10 / 3011 * (1 - 0.0005)
+        assert_eq!(transfers[1].amount, 0.504);
+        assert_eq!(orders[3].market, "spot");
+        assert_eq!(orders[3].side, "SELL");
+        assert_eq!(orders[3].qty, 0.504);
+        assert_eq!(orders[3].price, 3019.0);
+
+        // 全部完成后策略结束
+        assert!(execute(server.api(), strategy.clone(), positive).await.is_err());
+        let strategy = sql::get_arb_strategy_by_id(id).await.unwrap();
+        assert_eq!(strategy.doing_status, model::arb_strategy::DOING_STATUS_DONE);
     }
 }
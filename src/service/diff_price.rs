@@ -9,6 +9,7 @@ use tokio::select;
 use tokio::sync::mpsc::UnboundedReceiver;
 use crate::binance::ws_model::{Market, Platform};
 use crate::service::PriceStream;
+use crate::helper::{clock, metrics, shutdown};
 
 // src/service/diff_price.rs
 
@@ -18,6 +19,7 @@ pub async fn set_price_data(mut price_rx: UnboundedReceiver<PriceStream>) {
         select! {
             event = price_rx.recv() => {
                 if let Some(stream) = event {
+                    metrics::PRICE_BACKLOG.dec();
                     for ticker in stream.tickers {
                         if ticker.current_close.is_none() {
                             continue;
@@ -26,19 +28,22 @@ pub async fn set_price_data(mut price_rx: UnboundedReceiver<PriceStream>) {
                         let price = Decimal::from_str(&ticker.current_close.unwrap()).unwrap();
                         let platform = stream.platform.clone();
                         let market = stream.market.clone();
-                        let updated = (Utc::now() + Duration::hours(2)).naive_utc().to_string();
+                        let updated = (clock::now_utc() + Duration::hours(2)).naive_utc().to_string();
                         let price_field = match platform {
                         Platform::Binance => match market {
                             Market::Spot => "binance_spot_price",
                             Market::Futures => "binance_futures_price", // Skip if market is not recognized
+                            Market::Delivery => continue, // 交割合约价格只写入价格缓存
                         },
                         Platform::Bybit => match market {
                             Market::Spot => "bybit_spot_price",
                             Market::Futures => "bybit_futures_price", // Skip if market is not recognized
+                            Market::Delivery => continue,
                         },
                         Platform::Kucoin => match market {
                             Market::Spot => "kucoin_spot_price",
                             Market::Futures => "kucoin_futures_price", // Skip if market is not recognized
+                            Market::Delivery => continue,
                         },
                         _ => continue, // Skip if platform is not recognized
                     };
@@ -78,6 +83,9 @@ pub async fn set_price_data(mut price_rx: UnboundedReceiver<PriceStream>) {
                             }
                         }
                     }
+                } else {
+                    // 行情全部关闭, 已写完剩余数据
+                    break;
                 }
             },
         }
@@ -148,6 +156,9 @@ pub async fn set_price_data(mut price_rx: UnboundedReceiver<PriceStream>) {
 #[allow(unused_assignments)]
 pub async fn get_diff_signal() {
     loop {
+        if shutdown::is_shutdown() {
+            return;
+        }
         // Fetch all rows from the arb_coin_price table
         let coin_prices = sql::get_all_arb_coin_prices().await.unwrap();
 
@@ -167,7 +178,7 @@ pub async fn get_diff_signal() {
                     let price_diff = to_price.sub(*from_price);
                     let price_diff_rate = price_diff.div(*to_price).mul(Decimal::from(100));
                     let threshold = Decimal::from_str("0.9").unwrap();
-                    let updated = (Utc::now() + Duration::hours(2)).naive_utc().to_string();
+                    let updated = (clock::now_utc() + Duration::hours(2)).naive_utc().to_string();
 
                     if price_diff_rate > threshold {
                         // Check if the entry already exists in the diff_signal table
@@ -189,6 +200,9 @@ pub async fn get_diff_signal() {
                                 error!("{:?}", e);
                             }
                         } else {
+                            metrics::SIGNALS_OPENED
+                                .with_label_values(&[&coin_price.symbol, from_compare, to_compare])
+                                .inc();
                             // Insert a new record
                             if let Err(e) = sql::insert_arb_diff_signal(
                                 model::ArbDiffSignal {
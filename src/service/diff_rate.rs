@@ -1,100 +1,142 @@
+use crate::service::{basis, scheduler, signal};
+use crate::conf::C;
+use crate::helper::clock;
 use crate::{model, service, sql};
-use chrono::Local;
-use log::{debug, error};
+use log::{debug, error, warn};
 use rust_decimal::Decimal;
 use std::collections::HashMap;
 use std::ops::{Div, Sub};
 use std::str::FromStr;
 
-#[allow(unused_assignments)]
 pub async fn set_binance_diff_rate() {
     let mut diff_rate_his_map: HashMap<i64, Decimal> = HashMap::new();
     loop {
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        match sql::get_arb_diff_rate_list_by_diff_status(model::arb_diff_rate::DIFF_STATUS_RUN)
-            .await
-        {
-            Ok(result) => {
-                for diff_rate in result {
-                    let mut from_price = Decimal::ZERO;
-                    let mut to_price = Decimal::ZERO;
-                    if let Ok(from_price_info) = service::get_binance_price(
-                        diff_rate.from_market.clone(),
-                        diff_rate.from_symbol.clone(),
-                    )
-                    .await
+        update_diff_rates(&mut diff_rate_his_map).await;
+    }
+}
+
+/// 按价格缓存计算一轮运行中的差价比率, diff_rate_his_map 为各差价上次写入历史的比率
+///
+/// 实时每秒调用一次, 回放时按虚拟时钟每秒调用
+#[allow(unused_assignments)]
+pub async fn update_diff_rates(diff_rate_his_map: &mut HashMap<i64, Decimal>) {
+    match sql::get_arb_diff_rate_list_by_diff_status(model::arb_diff_rate::DIFF_STATUS_RUN)
+        .await
+    {
+        Ok(result) => {
+            for diff_rate in result {
+                let mut from_price = Decimal::ZERO;
+                let mut to_price = Decimal::ZERO;
+                if let Ok(from_price_info) = service::get_binance_price(
+                    diff_rate.from_market.clone(),
+                    diff_rate.from_symbol.clone(),
+                )
+                .await
+                {
+                    if let Ok(price) =
+                        Decimal::from_str(from_price_info.ticker.current_close.unwrap().as_str())
                     {
-                        if let Ok(price) =
-                            Decimal::from_str(from_price_info.ticker.current_close.unwrap().as_str())
-                        {
-                            from_price = price;
-                        }
+                        from_price = price;
                     }
+                }
 
-                    if let Ok(to_price_info) = service::get_binance_price(
-                        diff_rate.to_market.clone(),
-                        diff_rate.to_symbol.clone(),
-                    )
-                    .await
+                if let Ok(to_price_info) = service::get_binance_price(
+                    diff_rate.to_market.clone(),
+                    diff_rate.to_symbol.clone(),
+                )
+                .await
+                {
+                    if let Ok(price) =
+                        Decimal::from_str(to_price_info.ticker.current_close.unwrap().as_str())
                     {
-                        if let Ok(price) =
-                            Decimal::from_str(to_price_info.ticker.current_close.unwrap().as_str())
-                        {
-                            to_price = price;
-                        }
+                        to_price = price;
                     }
+                }
 
-                    // 计算差价、比率
-                    let mut diff = Decimal::ZERO;
-                    let mut rate = Decimal::ZERO;
-                    let mut info_rate = Decimal::ZERO;
+                // 价格缺失或过期时跳过
+                if from_price.is_zero() || to_price.is_zero() {
+                    debug!(diff_rate_id = diff_rate.id; "skip diff rate without fresh prices");
+                    continue;
+                }
 
-                    if diff_rate.option_choose == "positive".to_string() {
-                        diff = to_price.sub(from_price);
-                        rate = diff.div(from_price);
-                        rate.rescale(4 as u32);
-                        info_rate = rate;
-                        info_rate.rescale(3 as u32);
-                    } else {
-                        diff = from_price.sub(to_price);
-                        rate = diff.div(to_price);
-                        rate.rescale(4 as u32);
-                        info_rate = rate;
-                        info_rate.rescale(3 as u32);
-                    }
+                // 计算差价、比率
+                let mut diff = Decimal::ZERO;
+                let mut rate = Decimal::ZERO;
+                let mut info_rate = Decimal::ZERO;
+                // 不截断的比率, 写入采样表
+                let mut sample_rate = Decimal::ZERO;
 
-                    debug!(
-                        "option_choose: {:?}, from_symbol: {:?}, to_symbol: {:?}, from: {:?}, to: {:?}, diff: {:?}, rate: {:?}, info_rate: {:?}",
-                        diff_rate.option_choose.clone(), diff_rate.from_symbol.clone(), diff_rate.to_symbol.clone(), from_price, to_price, diff, rate, info_rate
-                    );
+                if diff_rate.option_choose == "positive".to_string() {
+                    diff = to_price.sub(from_price);
+                    rate = diff.div(from_price);
+                    sample_rate = rate.round_dp(8);
+                    rate.rescale(4 as u32);
+                    info_rate = rate;
+                    info_rate.rescale(3 as u32);
+                } else {
+                    diff = from_price.sub(to_price);
+                    rate = diff.div(to_price);
+                    sample_rate = rate.round_dp(8);
+                    rate.rescale(4 as u32);
+                    info_rate = rate;
+                    info_rate.rescale(3 as u32);
+                }
 
-                    // 跳过差价相同记录
-                    if let Some(map_rate) = diff_rate_his_map.get(&diff_rate.id) {
-                        if map_rate.ne(&info_rate) {
-                            if let Err(e) = sql::insert_arb_diff_rate_his(model::ArbDiffRateHis {
-                                id: 0,
-                                diff_rate_id: diff_rate.id,
-                                diff_price: diff,
-                                diff_rate: info_rate,
-                                created: Some(Local::now().timestamp()),
-                                updated: Some(Local::now().timestamp()),
-                                bak: None,
-                            })
-                            .await
-                            {
-                                error!("{:?}", e);
-                                // continue;
-                            }
-                            diff_rate_his_map.insert(diff_rate.id, info_rate);
-                        }
-                    } else {
+                // 交割合约按距交割时间年化
+                let delivery_time = basis::pair_delivery_time(
+                    &diff_rate.from_market,
+                    &diff_rate.from_symbol,
+                    &diff_rate.to_market,
+                    &diff_rate.to_symbol,
+                )
+                .await
+                .unwrap_or_else(|e| {
+                    warn!(diff_rate_id = diff_rate.id, err:? = e; "get delivery time err");
+                    None
+                });
+                let annualized_rate = delivery_time.and_then(|t| {
+                    basis::annualize(
+                        rate,
+                        clock::now().timestamp_millis(),
+                        t,
+                        C.basis.min_expiry_secs * 1000,
+                    )
+                });
+
+                debug!(
+                    "option_choose: {:?}, from_symbol: {:?}, to_symbol: {:?}, from: {:?}, to: {:?}, diff: {:?}, rate: {:?}, info_rate: {:?}",
+                    diff_rate.option_choose.clone(), diff_rate.from_symbol.clone(), diff_rate.to_symbol.clone(), from_price, to_price, diff, rate, info_rate
+                );
+
+                // 每轮都采样, 供 zscore 信号使用
+                let now = clock::now().timestamp();
+                if let Err(e) = sql::insert_arb_diff_rate_sample(
+                    model::ArbDiffRateSample {
+                        id: 0,
+                        diff_rate_id: diff_rate.id,
+                        diff_rate: sample_rate,
+                        created: now,
+                    },
+                    now - 2 * signal::MAX_LOOKBACK,
+                )
+                .await
+                {
+                    error!("{:?}", e);
+                }
+
+                let mut changed = false;
+                // 跳过差价相同记录
+                if let Some(map_rate) = diff_rate_his_map.get(&diff_rate.id) {
+                    if map_rate.ne(&info_rate) {
                         if let Err(e) = sql::insert_arb_diff_rate_his(model::ArbDiffRateHis {
                             id: 0,
                             diff_rate_id: diff_rate.id,
                             diff_price: diff,
                             diff_rate: info_rate,
-                            created: Some(Local::now().timestamp()),
-                            updated: Some(Local::now().timestamp()),
+                            annualized_rate,
+                            created: Some(clock::now().timestamp()),
+                            updated: Some(clock::now().timestamp()),
                             bak: None,
                         })
                         .await
@@ -103,50 +145,78 @@ pub async fn set_binance_diff_rate() {
                             // continue;
                         }
                         diff_rate_his_map.insert(diff_rate.id, info_rate);
+                        changed = true;
+                    }
+                } else {
+                    if let Err(e) = sql::insert_arb_diff_rate_his(model::ArbDiffRateHis {
+                        id: 0,
+                        diff_rate_id: diff_rate.id,
+                        diff_price: diff,
+                        diff_rate: info_rate,
+                        annualized_rate,
+                        created: Some(clock::now().timestamp()),
+                        updated: Some(clock::now().timestamp()),
+                        bak: None,
+                    })
+                    .await
+                    {
+                        error!("{:?}", e);
+                        // continue;
                     }
+                    diff_rate_his_map.insert(diff_rate.id, info_rate);
+                    changed = true;
+                }
 
-                    // 设置info表
-                    if let Ok(diff_rate_info) =
-                        sql::get_arb_diff_rate_info_by_diff_rate_id(diff_rate.id).await
+                // 设置info表
+                if let Ok(diff_rate_info) =
+                    sql::get_arb_diff_rate_info_by_diff_rate_id(diff_rate.id).await
+                {
+                    let _ = sql::update_arb_diff_rate_info_by_id(
+                        diff_rate_info.id,
+                        from_price,
+                        to_price,
+                        diff,
+                        rate,
+                        annualized_rate,
+                        delivery_time,
+                    )
+                    .await;
+                } else {
+                    if let Err(e) = sql::insert_arb_diff_rate_info(model::ArbDiffRateInfo {
+                        id: 0,
+                        diff_rate_id: diff_rate.id,
+                        platform: diff_rate.platform,
+                        coin: diff_rate.coin,
+                        option_choose: diff_rate.option_choose,
+                        from_market: diff_rate.from_market,
+                        from_symbol: diff_rate.from_symbol,
+                        from_price,
+                        to_market: diff_rate.to_market,
+                        to_symbol: diff_rate.to_symbol,
+                        to_price,
+                        diff_price: diff,
+                        diff_rate: rate,
+                        annualized_rate,
+                        delivery_time,
+                        created: Some(clock::now().timestamp()),
+                        updated: Some(clock::now().timestamp()),
+                        bak: None,
+                    })
+                    .await
                     {
-                        let _ = sql::update_arb_diff_rate_info_by_id(
-                            diff_rate_info.id,
-                            from_price,
-                            to_price,
-                            diff,
-                            rate,
-                        )
-                        .await;
-                    } else {
-                        if let Err(e) = sql::insert_arb_diff_rate_info(model::ArbDiffRateInfo {
-                            id: 0,
-                            diff_rate_id: diff_rate.id,
-                            platform: diff_rate.platform,
-                            coin: diff_rate.coin,
-                            option_choose: diff_rate.option_choose,
-                            from_market: diff_rate.from_market,
-                            from_symbol: diff_rate.from_symbol,
-                            from_price,
-                            to_market: diff_rate.to_market,
-                            to_symbol: diff_rate.to_symbol,
-                            to_price,
-                            diff_price: diff,
-                            diff_rate: rate,
-                            created: Some(Local::now().timestamp()),
-                            updated: Some(Local::now().timestamp()),
-                            bak: None,
-                        })
-                        .await
-                        {
-                            error!("{:?}", e);
-                            continue;
-                        }
+                        error!("{:?}", e);
+                        continue;
                     }
                 }
+
+                // 差价变化唤醒关联策略
+                if changed {
+                    scheduler::wake_diff_rate(diff_rate.id);
+                }
             }
-            Err(e) => {
-                error!("{:?}", e);
-            }
+        }
+        Err(e) => {
+            error!("{:?}", e);
         }
     }
 }
@@ -0,0 +1,487 @@
+use crate::binance::rest_model::KlineSummary;
+use crate::model;
+use anyhow::anyhow;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use ta::indicators::{
+    BollingerBands, ExponentialMovingAverage, KeltnerChannel, RelativeStrengthIndex,
+    SimpleMovingAverage, StandardDeviation,
+};
+use ta::{Close, High, Low, Next, Open, Volume};
+
+// 稳定币对冲信号: 按 arb_stable_coin.signal_config 计算指标, 由规则组合开平仓条件
+//
+// {
+//   "interval": "15m",
+//   "indicators": [{ "name": "boll", "kind": "bollinger", "period": 20, "multiplier": 2.0 }],
+//   "entry": ["close < 1", "close <= boll.lower"],
+//   "exit": ["close >= boll.upper"]
+// }
+//
+// 规则为 `左 运算符 右`, 运算符 < <= > >= , 同一组规则全部满足才触发
+// 操作数: 数字, K线 open/high/low/close, 策略 option_open/option_close, 指标输出 name.output
+// 指标输出: bollinger/keltner/ema_envelope 为 upper/middle/lower, rsi/zscore 为 value(可省略写作 name)
+// 价格类取值(K线及通道)按 price_truncate 截取, rsi/zscore 保留 VALUE_SCALE 位
+
+// rsi/zscore 等非价格指标保留位数
+const VALUE_SCALE: u32 = 4;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignalConfig {
+    #[serde(default = "default_interval")]
+    pub interval: String,
+    pub indicators: Vec<IndicatorConfig>,
+    pub entry: Vec<Rule>,
+    pub exit: Vec<Rule>,
+}
+
+fn default_interval() -> String {
+    "15m".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndicatorConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Kind {
+    // 均线 ± multiplier 倍标准差
+    Bollinger { period: usize, multiplier: f64 },
+    // EMA ± multiplier 倍 ATR
+    Keltner { period: usize, multiplier: f64 },
+    Rsi { period: usize },
+    // EMA * (1 ± multiplier)
+    EmaEnvelope { period: usize, multiplier: f64 },
+    // (close - 均线) / 标准差
+    Zscore { period: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Value(Decimal),
+    Var(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rule {
+    left: Operand,
+    op: Op,
+    right: Operand,
+}
+
+/// 指标计算结果, 按策略价格精度截取
+pub type Values = BTreeMap<String, Decimal>;
+
+/// 指标计算, 每根K线输入一次
+trait Indicator: Send {
+    fn next(&mut self, k: &KlineSummary) -> Vec<(&'static str, f64)>;
+}
+
+/// strategy 对应的预设, 11 为 boll 15m
+fn preset(strategy: &str) -> Option<&'static str> {
+    match strategy {
+        "11" => Some(
+            r#"{
+                "interval": "15m",
+                "indicators": [{ "name": "boll", "kind": "bollinger", "period": 20, "multiplier": 2.0 }],
+                "entry": ["close < 1", "close <= boll.lower"],
+                "exit": ["close >= boll.upper"]
+            }"#,
+        ),
+        _ => None,
+    }
+}
+
+/// 策略的信号配置, signal_config 为空时使用 strategy 预设, 都没有返回 None
+pub fn config(s: &model::ArbStableCoin) -> anyhow::Result<Option<SignalConfig>> {
+    let content = match s.signal_config.trim() {
+        "" => match preset(&s.strategy) {
+            Some(p) => p,
+            None => return Ok(None),
+        },
+        c => c,
+    };
+    let config: SignalConfig = serde_json::from_str(content)?;
+    config.validate()?;
+    Ok(Some(config))
+}
+
+impl SignalConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        crate::service::kline::interval_ms(&self.interval)?;
+        for (i, c) in self.indicators.iter().enumerate() {
+            if self.indicators[..i].iter().any(|p| p.name == c.name) {
+                return Err(anyhow!("duplicate indicator {}", c.name));
+            }
+            c.build()?;
+        }
+        if self.entry.is_empty() || self.exit.is_empty() {
+            return Err(anyhow!("entry and exit rules required"));
+        }
+        // 规则中的变量必须有定义
+        let mut names = vec![
+            "open".to_string(),
+            "high".to_string(),
+            "low".to_string(),
+            "close".to_string(),
+            "option_open".to_string(),
+            "option_close".to_string(),
+        ];
+        for c in self.indicators.iter() {
+            for output in c.kind.outputs() {
+                names.push(format!("{}.{}", c.name, output));
+            }
+            if c.kind.outputs() == ["value"] {
+                names.push(c.name.clone());
+            }
+        }
+        for rule in self.entry.iter().chain(self.exit.iter()) {
+            for operand in [&rule.left, &rule.right] {
+                if let Operand::Var(v) = operand {
+                    if !names.contains(v) {
+                        return Err(anyhow!("unknown variable {}", v));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 按K线计算指标, 返回最后一根K线的取值
+    pub fn evaluate(
+        &self,
+        s: &model::ArbStableCoin,
+        klines: &[KlineSummary],
+    ) -> anyhow::Result<Values> {
+        let last = klines.last().ok_or(anyhow!("no klines"))?;
+        let price_scale = s.price_truncate as u32;
+        let mut values = Values::new();
+        let mut put = |name: String, v: f64, scale: u32| -> anyhow::Result<()> {
+            let mut d = Decimal::from_f64(v).ok_or(anyhow!("decimal from f64 {} {}", name, v))?;
+            d.rescale(scale);
+            values.insert(name, d);
+            Ok(())
+        };
+        for c in self.indicators.iter() {
+            let mut indicator = c.build()?;
+            let mut outputs = vec![];
+            for k in klines.iter() {
+                outputs = indicator.next(k);
+            }
+            let scale = if c.kind.is_price() {
+                price_scale
+            } else {
+                VALUE_SCALE
+            };
+            for (output, v) in outputs {
+                if output == "value" {
+                    put(c.name.clone(), v, scale)?;
+                }
+                put(format!("{}.{}", c.name, output), v, scale)?;
+            }
+        }
+        put("open".to_string(), last.open, price_scale)?;
+        put("high".to_string(), last.high, price_scale)?;
+        put("low".to_string(), last.low, price_scale)?;
+        put("close".to_string(), last.close, price_scale)?;
+        values.insert("option_open".to_string(), s.option_open);
+        values.insert("option_close".to_string(), s.option_close);
+        Ok(values)
+    }
+}
+
+/// 规则全部满足
+pub fn matches(rules: &[Rule], values: &Values) -> anyhow::Result<bool> {
+    for rule in rules {
+        if !rule.eval(values)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+impl IndicatorConfig {
+    fn build(&self) -> anyhow::Result<Box<dyn Indicator>> {
+        Ok(match self.kind {
+            Kind::Bollinger { period, multiplier } => {
+                Box::new(BollingerBands::new(period, multiplier)?)
+            }
+            Kind::Keltner { period, multiplier } => {
+                Box::new(KeltnerChannel::new(period, multiplier)?)
+            }
+            Kind::Rsi { period } => Box::new(RelativeStrengthIndex::new(period)?),
+            Kind::EmaEnvelope { period, multiplier } => Box::new(EmaEnvelope {
+                ema: ExponentialMovingAverage::new(period)?,
+                multiplier,
+            }),
+            Kind::Zscore { period } => Box::new(ZScore {
+                sma: SimpleMovingAverage::new(period)?,
+                sd: StandardDeviation::new(period)?,
+            }),
+        })
+    }
+}
+
+impl Kind {
+    fn outputs(&self) -> &'static [&'static str] {
+        match self {
+            Kind::Bollinger { .. } | Kind::Keltner { .. } | Kind::EmaEnvelope { .. } => {
+                &["upper", "middle", "lower"]
+            }
+            Kind::Rsi { .. } | Kind::Zscore { .. } => &["value"],
+        }
+    }
+
+    // 输出是否为价格
+    fn is_price(&self) -> bool {
+        !matches!(self, Kind::Rsi { .. } | Kind::Zscore { .. })
+    }
+}
+
+impl Indicator for BollingerBands {
+    fn next(&mut self, k: &KlineSummary) -> Vec<(&'static str, f64)> {
+        let out = Next::next(self, k.close);
+        vec![
+            ("upper", out.upper),
+            ("middle", out.average),
+            ("lower", out.lower),
+        ]
+    }
+}
+
+impl Indicator for KeltnerChannel {
+    fn next(&mut self, k: &KlineSummary) -> Vec<(&'static str, f64)> {
+        let out = Next::next(self, k);
+        vec![
+            ("upper", out.upper),
+            ("middle", out.average),
+            ("lower", out.lower),
+        ]
+    }
+}
+
+impl Indicator for RelativeStrengthIndex {
+    fn next(&mut self, k: &KlineSummary) -> Vec<(&'static str, f64)> {
+        vec![("value", Next::next(self, k.close))]
+    }
+}
+
+struct EmaEnvelope {
+    ema: ExponentialMovingAverage,
+    multiplier: f64,
+}
+
+impl Indicator for EmaEnvelope {
+    fn next(&mut self, k: &KlineSummary) -> Vec<(&'static str, f64)> {
+        let ema = self.ema.next(k.close);
+        vec![
+            ("upper", ema * (1.0 + self.multiplier)),
+            ("middle", ema),
+            ("lower", ema * (1.0 - self.multiplier)),
+        ]
+    }
+}
+
+struct ZScore {
+    sma: SimpleMovingAverage,
+    sd: StandardDeviation,
+}
+
+impl Indicator for ZScore {
+    fn next(&mut self, k: &KlineSummary) -> Vec<(&'static str, f64)> {
+        let mean = self.sma.next(k.close);
+        let sd = self.sd.next(k.close);
+        let z = if sd == 0.0 {
+            0.0
+        } else {
+            (k.close - mean) / sd
+        };
+        vec![("value", z)]
+    }
+}
+
+impl Open for KlineSummary {
+    fn open(&self) -> f64 {
+        self.open
+    }
+}
+
+impl High for KlineSummary {
+    fn high(&self) -> f64 {
+        self.high
+    }
+}
+
+impl Low for KlineSummary {
+    fn low(&self) -> f64 {
+        self.low
+    }
+}
+
+impl Close for KlineSummary {
+    fn close(&self) -> f64 {
+        self.close
+    }
+}
+
+impl Volume for KlineSummary {
+    fn volume(&self) -> f64 {
+        self.volume
+    }
+}
+
+impl Rule {
+    fn eval(&self, values: &Values) -> anyhow::Result<bool> {
+        let get = |o: &Operand| match o {
+            Operand::Value(v) => Ok(*v),
+            Operand::Var(name) => values
+                .get(name)
+                .copied()
+                .ok_or(anyhow!("unknown variable {}", name)),
+        };
+        let (l, r) = (get(&self.left)?, get(&self.right)?);
+        Ok(match self.op {
+            Op::Lt => l < r,
+            Op::Le => l <= r,
+            Op::Gt => l > r,
+            Op::Ge => l >= r,
+        })
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let [left, op, right] = parts[..] else {
+            return Err(anyhow!("rule must be `left op right`: {}", s));
+        };
+        let op = match op {
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            _ => return Err(anyhow!("unknown operator {} in rule: {}", op, s)),
+        };
+        let operand = |v: &str| match Decimal::from_str(v) {
+            Ok(d) => Operand::Value(d),
+            Err(_) => Operand::Var(v.to_string()),
+        };
+        Ok(Rule {
+            left: operand(left),
+            op,
+            right: operand(right),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(close: f64) -> KlineSummary {
+        KlineSummary {
+            open: close,
+            high: close + 0.001,
+            low: close - 0.001,
+            close,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_boll_preset() {
+        let s = model::ArbStableCoin {
+            strategy: "11".to_string(),
+            price_truncate: 4,
+            ..Default::default()
+        };
+        let c = config(&s).unwrap().unwrap();
+        assert_eq!(c.interval, "15m");
+
+        // 价格稳定后跌破下轨
+        let mut klines: Vec<KlineSummary> = (0..30)
+            .map(|i| kline(if i % 2 == 0 { 0.9990 } else { 1.0010 }))
+            .collect();
+        klines.push(kline(0.9950));
+        let values = c.evaluate(&s, &klines).unwrap();
+        assert!(values["boll.lower"] > Decimal::new(9950, 4));
+        assert!(matches(&c.entry, &values).unwrap());
+        assert!(!matches(&c.exit, &values).unwrap());
+
+        let s = model::ArbStableCoin {
+            strategy: "21".to_string(),
+            ..Default::default()
+        };
+        assert!(config(&s).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_custom_config() {
+        let s = model::ArbStableCoin {
+            strategy: "11".to_string(),
+            price_truncate: 4,
+            option_open: Decimal::new(-2, 0),
+            signal_config: r#"{
+                "interval": "1h",
+                "indicators": [
+                    { "name": "z", "kind": "zscore", "period": 20 },
+                    { "name": "rsi", "kind": "rsi", "period": 14 },
+                    { "name": "kc", "kind": "keltner", "period": 20, "multiplier": 1.5 },
+                    { "name": "env", "kind": "ema_envelope", "period": 20, "multiplier": 0.001 }
+                ],
+                "entry": ["z <= option_open", "rsi < 30"],
+                "exit": ["close >= env.upper", "close >= kc.middle"]
+            }"#
+            .to_string(),
+            ..Default::default()
+        };
+        let c = config(&s).unwrap().unwrap();
+        assert_eq!(c.interval, "1h");
+        let mut klines: Vec<KlineSummary> = (0..40).map(|_| kline(1.0)).collect();
+        klines.extend((1..=5).map(|i| kline(1.0 - i as f64 * 0.001)));
+        let values = c.evaluate(&s, &klines).unwrap();
+        assert!(values.contains_key("kc.upper"));
+        assert!(matches(&c.entry, &values).unwrap());
+        // 价格按 price_truncate, rsi/zscore 按 VALUE_SCALE
+        assert_eq!(values["close"].scale(), 4);
+        assert_eq!(values["env.upper"].scale(), 4);
+        assert_eq!(values["rsi"].scale(), VALUE_SCALE);
+
+        // 价格精度为 0 时 zscore 不被截成整数
+        let s = model::ArbStableCoin {
+            price_truncate: 0,
+            ..s
+        };
+        let values = c.evaluate(&s, &klines).unwrap();
+        assert_eq!(values["close"], Decimal::ONE);
+        assert_eq!(values["z"], values["z.value"]);
+        assert!(values["z"].fract() != Decimal::ZERO);
+
+        let invalid = model::ArbStableCoin {
+            signal_config:
+                r#"{"indicators": [], "entry": ["close <= boll.lower"], "exit": ["close > 1"]}"#
+                    .to_string(),
+            ..Default::default()
+        };
+        assert!(config(&invalid).is_err());
+        assert!(Rule::try_from("close =< 1".to_string()).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::binance::config::Config;
+use crate::binance::rest_model::{KlineSummaries, KlineSummary};
+use crate::binance::websockets::{kline_stream, WebSockets};
+use crate::binance::ws_model::{CombinedStreamEvent, KlineEvent};
+use crate::binance::account;
+use crate::conf::C;
+use crate::db;
+use crate::helper::shutdown;
+use anyhow::anyhow;
+use chrono::Local;
+use dashmap::{DashMap, DashSet};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::{broadcast, Notify};
+use url::Url;
+
+// K线服务: 订阅时通过 REST 补齐历史, 之后由 kline websocket 更新
+// 最近 kline.history 条保存在内存, 全部写入 rocksdb, 按 rocksdb.retention_days 清理
+// 每个市场一个组合流连接, 订阅变化或断线时重连并补齐缺失的K线
+
+const FETCH_LIMIT: u16 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KlineKey {
+    // spot/futures/delivery
+    pub market: String,
+    pub symbol: String,
+    pub interval: String,
+}
+
+/// 已收盘的K线
+#[derive(Debug, Clone)]
+pub struct ClosedKline {
+    pub key: KlineKey,
+    pub kline: KlineSummary,
+}
+
+static CACHE: Lazy<DashMap<KlineKey, VecDeque<KlineSummary>>> = Lazy::new(DashMap::new);
+static WATCHING: Lazy<DashSet<KlineKey>> = Lazy::new(DashSet::new);
+// market -> 通知连接按新的订阅重连
+static STREAMS: Lazy<DashMap<String, Arc<Notify>>> = Lazy::new(DashMap::new);
+static CLOSED: Lazy<broadcast::Sender<ClosedKline>> = Lazy::new(|| broadcast::channel(1024).0);
+
+impl KlineKey {
+    pub fn new(market: &str, symbol: &str, interval: &str) -> Self {
+        KlineKey {
+            market: market.to_string(),
+            symbol: symbol.to_string(),
+            interval: interval.to_string(),
+        }
+    }
+}
+
+/// 订阅 [kline] subscriptions 配置的K线
+pub async fn start() {
+    for s in C.kline.subscriptions.iter() {
+        let key = KlineKey::new(&s.market, &s.symbol, &s.interval);
+        if let Err(e) = watch(&key).await {
+            error!(market = key.market.as_str(), symbol = key.symbol.as_str(), interval = key.interval.as_str(), err:? = e; "kline watch err");
+        }
+    }
+}
+
+/// 订阅收盘K线事件
+pub fn subscribe() -> broadcast::Receiver<ClosedKline> {
+    CLOSED.subscribe()
+}
+
+/// 开始维护K线, 已订阅则忽略
+pub async fn watch(key: &KlineKey) -> anyhow::Result<()> {
+    if WATCHING.contains(key) {
+        return Ok(());
+    }
+    interval_ms(&key.interval)?;
+    let klines = sync(key, C.kline.history).await?;
+    CACHE.insert(key.clone(), klines.into());
+    if !WATCHING.insert(key.clone()) {
+        return Ok(());
+    }
+    info!(market = key.market.as_str(), symbol = key.symbol.as_str(), interval = key.interval.as_str(); "kline watched");
+
+    match STREAMS.entry(key.market.clone()) {
+        dashmap::mapref::entry::Entry::Occupied(e) => e.get().notify_one(),
+        dashmap::mapref::entry::Entry::Vacant(e) => {
+            let notify = Arc::new(Notify::new());
+            e.insert(notify.clone());
+            shutdown::spawn(stream(key.market.clone(), notify));
+        }
+    }
+    Ok(())
+}
+
+/// 最近 n 条K线, 按 open_time 升序, 最后一条可能未收盘; 内存不足 n 条时读取 rocksdb
+pub fn latest(key: &KlineKey, n: usize) -> anyhow::Result<Vec<KlineSummary>> {
+    if let Some(klines) = CACHE.get(key) {
+        if klines.len() >= n {
+            return Ok(klines.iter().skip(klines.len() - n).cloned().collect());
+        }
+    }
+    db::kline::last(&key.market, &key.symbol, &key.interval, n)
+}
+
+async fn stream(market: String, notify: Arc<Notify>) {
+    let token = shutdown::token();
+    loop {
+        let keys: Vec<KlineKey> = WATCHING
+            .iter()
+            .filter(|k| k.market == market)
+            .map(|k| k.clone())
+            .collect();
+        let keep_running = AtomicBool::new(true);
+        let mut web_socket: WebSockets<'_, CombinedStreamEvent<KlineEvent>> =
+            WebSockets::new(|event: CombinedStreamEvent<KlineEvent>| {
+                update(&market, &event.data);
+                Ok(())
+            });
+
+        let connected = match stream_url(&market, &keys) {
+            Ok(url) => web_socket.connect(url).await.map_err(|e| anyhow!(e)),
+            Err(e) => Err(e),
+        };
+        match connected {
+            Ok(()) => {
+                // 断线期间的K线
+                for key in keys.iter() {
+                    match sync(key, C.kline.history).await {
+                        Ok(klines) => {
+                            CACHE.insert(key.clone(), klines.into());
+                        }
+                        Err(e) => {
+                            error!(market = market.as_str(), symbol = key.symbol.as_str(), err:? = e; "kline resync err")
+                        }
+                    }
+                }
+                select! {
+                    result = web_socket.event_loop(&keep_running) => {
+                        if let Err(e) = result {
+                            error!(market = market.as_str(), err:? = e; "kline websocket err");
+                        }
+                    }
+                    _ = notify.notified() => {}
+                    _ = token.cancelled() => {}
+                }
+                let _ = web_socket.disconnect().await;
+            }
+            Err(e) => error!(market = market.as_str(), err:? = e; "kline websocket connect err"),
+        }
+        if token.is_cancelled() {
+            break;
+        }
+        warn!(market = market.as_str(); "kline websocket reconnect");
+        select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+        }
+    }
+}
+
+fn stream_url(market: &str, keys: &[KlineKey]) -> anyhow::Result<Url> {
+    let conf = Config::with_endpoints();
+    let endpoint = match market {
+        "spot" => conf.ws_endpoint,
+        "futures" => conf.futures_ws_endpoint,
+        "delivery" => conf.delivery_ws_endpoint,
+        _ => return Err(anyhow!("unknown market {}", market)),
+    };
+    let streams: Vec<String> = keys
+        .iter()
+        .map(|k| kline_stream(&k.symbol.to_lowercase(), &k.interval))
+        .collect();
+    Ok(Url::parse(&format!(
+        "{}/stream?streams={}",
+        endpoint,
+        streams.join("/")
+    ))?)
+}
+
+fn update(market: &str, event: &KlineEvent) {
+    let key = KlineKey::new(market, &event.symbol, &event.kline.interval);
+    let kline = KlineSummary::from(&event.kline);
+    if let Err(e) = db::kline::append(&key.market, &key.symbol, &key.interval, &[kline.clone()]) {
+        error!(market = market, symbol = key.symbol.as_str(), err:? = e; "kline persist err");
+    }
+    if let Some(mut klines) = CACHE.get_mut(&key) {
+        push(&mut klines, kline.clone(), C.kline.history);
+    }
+    if event.kline.is_final {
+        let _ = CLOSED.send(ClosedKline { key, kline });
+    }
+}
+
+// 同一 open_time 覆盖未收盘的K线, 超出 history 丢弃最早的
+fn push(klines: &mut VecDeque<KlineSummary>, kline: KlineSummary, history: usize) {
+    match klines.back_mut() {
+        Some(last) if last.open_time == kline.open_time => *last = kline,
+        Some(last) if last.open_time > kline.open_time => {}
+        _ => klines.push_back(kline),
+    }
+    while klines.len() > history {
+        klines.pop_front();
+    }
+}
+
+/// 通过 REST 同步到 rocksdb 并返回最近 limit 条K线
+/// 首次拉取最近 FETCH_LIMIT 条, 之后从最后一条(可能未收盘)开始增量拉取, 停机期间及缓存中的缺口分页补齐
+pub async fn sync(key: &KlineKey, limit: usize) -> anyhow::Result<Vec<KlineSummary>> {
+    let (market, symbol, interval) = (&key.market, &key.symbol, &key.interval);
+    let step = interval_ms(interval)?;
+    let cached = db::kline::last(market, symbol, interval, limit)?;
+    match cached.last() {
+        None => {
+            let klines = fetch(key, None, None).await?;
+            db::kline::append(market, symbol, interval, &klines)?;
+        }
+        Some(last) => {
+            backfill(key, last.open_time, None).await?;
+            for (from, to) in gaps(&cached, step) {
+                info!(market = market.as_str(), symbol = symbol.as_str(), interval = interval.as_str(), from = from, to = to; "kline gap backfill");
+                backfill(key, from, Some(to - 1)).await?;
+            }
+        }
+    }
+
+    if C.rocksdb.retention_days > 0 {
+        let before = Local::now().timestamp_millis() - C.rocksdb.retention_days as i64 * 86_400_000;
+        db::kline::trim(market, symbol, interval, before)?;
+    }
+    db::kline::last(market, symbol, interval, limit)
+}
+
+// 从 start 开始分页拉取直到 end 或最新
+async fn backfill(key: &KlineKey, mut start: i64, end: Option<i64>) -> anyhow::Result<()> {
+    loop {
+        let klines = fetch(key, Some(start), end).await?;
+        db::kline::append(&key.market, &key.symbol, &key.interval, &klines)?;
+        match klines.last() {
+            Some(k) if klines.len() == FETCH_LIMIT as usize && k.open_time > start => {
+                start = k.open_time + 1
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+async fn fetch(
+    key: &KlineKey,
+    start: Option<i64>,
+    end: Option<i64>,
+) -> anyhow::Result<Vec<KlineSummary>> {
+    match account::public_api()
+        .get_klines(
+            &key.market,
+            key.symbol.as_str(),
+            key.interval.as_str(),
+            FETCH_LIMIT,
+            start.map(|s| s as u64),
+            end.map(|e| e as u64),
+        )
+        .await
+    {
+        Ok(KlineSummaries::AllKlineSummaries(klines)) => Ok(klines),
+        Err(e) => Err(anyhow!(
+            "get {} {} {} klines err: {}",
+            key.market,
+            key.symbol,
+            key.interval,
+            e
+        )),
+    }
+}
+
+/// K线周期毫秒数, 1M 长度不固定不支持
+pub fn interval_ms(interval: &str) -> anyhow::Result<i64> {
+    let err = || anyhow!("unsupported kline interval {}", interval);
+    let (n, unit) = interval.split_at(
+        interval
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(err)?,
+    );
+    let n: i64 = n.parse().map_err(|_| err())?;
+    let unit = match unit {
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        "w" => 604_800_000,
+        _ => return Err(err()),
+    };
+    Ok(n * unit)
+}
+
+/// 相邻K线间缺失的区间 [from, to), to 为缺口后第一条K线的 open_time
+pub fn gaps(klines: &[KlineSummary], step: i64) -> Vec<(i64, i64)> {
+    klines
+        .windows(2)
+        .filter(|w| w[1].open_time - w[0].open_time > step)
+        .map(|w| (w[0].open_time + step, w[1].open_time))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(open_time: i64) -> KlineSummary {
+        KlineSummary {
+            open_time,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            volume: 0.0,
+            close_time: open_time + 899_999,
+            quote_asset_volume: 0.0,
+            number_of_trades: 0,
+            taker_buy_base_asset_volume: 0.0,
+            taker_buy_quote_asset_volume: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_gaps() {
+        let step = interval_ms("15m").unwrap();
+        assert_eq!(step, 900_000);
+        assert!(interval_ms("1M").is_err());
+        assert!(interval_ms("").is_err());
+
+        let klines: Vec<KlineSummary> =
+            [0, 1, 2, 5, 6, 8].iter().map(|i| kline(i * step)).collect();
+        assert_eq!(
+            gaps(&klines, step),
+            vec![(3 * step, 5 * step), (7 * step, 8 * step)]
+        );
+        assert!(gaps(&klines[..3], step).is_empty());
+    }
+
+    #[test]
+    fn test_push() {
+        let mut klines: VecDeque<KlineSummary> = VecDeque::new();
+        push(&mut klines, kline(0), 2);
+        let mut k = kline(0);
+        k.close = 2.0;
+        push(&mut klines, k, 2);
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].close, 2.0);
+        push(&mut klines, kline(1), 2);
+        push(&mut klines, kline(2), 2);
+        push(&mut klines, kline(1), 2);
+        assert_eq!(
+            klines.iter().map(|k| k.open_time).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_kline_event() {
+        let msg = r#"{"stream":"btcusdt@kline_15m","data":{"e":"kline","E":1672515782136,"s":"BTCUSDT","k":{"t":1672515780000,"T":1672515839999,"s":"BTCUSDT","i":"15m","f":100,"L":200,"o":"0.0010","c":"0.0020","h":"0.0025","l":"0.0015","v":"1000","n":100,"x":false,"q":"1.0000","V":"500","Q":"0.500","B":"123456"}}}"#;
+        let event: CombinedStreamEvent<KlineEvent> = serde_json::from_str(msg).unwrap();
+        let k = KlineSummary::from(&event.data.kline);
+        assert_eq!(event.data.symbol, "BTCUSDT");
+        assert_eq!(k.open_time, 1672515780000);
+        assert_eq!(k.close, 0.002);
+        assert!(!event.data.kline.is_final);
+    }
+}
@@ -1,30 +1,43 @@
+pub mod basis;
 pub mod binance_strategy;
-mod common;
 pub mod diff_rate;
+pub mod indicator;
+pub mod kline;
 pub mod price;
 pub mod stable_coin_hedging;
+pub mod template;
 mod diff_price;
+pub mod operator;
+pub mod pnl;
+pub mod recorder;
+pub mod replay;
+pub mod report;
+pub mod scheduler;
+pub mod signal;
+pub mod validate;
 
 pub use diff_price::set_price_data;
 pub use diff_price::get_diff_signal;
-pub use binance_strategy::event_start;
-pub use binance_strategy::inspect_strategy;
 pub use binance_strategy::range_new_strategy;
 pub use diff_rate::set_binance_diff_rate;
 pub use price::get_binance_price;
-pub use price::set_binance_price;
-pub use stable_coin_hedging::event_stable_coin_start;
-pub use stable_coin_hedging::inspect_stable_coin;
+pub use price::get_price;
+pub use price::set_price_cache;
+pub use scheduler::schedule_strategy;
+pub use validate::validate_stable_coin;
+pub use validate::validate_strategy;
 
+use crate::binance::config::Config;
 use crate::binance::websockets::*;
 use crate::binance::ws_model::*;
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio_tungstenite::tungstenite::Message;
 use url::Url;
 use crate::constants::{BYBIT_MESSAGE, KUCOIN_MESSAGE};
+use crate::helper::{metrics, shutdown};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PriceStream {
@@ -34,7 +47,51 @@ pub struct PriceStream {
     pub local_time: i64,
 }
 
+/// 行情事件转换为 PriceStream, 实时行情及回放共用
+pub fn price_stream(platform: &Platform, market: &Market, event: WebSocketEvent, local_time: i64) -> PriceStream {
+    let mut tickers = Vec::new();
+    match platform {
+        Platform::Binance => {
+            if let WebSocketEvent::MiniTicker(tick_events) = event {
+                tickers.extend(*tick_events);
+            }
+        }
+        Platform::Bybit => {
+            if let WebSocketEvent::TickerDelta(bybit_event) = event {
+                tickers.push(bybit_event.into());
+            }
+        }
+        Platform::Kucoin => {
+            if let WebSocketEvent::KucoinTicker(ckucoin_event) = event {
+                tickers.push(ckucoin_event.into());
+            }
+        }
+    }
 
+    PriceStream {
+        tickers,
+        platform: platform.clone(),
+        market: market.clone(),
+        local_time,
+    }
+}
+
+/// 行情分发: 同时发送到 signal_tx 及价格缓存 book_tx
+pub async fn fan_out_price(
+    mut price_rx: UnboundedReceiver<PriceStream>,
+    signal_tx: UnboundedSender<PriceStream>,
+    book_tx: UnboundedSender<PriceStream>,
+) {
+    while let Some(stream) = price_rx.recv().await {
+        metrics::PRICE_BACKLOG.dec();
+        if book_tx.send(stream.clone()).is_ok() {
+            metrics::PRICE_BACKLOG.inc();
+        }
+        if signal_tx.send(stream).is_ok() {
+            metrics::PRICE_BACKLOG.inc();
+        }
+    }
+}
 
 pub async fn handle_websocket(
     url: &str,
@@ -44,67 +101,76 @@ pub async fn handle_websocket(
     price_tx: UnboundedSender<PriceStream>,
     keep_running: &AtomicBool,
 ) {
+    let token = shutdown::token();
     loop {
         let mut web_socket: WebSockets<'_, WebSocketEvent> = WebSockets::new(|event: WebSocketEvent| {
-            let mut tickers = Vec::new();
-            match platform {
-                Platform::Binance => {
-                    if let WebSocketEvent::MiniTicker(tick_events) = event {
-                        tickers.extend(*tick_events);
-                    }
-                }
-                Platform::Bybit => {
-                    if let WebSocketEvent::TickerDelta(bybit_event) = event {
-                        tickers.push(bybit_event.into());
-                    }
-                }
-                Platform::Kucoin => {
-                    if let WebSocketEvent::KucoinTicker(ckucoin_event) = event {
-                        tickers.push(ckucoin_event.into());
-                    }
-                }
+            let price_stream = price_stream(&platform, &market, event, chrono::Local::now().timestamp_millis());
+
+            let labels = [platform.as_str(), market.as_str()];
+            metrics::WS_MESSAGES.with_label_values(&labels).inc();
+            if let Some(event_time) = price_stream.tickers.iter().map(|t| t.event_time).max() {
+                let latency = price_stream.local_time - event_time as i64;
+                metrics::WS_LATENCY
+                    .with_label_values(&labels)
+                    .observe(latency as f64 / 1000.0);
             }
 
-            let price_stream = PriceStream {
-                tickers,
-                platform: platform.clone(),
-                market: market.clone(),
-                local_time: chrono::Local::now().timestamp_millis(),
-            };
-
             if price_tx.send(price_stream).is_err() {
                 keep_running.store(false, Ordering::Relaxed);
+            } else {
+                metrics::PRICE_BACKLOG.inc();
             }
 
             Ok(())
         });
+        if let Some(raw) = recorder::recorder(&platform, &market) {
+            web_socket.set_raw_handler(raw);
+        }
 
         web_socket.connect(Url::parse(url).unwrap()).await.unwrap();
         if let Some(msg) = message {
             web_socket.send_message(Message::Text(msg.to_string())).await.unwrap();
         }
-        if let Err(e) = web_socket.event_loop(&keep_running).await {
-            error!("Error: {e}");
-            continue
+        tokio::select! {
+            result = web_socket.event_loop(keep_running) => {
+                if let Err(e) = result {
+                    error!("Error: {e}");
+                    continue
+                }
+            }
+            // 退出时关闭连接
+            _ = token.cancelled() => {}
+        }
+        if let Err(e) = web_socket.disconnect().await {
+            error!("websocket disconnect err: {e}");
+        }
+        info!(platform = platform.as_str(), market = market.as_str(); "websocket disconnected");
+        if token.is_cancelled() || !keep_running.load(Ordering::Relaxed) {
+            break;
         }
-        web_socket.disconnect().await.unwrap();
-        info!("websocket disconnected");
     }
 
 }
 
 pub async fn binance_spot_all_ticker(price_tx: UnboundedSender<PriceStream>) {
     let keep_running = AtomicBool::new(true);
-    let url = "wss://stream.binance.com:9443/ws/!miniTicker@arr";
+    let url = format!("{}/ws/!miniTicker@arr", Config::with_endpoints().ws_endpoint);
 
-    handle_websocket(url, None, Platform::Binance, Market::Spot, price_tx, &keep_running).await;
+    handle_websocket(&url, None, Platform::Binance, Market::Spot, price_tx, &keep_running).await;
 }
 
 pub async fn binance_futures_all_ticker(price_tx: UnboundedSender<PriceStream>) {
     let keep_running = AtomicBool::new(true);
-    let url = "wss://fstream.binance.com/ws/!miniTicker@arr";
+    let url = format!("{}/ws/!miniTicker@arr", Config::with_endpoints().futures_ws_endpoint);
+
+    handle_websocket(&url, None, Platform::Binance, Market::Futures, price_tx, &keep_running).await;
+}
+
+pub async fn binance_delivery_all_ticker(price_tx: UnboundedSender<PriceStream>) {
+    let keep_running = AtomicBool::new(true);
+    let url = format!("{}/ws/!miniTicker@arr", Config::with_endpoints().delivery_ws_endpoint);
 
-    handle_websocket(url, None, Platform::Binance, Market::Futures, price_tx, &keep_running).await;
+    handle_websocket(&url, None, Platform::Binance, Market::Delivery, price_tx, &keep_running).await;
 }
 
 pub async fn bybit_spot_all_ticker(price_tx: UnboundedSender<PriceStream>) {
@@ -145,6 +211,7 @@ pub async fn binance_all_ticker(price_tx: UnboundedSender<PriceStream>) {
     tokio::join!(
         // binance_spot_all_ticker(price_tx.clone()),
         binance_futures_all_ticker(price_tx.clone()),
+        binance_delivery_all_ticker(price_tx.clone()),
     );
 }
 
@@ -165,43 +232,3 @@ pub async fn kucoin_all_ticker(price_tx: UnboundedSender<PriceStream>) {
         kucoin_futures_all_ticker(price_tx.clone())
     );
 }
-//
-// #[allow(dead_code)]
-// #[allow(irrefutable_let_patterns)]
-// pub async fn delivery_all_ticker(price_tx: UnboundedSender<PriceStream>) {
-//     let keep_running = AtomicBool::new(true);
-//     let all_ticker = all_mini_ticker_stream();
-//
-//     let mut web_socket: WebSockets<'_, Vec<WebsocketEvent>> =
-//         WebSockets::new(|events: Vec<WebsocketEvent>| {
-//             let mut tickers = Vec::new();
-//             for tick_events in events {
-//                 if let WebsocketEvent::DayMiniTicker(tick_event) = tick_events {
-//                     tickers.push(*tick_event)
-//                 }
-//             }
-//
-//             let price_stream = PriceStream {
-//                 tickers: tickers,
-//                 market: "delivery".to_string(),
-//                 local_time: chrono::Local::now().timestamp_millis(),
-//             };
-//             match price_tx.send(price_stream) {
-//                 Ok(_) => {}
-//                 Err(_e) => {
-//                     keep_running.store(false, Ordering::Relaxed);
-//                 }
-//             }
-//
-//             // Break the event loop
-//             // keep_running.store(false, Ordering::Relaxed);
-//             Ok(())
-//         });
-//
-//     web_socket.connect_delivery(all_ticker).await.unwrap(); // check error
-//     if let Err(e) = web_socket.event_loop(&keep_running).await {
-//         error!("Error: {e}");
-//     }
-//     web_socket.disconnect().await.unwrap();
-//     info!("delivery websocket disconnected");
-// }
@@ -0,0 +1,847 @@
+use crate::binance::api::{
+    FuturesGetOrderRequest, FuturesOrderRequest, OrderRequest, OrderStatusRequest,
+};
+use crate::binance::rest_model::{OrderSide, OrderType, UniversalTransferType};
+use crate::binance::{account, MyApi};
+use crate::conf::C;
+use crate::helper::metrics;
+use crate::service::scheduler;
+use crate::{model, sql};
+use anyhow::anyhow;
+use chrono::Local;
+use log::warn;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use std::ops::{Mul, Sub};
+use std::time::Duration;
+
+// 人工干预: 查看执行明细, 强制标记, 撤单, 紧急平仓
+
+#[derive(Debug, Clone)]
+pub struct Leg {
+    pub ex: model::ArbStrategyEx,
+    // 交易所订单状态, 划转或未下单为 None
+    pub order_status: Option<String>,
+    pub order_executed: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UnwindStep {
+    Order {
+        ex_id: Option<i64>,
+        market: String,
+        symbol: String,
+        side: OrderSide,
+        amount: Decimal,
+    },
+    Transfer {
+        ex_id: Option<i64>,
+        coin: String,
+        transfer_type: UniversalTransferType,
+        amount: Decimal,
+    },
+}
+
+fn audit(bak: &Option<String>, action: &str, reason: &str) -> String {
+    let line = format!(
+        "[{}] {}: {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        action,
+        reason
+    );
+    match bak {
+        Some(b) if !b.is_empty() => format!("{}\n{}", b, line),
+        _ => line,
+    }
+}
+
+// 紧急平仓明细 option_type 前缀
+const UNWIND_PREFIX: &str = "unwind_";
+const UNWIND_TRANSFER: &str = "unwind_transfer_delivery_to_spot";
+
+fn leg_side(ex: &model::ArbStrategyEx) -> Option<OrderSide> {
+    side_of(&ex.option_type)
+}
+
+pub(crate) fn side_of(option_type: &str) -> Option<OrderSide> {
+    if option_type.ends_with("_buy") {
+        Some(OrderSide::Buy)
+    } else if option_type.ends_with("_sell") {
+        Some(OrderSide::Sell)
+    } else {
+        None
+    }
+}
+
+fn has_open_order(ex: &model::ArbStrategyEx) -> bool {
+    ex.market != "transfer"
+        && !ex.current_order_id.is_empty()
+        && ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE
+}
+
+async fn order_status(api: &MyApi, ex: &model::ArbStrategyEx) -> anyhow::Result<(String, f64)> {
+    query_order(api, &ex.market, &ex.symbol, &ex.current_order_id).await
+}
+
+async fn query_order(
+    api: &MyApi,
+    market: &str,
+    symbol: &str,
+    order_id: &str,
+) -> anyhow::Result<(String, f64)> {
+    let req = FuturesGetOrderRequest {
+        symbol: symbol.to_string(),
+        order_id: Some(order_id.to_string()),
+        orig_client_order_id: None,
+    };
+    let status = match market {
+        "spot" => {
+            let order = api
+                .order_status(OrderStatusRequest {
+                    symbol: symbol.to_string(),
+                    order_id: Some(order_id.parse::<u64>()?),
+                    orig_client_order_id: None,
+                    recv_window: None,
+                })
+                .await?;
+            (format!("{:?}", order.status), order.executed_qty)
+        }
+        "futures" => {
+            let order = api.futures_order_status(req).await?;
+            (order.status, order.executed_qty)
+        }
+        "delivery" => {
+            let order = api.delivery_order_status(req).await?;
+            (order.status, order.executed_qty)
+        }
+        m => return Err(anyhow!("unknown market: {}", m)),
+    };
+    Ok(status)
+}
+
+// 订单不再变化的状态, 现货为 Debug 格式
+fn is_final(status: &str) -> bool {
+    ["FILLED", "CANCELED", "EXPIRED", "REJECTED"]
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case(status))
+}
+
+// 平仓市价单查询成交次数, 间隔 [scheduler] order_check_ms
+const FILL_CHECKS: usize = 10;
+
+// 等待市价单结束, 返回实际成交数量
+async fn wait_fill(
+    api: &MyApi,
+    market: &str,
+    symbol: &str,
+    order_id: &str,
+    (mut status, mut executed): (String, f64),
+) -> anyhow::Result<Decimal> {
+    for _ in 0..FILL_CHECKS {
+        if is_final(&status) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(C.scheduler.order_check_ms)).await;
+        (status, executed) = query_order(api, market, symbol, order_id).await?;
+    }
+    Decimal::from_f64(executed).ok_or(anyhow!("invalid executed qty {} for {}", executed, order_id))
+}
+
+/// 策略执行明细及交易所订单实时状态
+pub async fn get_legs(strategy_id: i64) -> anyhow::Result<(model::ArbStrategy, Vec<Leg>)> {
+    let strategy = sql::get_arb_strategy_by_id(strategy_id).await?;
+    let api = account::get_api(strategy.user_id)?;
+    let mut legs = Vec::new();
+    for ex in sql::get_arb_strategy_ex_list_by_strategy_id(strategy_id).await? {
+        let mut leg = Leg {
+            ex,
+            order_status: None,
+            order_executed: None,
+        };
+        if leg.ex.market != "transfer" && !leg.ex.current_order_id.is_empty() {
+            match order_status(&api, &leg.ex).await {
+                Ok((status, executed)) => {
+                    leg.order_status = Some(status);
+                    leg.order_executed = Decimal::from_f64(executed);
+                }
+                Err(e) => leg.order_status = Some(format!("err: {}", e)),
+            }
+        }
+        legs.push(leg);
+    }
+    Ok((strategy, legs))
+}
+
+/// 强制标记执行完成/未完成
+pub async fn mark_leg(ex_id: i64, done: bool, reason: &str) -> anyhow::Result<()> {
+    let ex = sql::get_arb_strategy_ex_by_id(ex_id).await?;
+    let (option_status, action) = if done {
+        (model::arb_strategy_ex::OPTION_STATUS_DONE, "mark done")
+    } else {
+        (model::arb_strategy_ex::OPTION_STATUS_UN_DONE, "mark undone")
+    };
+    let bak = audit(&ex.bak, action, reason);
+    sql::update_strategy_ex_manual_by_id(ex.id, option_status, &ex.current_order_id, &bak).await?;
+    warn!(
+        strategy_id = ex.arb_strategy_id,
+        ex_id = ex.id,
+        option_type = ex.option_type.as_str(),
+        reason = reason;
+        "{}", action
+    );
+    Ok(())
+}
+
+/// 撤销当前挂单, 清空 current_order_id 以便重新下单
+pub async fn cancel_leg_order(ex_id: i64, reason: &str) -> anyhow::Result<()> {
+    let ex = sql::get_arb_strategy_ex_by_id(ex_id).await?;
+    if !has_open_order(&ex) {
+        return Err(anyhow!("ex_id: {} has no open order", ex_id));
+    }
+    let api = account::get_api(ex.user_id)?;
+    cancel_order(&api, &ex, reason).await
+}
+
+async fn cancel_order(api: &MyApi, ex: &model::ArbStrategyEx, reason: &str) -> anyhow::Result<()> {
+    let req = FuturesGetOrderRequest {
+        symbol: ex.symbol.clone(),
+        order_id: Some(ex.current_order_id.clone()),
+        orig_client_order_id: None,
+    };
+    match ex.market.as_str() {
+        "spot" => {
+            api.cancel_order(OrderStatusRequest {
+                symbol: ex.symbol.clone(),
+                order_id: Some(ex.current_order_id.parse::<u64>()?),
+                orig_client_order_id: None,
+                recv_window: None,
+            })
+            .await?;
+        }
+        "futures" => {
+            api.futures_cancel_order(req).await?;
+        }
+        "delivery" => {
+            api.delivery_cancel_order(req).await?;
+        }
+        m => return Err(anyhow!("unknown market: {}", m)),
+    }
+
+    let ex_info_id = sql::get_arb_strategy_ex_info_by_order_id(ex.current_order_id.clone())
+        .await
+        .ok()
+        .map(|info| info.id);
+    let bak = audit(
+        &ex.bak,
+        &format!("cancel order {}", ex.current_order_id),
+        reason,
+    );
+    sql::leg_cancelled(ex.id, ex_info_id, ex.option_status, &bak).await?;
+    warn!(
+        strategy_id = ex.arb_strategy_id,
+        ex_id = ex.id,
+        option_type = ex.option_type.as_str(),
+        order_id = ex.current_order_id.as_str(),
+        reason = reason;
+        "cancel order"
+    );
+    Ok(())
+}
+
+/// 按已成交明细计算紧急平仓步骤: 先平合约, 再划转回现货, 最后卖出现货
+///
+/// 已执行的平仓记录在关联的 leg 上, 未关联 leg 的记录在 infos 中, 重复执行时只处理剩余仓位
+pub fn unwind_plan(
+    s: &model::ArbStrategy,
+    list: &[model::ArbStrategyEx],
+    infos: &[model::ArbStrategyExInfo],
+) -> Vec<UnwindStep> {
+    // 各市场交易对净持仓, 现货买入手续费以币扣除, 按到账数量计
+    let mut positions: Vec<((String, String), Decimal)> = Vec::new();
+    let mut add = |market: &str, symbol: &str, side: OrderSide, executed: Decimal| {
+        let key = (market.to_string(), symbol.to_string());
+        let amt = match side {
+            OrderSide::Buy if market == "spot" => executed.mul(Decimal::ONE.sub(s.spot_fee)),
+            OrderSide::Buy => executed,
+            OrderSide::Sell => -executed,
+        };
+        match positions.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v += amt,
+            None => positions.push((key, amt)),
+        }
+    };
+    for ex in list {
+        let Some(side) = leg_side(ex) else {
+            continue;
+        };
+        if ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE {
+            continue;
+        }
+        add(&ex.market, &ex.symbol, side, ex.option_executed_amt);
+    }
+    let unattached: Vec<&model::ArbStrategyExInfo> = infos
+        .iter()
+        .filter(|info| info.option_type.starts_with(UNWIND_PREFIX) && info.arb_strategy_ex_id == 0)
+        .collect();
+    for info in &unattached {
+        if let Some(side) = side_of(&info.option_type) {
+            add(&info.market, &info.symbol, side, info.executed_amt);
+        }
+    }
+
+    // 平仓记录到对应未完成的反向执行明细
+    let close_ex_id = |market: &str, symbol: &str, side: &OrderSide| {
+        list.iter()
+            .find(|ex| {
+                ex.market == market
+                    && ex.symbol == symbol
+                    && ex.option_status != model::arb_strategy_ex::OPTION_STATUS_DONE
+                    && leg_side(ex).as_ref() == Some(side)
+            })
+            .map(|ex| ex.id)
+    };
+    let order_step = |market: &str, symbol: &str, net: Decimal, amount: Decimal| {
+        let side = if net > Decimal::ZERO {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        UnwindStep::Order {
+            ex_id: close_ex_id(market, symbol, &side),
+            market: market.to_string(),
+            symbol: symbol.to_string(),
+            side,
+            amount,
+        }
+    };
+
+    let mut steps = Vec::new();
+    for ((market, symbol), net) in &positions {
+        if market != "spot" && !net.is_zero() {
+            steps.push(order_step(market, symbol, *net, net.abs()));
+        }
+    }
+
+    // 现货余额: 划出的部分扣除, 划回到账的部分加回
+    let mut spot_transferred = Decimal::ZERO;
+    let transfer = |option_type: &str| list.iter().find(|ex| ex.option_type == option_type);
+    if let Some(out) = transfer("transfer_spot_to_delivery") {
+        if out.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE {
+            spot_transferred -= out.option_executed_amt;
+            let back = transfer("transfer_delivery_to_spot");
+            let back_amt = match back {
+                Some(ex) if ex.option_status == model::arb_strategy_ex::OPTION_STATUS_DONE => {
+                    Some(ex.option_executed_amt)
+                }
+                _ => unattached
+                    .iter()
+                    .filter(|info| info.option_type == UNWIND_TRANSFER)
+                    .map(|info| info.executed_amt)
+                    .reduce(|a, b| a + b),
+            };
+            let back_amt = match back_amt {
+                Some(amt) => amt,
+                None => {
+                    let amount = out
+                        .option_executed_amt
+                        .mul(Decimal::ONE.sub(s.delivery_fee))
+                        .round_dp_with_strategy(s.to_amt_truncate as u32, RoundingStrategy::ToZero);
+                    steps.push(UnwindStep::Transfer {
+                        ex_id: back.map(|ex| ex.id),
+                        coin: s.coin.clone(),
+                        transfer_type: UniversalTransferType::CmfutureMain,
+                        amount,
+                    });
+                    amount
+                }
+            };
+            spot_transferred += back_amt;
+        }
+    }
+
+    for ((market, symbol), net) in &positions {
+        if market == "spot" {
+            let net = *net + spot_transferred;
+            let amount = net
+                .abs()
+                .round_dp_with_strategy(s.from_amt_truncate as u32, RoundingStrategy::ToZero);
+            if !amount.is_zero() {
+                steps.push(order_step(market, symbol, net, amount));
+            }
+        }
+    }
+
+    steps
+}
+
+/// 紧急平仓: 停止策略, 撤销挂单, 市价平掉已成交仓位, 最后标记策略完成
+pub async fn unwind(strategy_id: i64, reason: &str) -> anyhow::Result<Vec<UnwindStep>> {
+    let s = sql::get_arb_strategy_by_id(strategy_id).await?;
+    let bak = audit(&s.bak, "unwind start", reason);
+    sql::update_strategy_manual_by_id(s.id, model::arb_strategy::DOING_STATUS_UN_RUN, &bak).await?;
+    warn!(strategy_id = s.id, reason = reason; "unwind start");
+    // 等待调度器中正在执行的步骤结束, 平仓期间不再执行
+    let _hold = scheduler::hold_strategy(s.id).await;
+
+    let api = account::get_api(s.user_id)?;
+    for ex in sql::get_arb_strategy_ex_list_by_strategy_id(s.id).await? {
+        if has_open_order(&ex) {
+            cancel_order(&api, &ex, reason).await?;
+        }
+    }
+
+    // 撤单后重新读取
+    let list = sql::get_arb_strategy_ex_list_by_strategy_id(s.id).await?;
+    let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(s.id).await?;
+    let steps = unwind_plan(&s, &list, &infos);
+    for step in &steps {
+        execute_step(&api, &s, &list, step, reason).await?;
+    }
+
+    let bak = audit(&Some(bak), "unwind done", reason);
+    sql::update_strategy_manual_by_id(s.id, model::arb_strategy::DOING_STATUS_DONE, &bak).await?;
+    warn!(strategy_id = s.id, steps = steps.len(); "unwind done");
+    Ok(steps)
+}
+
+async fn execute_step(
+    api: &MyApi,
+    s: &model::ArbStrategy,
+    list: &[model::ArbStrategyEx],
+    step: &UnwindStep,
+    reason: &str,
+) -> anyhow::Result<()> {
+    let (ex_id, order_id, market, symbol, option_type, amount, executed) = match step {
+        UnwindStep::Order {
+            ex_id,
+            market,
+            symbol,
+            side,
+            amount,
+        } => {
+            let quantity = Some(
+                amount
+                    .to_f64()
+                    .ok_or(anyhow!("invalid unwind amount {} for {}", amount, symbol))?,
+            );
+            let (order_id, placed) = match market.as_str() {
+                "spot" => {
+                    let order = api
+                        .place_order(OrderRequest {
+                            symbol: symbol.clone(),
+                            side: side.clone(),
+                            order_type: OrderType::Market,
+                            quantity,
+                            ..OrderRequest::default()
+                        })
+                        .await?;
+                    (
+                        order.order_id,
+                        (format!("{:?}", order.status), order.executed_qty),
+                    )
+                }
+                "futures" | "delivery" => {
+                    // 只减仓, 重复执行时不会反向开仓
+                    let order = FuturesOrderRequest {
+                        symbol: symbol.clone(),
+                        side: side.clone(),
+                        order_type: OrderType::Market,
+                        quantity,
+                        reduce_only: Some(true),
+                        ..FuturesOrderRequest::default()
+                    };
+                    let order = if market == "futures" {
+                        api.futures_place_order(order).await?
+                    } else {
+                        api.delivery_place_order(order).await?
+                    };
+                    (order.order_id, (order.status, order.executed_qty))
+                }
+                m => return Err(anyhow!("unknown market: {}", m)),
+            };
+            let option_type = format!("{}{}_{:?}", UNWIND_PREFIX, market, side).to_lowercase();
+            metrics::order(&option_type, "placed");
+            let executed =
+                wait_fill(api, market, symbol, &order_id.to_string(), placed).await?;
+            (
+                *ex_id,
+                order_id,
+                market.clone(),
+                symbol.clone(),
+                option_type,
+                *amount,
+                executed,
+            )
+        }
+        UnwindStep::Transfer {
+            ex_id,
+            coin,
+            transfer_type,
+            amount,
+        } => {
+            let transfer = api
+                .universal_transfer(
+                    coin.clone(),
+                    amount
+                        .to_f64()
+                        .ok_or(anyhow!("invalid unwind transfer amount {} for {}", amount, coin))?,
+                    transfer_type.clone(),
+                )
+                .await?;
+            let option_type = UNWIND_TRANSFER.to_string();
+            metrics::order(&option_type, "placed");
+            // 划转成功即全部到账
+            (
+                *ex_id,
+                transfer.tran_id,
+                "transfer".to_string(),
+                coin.clone(),
+                option_type,
+                *amount,
+                *amount,
+            )
+        }
+    };
+    warn!(
+        strategy_id = s.id,
+        ex_id = ex_id.unwrap_or_default(),
+        option_type = option_type.as_str(),
+        symbol = symbol.as_str(),
+        amount:% = amount,
+        executed:% = executed,
+        order_id = order_id,
+        reason = reason;
+        "unwind"
+    );
+
+    // 按实际成交记录, 与 leg 完成在同一事务中写入; 未全部成交时不关联 leg, 重新执行时平掉剩余
+    let filled = executed >= amount;
+    let ex = ex_id
+        .filter(|_| filled)
+        .and_then(|id| list.iter().find(|ex| ex.id == id));
+    let bak = ex.map(|ex| audit(&ex.bak, "unwind", reason)).unwrap_or_default();
+    metrics::order(&option_type, if filled { "filled" } else { "expired" });
+    let info = model::ArbStrategyExInfo {
+        id: 0,
+        user_id: s.user_id,
+        platform: s.platform.clone(),
+        option_choose: s.option_choose.clone(),
+        arb_strategy_id: s.id,
+        arb_strategy_ex_id: ex.map(|ex| ex.id).unwrap_or_default(),
+        coin: s.coin.clone(),
+        market,
+        symbol,
+        option_type,
+        price: Decimal::ZERO,
+        amount,
+        executed_amt: executed,
+        order_id: order_id.to_string(),
+        is_ok: if filled {
+            model::arb_strategy_ex_info::IS_OK_DONE
+        } else {
+            model::arb_strategy_ex_info::IS_OK_EXPIRED
+        },
+        created: Some(Local::now().timestamp()),
+        updated: None,
+        bak: Some(reason.to_string()),
+    };
+    sql::leg_unwound(ex.map(|ex| ex.id), info, &bak).await?;
+    if !filled {
+        return Err(anyhow!(
+            "unwind order {} filled {} of {}",
+            order_id,
+            executed,
+            amount
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::script::OrderRule;
+    use crate::mock::{Behavior, MockServer, Script};
+
+    fn ex(
+        id: i64,
+        market: &str,
+        option_type: &str,
+        status: i8,
+        executed: i64,
+    ) -> model::ArbStrategyEx {
+        model::ArbStrategyEx {
+            id,
+            market: market.to_string(),
+            symbol: if market == "transfer" {
+                "BTC"
+            } else if market == "spot" {
+                "BTCUSDT"
+            } else {
+                "BTCUSD_PERP"
+            }
+            .to_string(),
+            option_type: option_type.to_string(),
+            option_status: status,
+            option_executed_amt: Decimal::from(executed),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unwind_plan_positive() {
+        let s = model::ArbStrategy {
+            coin: "BTC".to_string(),
+            ..Default::default()
+        };
+        let done = model::arb_strategy_ex::OPTION_STATUS_DONE;
+        let un_done = model::arb_strategy_ex::OPTION_STATUS_UN_DONE;
+        let list = vec![
+            ex(1, "spot", "spot_buy", done, 2),
+            ex(2, "transfer", "transfer_spot_to_delivery", done, 2),
+            ex(3, "delivery", "delivery_sell", done, 5),
+            ex(4, "delivery", "delivery_buy", un_done, 0),
+            ex(5, "transfer", "transfer_delivery_to_spot", un_done, 0),
+            ex(6, "spot", "spot_sell", un_done, 0),
+        ];
+        let steps = unwind_plan(&s, &list, &[]);
+        assert_eq!(steps.len(), 3);
+        assert!(
+            matches!(&steps[0], UnwindStep::Order { ex_id: Some(4), side: OrderSide::Buy, amount, .. } if *amount == Decimal::from(5))
+        );
+        assert!(matches!(
+            &steps[1],
+            UnwindStep::Transfer { ex_id: Some(5), .. }
+        ));
+        assert!(matches!(
+            &steps[2],
+            UnwindStep::Order {
+                ex_id: Some(6),
+                side: OrderSide::Sell,
+                ..
+            }
+        ));
+
+        // 只完成现货买入, 直接卖出
+        let list = vec![
+            ex(1, "spot", "spot_buy", done, 2),
+            ex(6, "spot", "spot_sell", un_done, 0),
+        ];
+        let steps = unwind_plan(&s, &list, &[]);
+        assert_eq!(steps.len(), 1);
+    }
+
+    #[test]
+    fn test_unwind_plan_rerun() {
+        let s = model::ArbStrategy {
+            coin: "BTC".to_string(),
+            ..Default::default()
+        };
+        let done = model::arb_strategy_ex::OPTION_STATUS_DONE;
+        let unwound = |market: &str, option_type: &str, executed: i64| model::ArbStrategyExInfo {
+            market: market.to_string(),
+            symbol: if market == "spot" { "BTCUSDT" } else { "BTC" }.to_string(),
+            option_type: option_type.to_string(),
+            executed_amt: Decimal::from(executed),
+            ..Default::default()
+        };
+
+        // 第一次平仓完成合约买入后划转失败, 重新执行从划转开始
+        let list = vec![
+            ex(1, "spot", "spot_buy", done, 2),
+            ex(2, "transfer", "transfer_spot_to_delivery", done, 2),
+            ex(3, "delivery", "delivery_sell", done, 5),
+            ex(4, "delivery", "delivery_buy", done, 5),
+        ];
+        let steps = unwind_plan(&s, &list, &[]);
+        assert_eq!(steps.len(), 2);
+        assert!(matches!(&steps[0], UnwindStep::Transfer { ex_id: None, .. }));
+        assert!(matches!(
+            &steps[1],
+            UnwindStep::Order {
+                ex_id: None,
+                side: OrderSide::Sell,
+                ..
+            }
+        ));
+
+        // 未关联 leg 的划转及卖出已执行
+        let infos = vec![
+            unwound("transfer", UNWIND_TRANSFER, 2),
+            unwound("spot", "unwind_spot_sell", 2),
+        ];
+        assert!(unwind_plan(&s, &list, &infos).is_empty());
+        // 只卖出一部分时卖出剩余
+        let infos = vec![
+            unwound("transfer", UNWIND_TRANSFER, 2),
+            unwound("spot", "unwind_spot_sell", 1),
+        ];
+        let steps = unwind_plan(&s, &list, &infos);
+        assert!(
+            matches!(&steps[..], [UnwindStep::Order { side: OrderSide::Sell, amount, .. }] if *amount == Decimal::ONE)
+        );
+    }
+
+    #[test]
+    fn test_unwind_plan_fee() {
+        let s = model::ArbStrategy {
+            coin: "BTC".to_string(),
+            spot_fee: Decimal::new(1, 3),
+            delivery_fee: Decimal::new(5, 4),
+            from_amt_truncate: 4,
+            to_amt_truncate: 4,
+            ..Default::default()
+        };
+        let done = model::arb_strategy_ex::OPTION_STATUS_DONE;
+        let un_done = model::arb_strategy_ex::OPTION_STATUS_UN_DONE;
+        let amount = |step: &UnwindStep| match step {
+            UnwindStep::Order { amount, .. } | UnwindStep::Transfer { amount, .. } => *amount,
+        };
+
+        // 买入 2 到账 1.998 全部划出, 划回 1.998 * 0.9995 截断为 1.997, 卖出划回到账的数量
+        let mut list = vec![
+            ex(1, "spot", "spot_buy", done, 2),
+            ex(2, "transfer", "transfer_spot_to_delivery", done, 0),
+            ex(3, "delivery", "delivery_sell", done, 5),
+            ex(4, "delivery", "delivery_buy", un_done, 0),
+            ex(5, "transfer", "transfer_delivery_to_spot", un_done, 0),
+            ex(6, "spot", "spot_sell", un_done, 0),
+        ];
+        list[1].option_executed_amt = Decimal::new(1998, 3);
+        let steps = unwind_plan(&s, &list, &[]);
+        assert_eq!(steps.len(), 3);
+        assert_eq!(amount(&steps[1]), Decimal::new(1997, 3));
+        assert_eq!(amount(&steps[2]), Decimal::new(1997, 3));
+
+        // 只划出 1.5, 未划出的 0.498 一并卖出
+        list[1].option_executed_amt = Decimal::new(15, 1);
+        let steps = unwind_plan(&s, &list, &[]);
+        assert_eq!(amount(&steps[1]), Decimal::new(14992, 4));
+        assert_eq!(amount(&steps[2]), Decimal::new(19972, 4));
+
+        // 已划回时按实际到账数量
+        list[4].option_status = done;
+        list[4].option_executed_amt = Decimal::new(149, 2);
+        let steps = unwind_plan(&s, &list, &[]);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(amount(&steps[1]), Decimal::new(1988, 3));
+    }
+
+    #[tokio::test]
+    async fn test_unwind_partial_on_mock() {
+        crate::db::init_test_env().await.unwrap();
+        let script = Script {
+            orders: vec![OrderRule {
+                market: Some("futures".to_string()),
+                symbol: None,
+                side: None,
+                behavior: Behavior::Partial,
+                fill_ratio: 0.4,
+                code: 0,
+                msg: String::new(),
+                times: Some(1),
+            }],
+            ..Script::default()
+        };
+        let server = MockServer::start(script, "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap();
+        let id = sql::insert_arb_strategy(model::ArbStrategy {
+            coin: "BTC".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap() as i64;
+        let s = sql::get_arb_strategy_by_id(id).await.unwrap();
+        let done = model::arb_strategy_ex::OPTION_STATUS_DONE;
+        let un_done = model::arb_strategy_ex::OPTION_STATUS_UN_DONE;
+        for leg in [
+            ex(0, "futures", "futures_sell", done, 3),
+            ex(0, "futures", "futures_buy", un_done, 0),
+        ] {
+            sql::insert_arb_strategy_ex(model::ArbStrategyEx {
+                arb_strategy_id: id,
+                ..leg
+            })
+            .await
+            .unwrap();
+        }
+
+        // 部分成交: 按实际成交记录, leg 未完成
+        let list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let steps = unwind_plan(&s, &list, &[]);
+        assert!(execute_step(&server.api(), &s, &list, &steps[0], "test").await.is_err());
+        let list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(id).await.unwrap();
+        assert_eq!(list[1].option_status, un_done);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].arb_strategy_ex_id, 0);
+        assert_eq!(infos[0].executed_amt, Decimal::new(12, 1));
+
+        // 重新执行平掉剩余
+        let steps = unwind_plan(&s, &list, &infos);
+        assert!(
+            matches!(&steps[..], [UnwindStep::Order { ex_id: Some(_), amount, .. }] if *amount == Decimal::new(18, 1))
+        );
+        execute_step(&server.api(), &s, &list, &steps[0], "test").await.unwrap();
+        let list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(id).await.unwrap();
+        assert_eq!(list[1].option_status, done);
+        assert!(unwind_plan(&s, &list, &infos).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_leg_unwound_on_sqlite() {
+        crate::db::init_test_env().await.unwrap();
+        let s = model::ArbStrategy {
+            coin: "ETH".to_string(),
+            ..Default::default()
+        };
+        let id = sql::insert_arb_strategy(s).await.unwrap() as i64;
+        let s = sql::get_arb_strategy_by_id(id).await.unwrap();
+        let done = model::arb_strategy_ex::OPTION_STATUS_DONE;
+        let un_done = model::arb_strategy_ex::OPTION_STATUS_UN_DONE;
+        for leg in [
+            ex(0, "futures", "futures_sell", done, 3),
+            ex(0, "futures", "futures_buy", un_done, 0),
+        ] {
+            sql::insert_arb_strategy_ex(model::ArbStrategyEx {
+                arb_strategy_id: id,
+                ..leg
+            })
+            .await
+            .unwrap();
+        }
+        let list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let steps = unwind_plan(&s, &list, &[]);
+        let [UnwindStep::Order {
+            ex_id: Some(ex_id),
+            amount,
+            ..
+        }] = &steps[..]
+        else {
+            panic!("unexpected plan {:?}", steps);
+        };
+
+        let info = model::ArbStrategyExInfo {
+            arb_strategy_id: id,
+            arb_strategy_ex_id: *ex_id,
+            market: "futures".to_string(),
+            option_type: "unwind_futures_buy".to_string(),
+            amount: *amount,
+            executed_amt: *amount,
+            order_id: "9001".to_string(),
+            ..Default::default()
+        };
+        sql::leg_unwound(Some(*ex_id), info, "unwind").await.unwrap();
+
+        // 重新执行时没有剩余仓位
+        let list = sql::get_arb_strategy_ex_list_by_strategy_id(id).await.unwrap();
+        let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(id).await.unwrap();
+        assert_eq!(list[1].option_executed_amt, Decimal::from(3));
+        assert_eq!(list[1].current_order_id, "9001");
+        assert!(unwind_plan(&s, &list, &infos).is_empty());
+    }
+}
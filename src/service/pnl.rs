@@ -0,0 +1,496 @@
+use crate::binance::account;
+use crate::binance::rest_model::{Income, Trade};
+use crate::conf::C;
+use crate::helper::shutdown;
+use crate::binance::rest_model::OrderSide;
+use crate::service::operator;
+use crate::{model, service, sql};
+use anyhow::anyhow;
+use chrono::Local;
+use log::{error, info};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::select;
+
+// 期现套利策略盈亏: 按 arb_strategy_ex_info 的订单拉取实际成交、手续费及资金费
+//
+// 每个 (market, symbol) 按移动平均成本计算已实现盈亏, 未平仓位按当前价格计算未实现盈亏
+// 现货、U本位以 USDT 计价; 币本位为反向合约以币计价, 按 张数*面值 的 1/价格 记账
+// 现货手续费为本币时从持仓扣除, 其余手续费及资金费计入已实现盈亏, 手续费按成交价折算
+// 资金费按结算时同账户同交易对各策略的持仓占比分摊, 不会在多个策略中重复计入
+
+const USDT: &str = "USDT";
+
+/// 单个交易对的盈亏
+#[derive(Debug, Clone, Serialize)]
+pub struct LegPnl {
+    pub market: String,
+    pub symbol: String,
+    // 盈亏计价币种, USDT 或策略币种
+    pub asset: String,
+    // 未平仓位, 现货/U本位为币数量, 币本位为 张数*面值
+    pub position: Decimal,
+    pub realized: Decimal,
+    pub unrealized: Decimal,
+    pub fee_usdt: Decimal,
+    pub funding: Decimal,
+    pub trades: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Pnl {
+    pub summary: model::ArbStrategyPnl,
+    pub legs: Vec<LegPnl>,
+}
+
+// 移动平均成本记账, position 多为正空为负
+#[derive(Debug, Clone, Default)]
+struct Book {
+    position: Decimal,
+    avg_price: Decimal,
+    realized: Decimal,
+}
+
+impl Book {
+    fn fill(&mut self, buy: bool, qty: Decimal, price: Decimal) {
+        let signed = if buy { qty } else { -qty };
+        // 开仓或加仓
+        if self.position.is_zero() || self.position.is_sign_positive() == buy {
+            let held = self.position.abs();
+            self.avg_price = (self.avg_price * held + price * qty) / (held + qty);
+            self.position += signed;
+            return;
+        }
+        // 减仓, 超出部分按成交价反向开仓
+        let closing = qty.min(self.position.abs());
+        let direction = if self.position.is_sign_positive() {
+            Decimal::ONE
+        } else {
+            Decimal::NEGATIVE_ONE
+        };
+        self.realized += closing * (price - self.avg_price) * direction;
+        self.position += signed;
+        if self.position.is_zero() {
+            self.avg_price = Decimal::ZERO;
+        } else if self.position.is_sign_positive() == buy {
+            self.avg_price = price;
+        }
+    }
+
+    // 手续费从持仓扣除, 持仓成本不变
+    fn deduct(&mut self, qty: Decimal) {
+        if self.position > qty {
+            self.avg_price = self.avg_price * self.position / (self.position - qty);
+        }
+        self.position -= qty;
+    }
+
+    fn unrealized(&self, mark: Decimal) -> Decimal {
+        self.position * (mark - self.avg_price)
+    }
+}
+
+/// 按成交计算单个交易对盈亏, prices 为手续费币种的当前 USDT 价格, 策略币种及 USDT 按成交价折算
+fn leg_pnl(
+    s: &model::ArbStrategy,
+    market: &str,
+    symbol: &str,
+    trades: &[Trade],
+    funding: Decimal,
+    mark: Option<Decimal>,
+    prices: &HashMap<String, Decimal>,
+) -> anyhow::Result<LegPnl> {
+    let inverse = market == "delivery";
+    let asset = if inverse { s.coin.as_str() } else { USDT };
+    let price_of = |a: &str| prices.get(a).copied().ok_or(anyhow!("no price for {}", a));
+    let contract_value = Decimal::from(s.contract_mul);
+
+    let mut book = Book::default();
+    let mut fee_usdt = Decimal::ZERO;
+    for t in trades {
+        let qty = decimal(t.qty)?;
+        let price = decimal(t.price)?;
+        let commission = decimal(t.commission)?;
+        if inverse {
+            // 反向合约多头在 1/价格 上为空头
+            book.fill(!t.buyer, qty * contract_value, Decimal::ONE / price);
+        } else {
+            book.fill(t.buyer, qty, price);
+        }
+
+        // 成交价即策略币种的 USDT 价格, 其余币种(如 BNB)没有成交时价格, 按当前价格
+        let fee = match t.commission_asset.as_str() {
+            USDT => commission,
+            a if a == s.coin => commission * price,
+            a => commission * price_of(a)?,
+        };
+        fee_usdt += fee;
+        if market == "spot" && t.commission_asset == s.coin {
+            book.deduct(commission);
+        } else if t.commission_asset == asset {
+            book.realized -= commission;
+        } else if inverse {
+            book.realized -= fee / price;
+        } else {
+            book.realized -= fee;
+        }
+    }
+
+    let unrealized = match mark {
+        _ if book.position.is_zero() => Decimal::ZERO,
+        Some(mark) if inverse => book.unrealized(Decimal::ONE / mark),
+        Some(mark) => book.unrealized(mark),
+        None => return Err(anyhow!("no mark price for {} {}", market, symbol)),
+    };
+
+    Ok(LegPnl {
+        market: market.to_string(),
+        symbol: symbol.to_string(),
+        asset: asset.to_string(),
+        position: book.position,
+        realized: book.realized + funding,
+        unrealized,
+        fee_usdt,
+        funding,
+        trades: trades.len(),
+    })
+}
+
+/// 策略分得的资金费: 每笔资金费按结算时本策略持仓绝对值占该交易对所有策略持仓绝对值之比分摊,
+/// infos 为同账户同交易对所有策略的成交明细, 按更新时间(无则创建时间)计入持仓
+fn funding_share(
+    strategy_id: i64,
+    incomes: &[Income],
+    infos: &[model::ArbStrategyExInfo],
+) -> anyhow::Result<Decimal> {
+    let mut funding = Decimal::ZERO;
+    for income in incomes {
+        let mut positions: HashMap<i64, Decimal> = HashMap::new();
+        for info in infos {
+            let time = info.updated.or(info.created).unwrap_or_default() * 1000;
+            if time > income.time as i64 {
+                continue;
+            }
+            let qty = match operator::side_of(&info.option_type) {
+                Some(OrderSide::Buy) => info.executed_amt,
+                Some(OrderSide::Sell) => -info.executed_amt,
+                None => continue,
+            };
+            *positions.entry(info.arb_strategy_id).or_default() += qty;
+        }
+        let total: Decimal = positions.values().map(|p| p.abs()).sum();
+        let Some(own) = positions.get(&strategy_id).filter(|_| !total.is_zero()) else {
+            continue;
+        };
+        funding += decimal(income.income)? * own.abs() / total;
+    }
+    Ok(funding)
+}
+
+/// 汇总为 USDT 及币本位盈亏
+fn summary(s: &model::ArbStrategy, legs: &[LegPnl], coin_price: Decimal) -> model::ArbStrategyPnl {
+    let mut pnl = model::ArbStrategyPnl {
+        arb_strategy_id: s.id,
+        user_id: s.user_id,
+        coin: s.coin.clone(),
+        coin_price,
+        ..Default::default()
+    };
+    // (USDT, 币)
+    let both = |v: Decimal, asset: &str| {
+        if asset == USDT {
+            (v, v / coin_price)
+        } else {
+            (v * coin_price, v)
+        }
+    };
+    for leg in legs {
+        let (realized_usdt, realized_coin) = both(leg.realized, &leg.asset);
+        let (unrealized_usdt, unrealized_coin) = both(leg.unrealized, &leg.asset);
+        pnl.realized_usdt += realized_usdt;
+        pnl.realized_coin += realized_coin;
+        pnl.unrealized_usdt += unrealized_usdt;
+        pnl.unrealized_coin += unrealized_coin;
+        pnl.fee_usdt += leg.fee_usdt;
+        pnl.funding_usdt += both(leg.funding, &leg.asset).0;
+    }
+    for v in [
+        &mut pnl.realized_usdt,
+        &mut pnl.realized_coin,
+        &mut pnl.unrealized_usdt,
+        &mut pnl.unrealized_coin,
+        &mut pnl.fee_usdt,
+        &mut pnl.funding_usdt,
+    ] {
+        *v = v.round_dp(8);
+    }
+    pnl
+}
+
+/// 拉取成交、资金费及当前价格计算策略盈亏
+pub async fn compute(s: &model::ArbStrategy) -> anyhow::Result<Pnl> {
+    let api = account::get_api(s.user_id)?;
+    let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(s.id).await?;
+
+    // (market, symbol) -> 成交, 按首次下单顺序
+    let mut fills: Vec<((String, String), Vec<Trade>)> = Vec::new();
+    let mut orders = HashSet::new();
+    for info in infos.iter() {
+        if info.market == "transfer" || info.order_id.is_empty() {
+            continue;
+        }
+        if !orders.insert((&info.market, &info.symbol, &info.order_id)) {
+            continue;
+        }
+        let trades = api
+            .get_order_trades(&info.market, &info.symbol, &info.order_id)
+            .await?;
+        let key = (info.market.clone(), info.symbol.clone());
+        match fills.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, list)) => list.extend(trades),
+            None => fills.push((key, trades)),
+        }
+    }
+    for (_, trades) in fills.iter_mut() {
+        trades.sort_by_key(|t| (t.time, t.id));
+    }
+    if fills.is_empty() {
+        return Ok(Pnl {
+            summary: summary(s, &[], Decimal::ZERO),
+            legs: vec![],
+        });
+    }
+
+    let mut prices = HashMap::from([(USDT.to_string(), Decimal::ONE)]);
+    let assets: HashSet<&str> = fills
+        .iter()
+        .flat_map(|(_, trades)| trades.iter().map(|t| t.commission_asset.as_str()))
+        .chain([s.coin.as_str()])
+        .collect();
+    for asset in assets {
+        if !prices.contains_key(asset) {
+            prices.insert(
+                asset.to_string(),
+                price("spot", &format!("{}{}", asset, USDT)).await?,
+            );
+        }
+    }
+
+    let now = Local::now().timestamp_millis();
+    let start_time = s.created.unwrap_or_default() * 1000;
+    let end_time = match s.doing_status {
+        model::arb_strategy::DOING_STATUS_DONE => s.updated.map_or(now, |u| u * 1000),
+        _ => now,
+    };
+    let mut legs = Vec::new();
+    for ((market, symbol), trades) in fills.iter() {
+        let perpetual = market == "futures" || symbol.ends_with("_PERP");
+        let funding = if perpetual && !trades.is_empty() {
+            let incomes = api
+                .get_funding_income(market, symbol, start_time as u64, end_time as u64)
+                .await?;
+            let infos =
+                sql::get_arb_strategy_ex_info_list_by_symbol(s.user_id, market, symbol).await?;
+            funding_share(s.id, &incomes, &infos)?
+        } else {
+            Decimal::ZERO
+        };
+        let mark = price(market, symbol).await.ok();
+        legs.push(leg_pnl(s, market, symbol, trades, funding, mark, &prices)?);
+    }
+
+    Ok(Pnl {
+        summary: summary(s, &legs, prices[&s.coin]),
+        legs,
+    })
+}
+
+/// 计算并保存策略盈亏
+pub async fn refresh(s: &model::ArbStrategy) -> anyhow::Result<Pnl> {
+    let mut pnl = compute(s).await?;
+    let now = Local::now().timestamp();
+    let saved = sql::get_arb_strategy_pnl_by_strategy_id(s.id).await?;
+    pnl.summary.created = saved.and_then(|p| p.created).or(Some(now));
+    pnl.summary.updated = Some(now);
+    sql::save_arb_strategy_pnl(pnl.summary.clone()).await?;
+    info!(
+        strategy_id = s.id,
+        realized_usdt:% = pnl.summary.realized_usdt,
+        unrealized_usdt:% = pnl.summary.unrealized_usdt;
+        "strategy pnl"
+    );
+    Ok(pnl)
+}
+
+/// 定时计算运行中策略, 其余策略在状态变化后计算一次
+pub async fn start() {
+    let token = shutdown::token();
+    let interval = Duration::from_secs(C.pnl.interval_secs);
+    loop {
+        if let Err(e) = refresh_all().await {
+            error!(err:? = e; "refresh pnl err");
+        }
+        select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+async fn refresh_all() -> anyhow::Result<()> {
+    let saved: HashMap<i64, Option<i64>> = sql::get_arb_strategy_pnl_list()
+        .await?
+        .into_iter()
+        .map(|p| (p.arb_strategy_id, p.updated))
+        .collect();
+    for s in sql::get_arb_strategy_list().await? {
+        let stale = match saved.get(&s.id) {
+            _ if s.doing_status == model::arb_strategy::DOING_STATUS_RUN => true,
+            Some(updated) => *updated < s.updated,
+            None => s.doing_status == model::arb_strategy::DOING_STATUS_DONE,
+        };
+        if !stale {
+            continue;
+        }
+        if let Err(e) = refresh(&s).await {
+            error!(strategy_id = s.id, err:? = e; "strategy pnl err");
+        }
+        if shutdown::is_shutdown() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn price(market: &str, symbol: &str) -> anyhow::Result<Decimal> {
+    let info = service::get_binance_price(market.to_string(), symbol.to_string()).await?;
+    let close = info
+        .ticker
+        .current_close
+        .ok_or(anyhow!("no price for {} {}", market, symbol))?;
+    Ok(Decimal::from_str(&close)?)
+}
+
+fn decimal(v: f64) -> anyhow::Result<Decimal> {
+    Decimal::from_f64(v).ok_or(anyhow!("decimal from f64 {}", v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(buyer: bool, qty: f64, price: f64, commission: f64, asset: &str) -> Trade {
+        Trade {
+            symbol: String::new(),
+            id: 0,
+            order_id: 0,
+            price,
+            qty,
+            commission,
+            commission_asset: asset.to_string(),
+            time: 0,
+            buyer,
+        }
+    }
+
+    #[test]
+    fn test_positive_pnl() {
+        let s = model::ArbStrategy {
+            id: 1,
+            coin: "BTC".to_string(),
+            contract_mul: 100,
+            ..Default::default()
+        };
+        let prices = HashMap::from([
+            (USDT.to_string(), Decimal::ONE),
+            ("BTC".to_string(), Decimal::from(25000)),
+        ]);
+
+        // 现货 20000 买入 1 BTC, 手续费 0.001 BTC; 25000 卖出 0.999, 手续费 5 USDT
+        let spot = [
+            trade(true, 1.0, 20000.0, 0.001, "BTC"),
+            trade(false, 0.999, 25000.0, 5.0, "USDT"),
+        ];
+        let leg = leg_pnl(&s, "spot", "BTCUSDT", &spot, Decimal::ZERO, None, &prices).unwrap();
+        assert!(leg.position.is_zero());
+        // 0.999 * 25000 - 20000 - 5
+        assert_eq!(leg.realized.round_dp(8), Decimal::from(4970));
+        // 0.001 BTC 按成交价 20000 折算
+        assert_eq!(leg.fee_usdt, Decimal::from(25));
+
+        // 币本位 20000 卖出 10 张, 25000 买入 5 张, 剩余 5 张按 25000 计算
+        let delivery = [
+            trade(false, 10.0, 20000.0, 0.0, "BTC"),
+            trade(true, 5.0, 25000.0, 0.0, "BTC"),
+        ];
+        let mark = Some(Decimal::from(25000));
+        let leg = leg_pnl(
+            &s,
+            "delivery",
+            "BTCUSD_240628",
+            &delivery,
+            Decimal::ZERO,
+            mark,
+            &prices,
+        )
+        .unwrap();
+        assert_eq!(leg.asset, "BTC");
+        // 空头亏损 500 USD * (1/20000 - 1/25000) = 0.005 BTC
+        assert_eq!(leg.realized.round_dp(8), Decimal::new(-5, 3));
+        assert_eq!(leg.unrealized.round_dp(8), Decimal::new(-5, 3));
+
+        let pnl = summary(&s, &[leg], Decimal::from(25000));
+        assert_eq!(pnl.realized_usdt, Decimal::from(-125));
+        assert_eq!(pnl.unrealized_coin, Decimal::new(-5, 3));
+    }
+
+    #[test]
+    fn test_book_flip() {
+        let mut book = Book::default();
+        book.fill(true, Decimal::from(2), Decimal::from(10));
+        book.fill(false, Decimal::from(3), Decimal::from(12));
+        assert_eq!(book.realized, Decimal::from(4));
+        assert_eq!(book.position, Decimal::from(-1));
+        assert_eq!(book.avg_price, Decimal::from(12));
+        assert_eq!(book.unrealized(Decimal::from(11)), Decimal::ONE);
+    }
+
+    #[test]
+    fn test_funding_share() {
+        let info = |strategy_id: i64, option_type: &str, qty: i64, updated: i64| {
+            model::ArbStrategyExInfo {
+                arb_strategy_id: strategy_id,
+                option_type: option_type.to_string(),
+                executed_amt: Decimal::from(qty),
+                updated: Some(updated),
+                ..Default::default()
+            }
+        };
+        let income = |income: f64, time: u64| Income {
+            symbol: "BTCUSDT".to_string(),
+            income_type: "FUNDING_FEE".to_string(),
+            income,
+            asset: USDT.to_string(),
+            time,
+        };
+        // 策略 1 空 3, 策略 2 在 200 秒空 1, 策略 1 在 300 秒平仓
+        let infos = [
+            info(1, "futures_sell", 3, 100),
+            info(2, "futures_sell", 1, 200),
+            info(1, "futures_buy", 3, 300),
+        ];
+        let incomes = [
+            income(10.0, 150_000),
+            income(8.0, 250_000),
+            income(4.0, 350_000),
+        ];
+        // 10 全部 + 8 * 3/4, 平仓后不再分摊
+        assert_eq!(funding_share(1, &incomes, &infos).unwrap(), Decimal::from(16));
+        assert_eq!(funding_share(2, &incomes, &infos).unwrap(), Decimal::from(6));
+        assert_eq!(funding_share(3, &incomes, &infos).unwrap(), Decimal::ZERO);
+    }
+}
@@ -1,47 +1,170 @@
 use crate::binance::ws_model::BinanceTickerEvent;
-use crate::conf::redis_key;
+use crate::conf::{self, redis_key};
 use crate::db;
+use crate::helper::{clock, metrics};
 use crate::service::PriceStream;
-use redis::AsyncCommands;
+use anyhow::anyhow;
+use log::error;
+use redis::aio::MultiplexedConnection;
 use serde::{Deserialize, Serialize};
 use tokio::select;
 use tokio::sync::mpsc::UnboundedReceiver;
 
+// 价格缓存: 各平台、市场最新 ticker 及本地接收时间, 读取时按该行情最后一帧接收时间检查是否过期
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PriceInfo {
     pub ticker: BinanceTickerEvent,
     pub market: String,
+    pub platform: String,
+    // 本地接收时间(ms)
+    pub updated: i64,
+}
+
+// 缓存及发布的内容
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct CachedPrice {
+    ticker: BinanceTickerEvent,
+    updated: i64,
+}
+
+// 发布到 price:<platform>:<market> 的消息
+#[derive(Debug, Serialize)]
+struct PriceMessage<'a> {
+    platform: &'a str,
+    market: &'a str,
+    updated: i64,
+    tickers: &'a [BinanceTickerEvent],
 }
 
-pub async fn set_binance_price(mut price_rx: UnboundedReceiver<PriceStream>) {
+pub async fn set_price_cache(mut price_rx: UnboundedReceiver<PriceStream>) {
     let mut redis = db::get_db().unwrap().redis().await.unwrap();
     loop {
         select! {
             event = price_rx.recv() => {
-                if let Some(stream)  = event {
-                    // println!("{:?} {:?}", stream.market, stream.local_time);
-                    let key = format!("{}{}", stream.market.as_str(), redis_key::BINANCE_PRICE_KEY);
-
-                    let mut items = vec![];
-                    for ticker in stream.tickers {
-                        let ticker_json = serde_json::to_string(&ticker).unwrap();
-                        items.push((ticker.symbol, ticker_json))
-                    }
-                    let _: () = redis.hset_multiple(key, &items).await.unwrap();
-                    // info!("--------------, set binance {:?} price, current time: {:?}", stream.market, Local::now().timestamp_millis());
+                if let Some(stream) = event {
+                    metrics::PRICE_BACKLOG.dec();
+                    cache_price(&mut redis, &stream).await;
+                } else {
+                    break;
                 }
             },
         }
     }
 }
 
-pub async fn get_binance_price(market: String, symbol: String) -> anyhow::Result<PriceInfo> {
+/// 写入价格缓存并发布, 回放时按帧顺序直接调用
+pub async fn cache_price(redis: &mut MultiplexedConnection, stream: &PriceStream) {
+    let c = &conf::C.price_cache;
+    if stream.tickers.is_empty() {
+        return;
+    }
+    let platform = stream.platform.as_str().to_lowercase();
+    let market = stream.market.as_str();
+    let key = redis_key::price_key(&platform, market);
+    let tickers = with_price(&stream.tickers);
+
+    let mut items = vec![];
+    for ticker in &tickers {
+        let cached = CachedPrice {
+            ticker: ticker.clone(),
+            updated: stream.local_time,
+        };
+        let cached_json = serde_json::to_string(&cached).unwrap();
+        items.push((ticker.symbol.clone(), cached_json))
+    }
+    // 无价格的增量帧也说明行情存活
+    items.push((
+        redis_key::PRICE_FEED_UPDATED_FIELD.to_string(),
+        stream.local_time.to_string(),
+    ));
+    let mut pipe = redis::pipe();
+    pipe.hset_multiple(&key, &items).ignore();
+    pipe.expire(&key, c.ttl_secs as i64).ignore();
+    if c.publish && !tickers.is_empty() {
+        let message = PriceMessage {
+            platform: &platform,
+            market,
+            updated: stream.local_time,
+            tickers: &tickers,
+        };
+        let channel = redis_key::price_channel(&platform, market);
+        pipe.publish(channel, serde_json::to_string(&message).unwrap()).ignore();
+    }
+    if let Err(e) = pipe.query_async::<_, ()>(redis).await {
+        error!(platform = platform.as_str(), market = market, err:? = e; "set price cache err");
+    }
+}
+
+// 只缓存带最新价的 ticker, bybit 增量推送中未变化的 lastPrice 为空, 不能覆盖已缓存的价格
+fn with_price(tickers: &[BinanceTickerEvent]) -> Vec<BinanceTickerEvent> {
+    tickers
+        .iter()
+        .filter(|t| t.current_close.as_ref().is_some_and(|c| !c.is_empty()))
+        .cloned()
+        .collect()
+}
+
+/// 缓存中的价格, 不存在或超过 max_age_ms 未更新时返回错误
+pub async fn get_price(platform: &str, market: &str, symbol: &str) -> anyhow::Result<PriceInfo> {
     let mut redis = db::get_db()?.redis().await?;
 
-    let key = format!("{}{}", market, redis_key::BINANCE_PRICE_KEY);
-    let x: String = redis.hget(key, symbol).await?;
-    let ticker = serde_json::from_str::<BinanceTickerEvent>(x.as_str()).unwrap();
-    let info = PriceInfo { ticker, market };
+    let key = redis_key::price_key(platform, market);
+    let (x, feed_updated): (Option<String>, Option<i64>) = redis::cmd("HMGET")
+        .arg(key)
+        .arg(symbol)
+        .arg(redis_key::PRICE_FEED_UPDATED_FIELD)
+        .query_async(&mut redis)
+        .await?;
+    let x = x.ok_or(anyhow!("no price for {} {} {}", platform, market, symbol))?;
+    let cached = serde_json::from_str::<CachedPrice>(x.as_str())?;
+    let age = clock::now_millis() - feed_updated.unwrap_or(cached.updated);
+    if age > conf::C.price_cache.max_age_ms {
+        return Err(anyhow!(
+            "stale price feed for {} {} {}, age {}ms",
+            platform,
+            market,
+            symbol,
+            age
+        ));
+    }
+
+    Ok(PriceInfo {
+        ticker: cached.ticker,
+        market: market.to_string(),
+        platform: platform.to_string(),
+        updated: cached.updated,
+    })
+}
+
+pub async fn get_binance_price(market: String, symbol: String) -> anyhow::Result<PriceInfo> {
+    get_price("binance", &market, &symbol).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(info)
+    #[test]
+    fn test_with_price() {
+        let ticker = |symbol: &str, close: Option<&str>| BinanceTickerEvent {
+            event_type: "".to_string(),
+            event_time: 0,
+            symbol: symbol.to_string(),
+            current_close: close.map(|c| c.to_string()),
+            open: None,
+            high: None,
+            low: None,
+            volume: None,
+            quote_volume: None,
+        };
+        let tickers = [
+            ticker("BTCUSDT", Some("65000.1")),
+            ticker("ETHUSDT", None),
+            ticker("SOLUSDT", Some("")),
+        ];
+        let cached = with_price(&tickers);
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].symbol, "BTCUSDT");
+    }
 }
@@ -0,0 +1,340 @@
+use crate::binance::ws_model::{Market, Platform};
+use crate::conf;
+use crate::helper::{metrics, shutdown};
+use chrono::{TimeZone, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+// 原始行情录制: 每个 text 帧一行 json, 按 feed 写入 <dir>/<feed>/<开始时间>.jsonl.gz
+//
+// 写入中的文件带 .part 后缀, 切分时改名; 总大小超过上限时删除最早的文件
+// 行情连接只把帧放入有界 channel, 由 start 统一写盘; channel 满时丢弃新帧并计入 arb_recorder_dropped_frames_total
+
+pub const FILE_SUFFIX: &str = ".jsonl.gz";
+const PART_SUFFIX: &str = ".part";
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Frame {
+    pub venue: String,
+    pub market: String,
+    // 本地接收时间(ms)
+    pub ts: i64,
+    pub data: String,
+}
+
+type Channel = (Sender<Frame>, Mutex<Option<Receiver<Frame>>>);
+
+static CHANNEL: Lazy<Channel> = Lazy::new(|| {
+    let (tx, rx) = channel(conf::C.recorder.channel_capacity.max(1));
+    (tx, Mutex::new(Some(rx)))
+});
+
+pub fn feed_name(platform: &Platform, market: &Market) -> String {
+    format!("{}_{}", platform.as_str().to_lowercase(), market.as_str())
+}
+
+/// feed 开启录制时返回接收原始帧的回调
+pub fn recorder(platform: &Platform, market: &Market) -> Option<impl FnMut(&str) + Send> {
+    if !conf::C
+        .recorder
+        .feeds
+        .contains(&feed_name(platform, market))
+    {
+        return None;
+    }
+    let tx = CHANNEL.0.clone();
+    let venue = platform.as_str().to_lowercase();
+    let market = market.as_str().to_string();
+    let mut dropping = false;
+    Some(move |data: &str| {
+        let frame = Frame {
+            venue: venue.clone(),
+            market: market.clone(),
+            ts: chrono::Local::now().timestamp_millis(),
+            data: data.to_string(),
+        };
+        send(&tx, frame, &mut dropping);
+    })
+}
+
+// 不阻塞行情连接, channel 满时丢弃并计数, 连续丢弃只记录一次日志
+fn send(tx: &Sender<Frame>, frame: Frame, dropping: &mut bool) {
+    match tx.try_send(frame) {
+        Ok(()) => *dropping = false,
+        Err(TrySendError::Full(frame)) => {
+            metrics::RECORDER_DROPPED
+                .with_label_values(&[&frame.venue, &frame.market])
+                .inc();
+            if !*dropping {
+                warn!(venue = frame.venue.as_str(), market = frame.market.as_str(); "recorder channel full, dropping frames");
+                *dropping = true;
+            }
+        }
+        // 写盘任务已退出
+        Err(TrySendError::Closed(_)) => {}
+    }
+}
+
+/// 写盘任务, 退出时写完已收到的帧并关闭文件
+pub async fn start() {
+    let c = &conf::C.recorder;
+    if c.feeds.is_empty() {
+        return;
+    }
+    let Some(mut rx) = CHANNEL.1.lock().unwrap().take() else {
+        return;
+    };
+    let mut writer = Writer::new(
+        PathBuf::from(&c.dir),
+        c.max_file_mb * MB,
+        Duration::from_secs(c.rotate_secs),
+        c.max_total_mb * MB,
+    );
+    if let Err(e) = writer.recover() {
+        warn!(dir = c.dir.as_str(), err:? = e; "recover recorder files err");
+    }
+    info!(dir = c.dir.as_str(), feeds:? = c.feeds; "recorder started");
+
+    let token = shutdown::token();
+    loop {
+        select! {
+            frame = rx.recv() => match frame {
+                Some(frame) => {
+                    if let Err(e) = writer.write(&frame) {
+                        error!(venue = frame.venue.as_str(), market = frame.market.as_str(), err:? = e; "record frame err");
+                    }
+                }
+                None => break,
+            },
+            _ = token.cancelled() => break,
+        }
+    }
+    while let Ok(frame) = rx.try_recv() {
+        if let Err(e) = writer.write(&frame) {
+            error!(err:? = e; "record frame err");
+        }
+    }
+    if let Err(e) = writer.close() {
+        error!(err:? = e; "close recorder files err");
+    }
+}
+
+struct OpenFile {
+    encoder: GzEncoder<BufWriter<fs::File>>,
+    path: PathBuf,
+    opened: i64,
+    bytes: u64,
+}
+
+struct Writer {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    rotate: Duration,
+    max_total_bytes: u64,
+    files: HashMap<String, OpenFile>,
+}
+
+impl Writer {
+    fn new(dir: PathBuf, max_file_bytes: u64, rotate: Duration, max_total_bytes: u64) -> Self {
+        Writer {
+            dir,
+            max_file_bytes,
+            rotate,
+            max_total_bytes,
+            files: HashMap::new(),
+        }
+    }
+
+    fn write(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        let feed = format!("{}_{}", frame.venue, frame.market);
+        let expired = self.files.get(&feed).is_some_and(|f| {
+            f.bytes >= self.max_file_bytes || frame.ts - f.opened >= self.rotate.as_millis() as i64
+        });
+        if expired {
+            self.finish(&feed)?;
+        }
+        if !self.files.contains_key(&feed) {
+            let file = self.open(&feed, frame.ts)?;
+            self.files.insert(feed.clone(), file);
+        }
+        let file = self.files.get_mut(&feed).unwrap();
+        let mut line = serde_json::to_vec(frame)?;
+        line.push(b'\n');
+        file.encoder.write_all(&line)?;
+        file.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self, feed: &str, ts: i64) -> anyhow::Result<OpenFile> {
+        let dir = self.dir.join(feed);
+        fs::create_dir_all(&dir)?;
+        let name = Utc
+            .timestamp_millis_opt(ts)
+            .single()
+            .unwrap_or_default()
+            .format("%Y%m%d-%H%M%S%.3f");
+        let path = dir.join(format!("{}{}{}", name, FILE_SUFFIX, PART_SUFFIX));
+        let file = BufWriter::new(fs::File::create(&path)?);
+        Ok(OpenFile {
+            encoder: GzEncoder::new(file, Compression::default()),
+            path,
+            opened: ts,
+            bytes: 0,
+        })
+    }
+
+    fn finish(&mut self, feed: &str) -> anyhow::Result<()> {
+        let Some(file) = self.files.remove(feed) else {
+            return Ok(());
+        };
+        file.encoder.finish()?.flush()?;
+        fs::rename(&file.path, file.path.with_extension(""))?;
+        self.prune()
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        let feeds: Vec<String> = self.files.keys().cloned().collect();
+        for feed in feeds {
+            self.finish(&feed)?;
+        }
+        Ok(())
+    }
+
+    // 上次异常退出遗留的 .part 文件改名保留, 末尾可能不完整
+    fn recover(&self) -> anyhow::Result<()> {
+        for path in list(&self.dir, PART_SUFFIX)? {
+            fs::rename(&path, path.with_extension(""))?;
+        }
+        Ok(())
+    }
+
+    // 删除最早的文件直到总大小不超过上限
+    fn prune(&self) -> anyhow::Result<()> {
+        let mut files = Vec::new();
+        for path in list(&self.dir, FILE_SUFFIX)? {
+            let meta = fs::metadata(&path)?;
+            files.push((meta.modified()?, path, meta.len()));
+        }
+        files.sort();
+        let mut total: u64 = files.iter().map(|(_, _, len)| len).sum();
+        for (_, path, len) in files {
+            if total <= self.max_total_bytes {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= len;
+            info!(path = path.display().to_string().as_str(); "recorder file removed");
+        }
+        Ok(())
+    }
+}
+
+/// dir 下各 feed 目录中以 suffix 结尾的文件, 按文件名排序
+pub fn list(dir: &Path, suffix: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for feed in fs::read_dir(dir)? {
+        let feed = feed?.path();
+        if !feed.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(&feed)? {
+            let path = file?.path();
+            if path.to_string_lossy().ends_with(suffix) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_writer() {
+        let dir = std::env::temp_dir().join(format!("recorder-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let frame = |market: &str, ts: i64| Frame {
+            venue: "binance".to_string(),
+            market: market.to_string(),
+            ts,
+            data: r#"{"e":"24hrMiniTicker","s":"BTCUSDT"}"#.to_string(),
+        };
+
+        let mut writer = Writer::new(dir.clone(), MB, Duration::from_secs(60), MB);
+        writer.write(&frame("spot", 0)).unwrap();
+        writer.write(&frame("spot", 1_000)).unwrap();
+        writer.write(&frame("futures", 1_000)).unwrap();
+        // 超过 rotate 切分
+        writer.write(&frame("spot", 61_000)).unwrap();
+        assert_eq!(list(&dir, FILE_SUFFIX).unwrap().len(), 1);
+        writer.close().unwrap();
+
+        let files = list(&dir, FILE_SUFFIX).unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(list(&dir, PART_SUFFIX).unwrap().is_empty());
+        let reader = BufReader::new(GzDecoder::new(fs::File::open(&files[1]).unwrap()));
+        let frames: Vec<Frame> = reader
+            .lines()
+            .map(|l| serde_json::from_str(&l.unwrap()).unwrap())
+            .collect();
+        assert_eq!(frames, vec![frame("spot", 0), frame("spot", 1_000)]);
+
+        // 超过总大小删除最早的文件
+        let writer = Writer::new(dir.clone(), MB, Duration::from_secs(60), 0);
+        writer.prune().unwrap();
+        assert!(list(&dir, FILE_SUFFIX).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_send_drops_when_full() {
+        let (tx, mut rx) = channel(2);
+        let frame = |ts: i64| Frame {
+            venue: "test".to_string(),
+            market: "dropped".to_string(),
+            ts,
+            data: String::new(),
+        };
+        let dropped = || {
+            metrics::RECORDER_DROPPED
+                .with_label_values(&["test", "dropped"])
+                .get()
+        };
+        let mut dropping = false;
+        for ts in 0..5 {
+            send(&tx, frame(ts), &mut dropping);
+        }
+        assert!(dropping);
+        assert_eq!(dropped(), 3);
+        assert_eq!(rx.try_recv().unwrap().ts, 0);
+        assert_eq!(rx.try_recv().unwrap().ts, 1);
+        assert!(rx.try_recv().is_err());
+
+        // 有空位后恢复写入
+        send(&tx, frame(5), &mut dropping);
+        assert!(!dropping);
+        assert_eq!(rx.try_recv().unwrap().ts, 5);
+        assert_eq!(dropped(), 3);
+    }
+}
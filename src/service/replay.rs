@@ -0,0 +1,400 @@
+use crate::binance::ws_model::{Market, Platform, WebSocketEvent};
+use crate::conf::{self, Config};
+use crate::db;
+use crate::helper::{clock, metrics, shutdown};
+use crate::service::recorder::{self, Frame};
+use crate::service::{diff_rate, price, price_stream, PriceStream};
+use anyhow::anyhow;
+use flate2::read::MultiGzDecoder;
+use log::{info, warn};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+// 回放 recorder 录制的原始帧: 各 feed 按接收时间归并, 按速度发送到 PriceStream 管道
+//
+// process 按帧顺序设置虚拟时钟并写入价格缓存, 行情处理使用帧的接收时间;
+// 差价比率按虚拟时钟每秒计算一轮, 与回放速度无关
+// 回放写入 Redis 及数据库, 必须通过 ARB_CONFIG 使用与实盘不同的配置
+
+// 全速回放时每发送多少帧让出一次
+const YIELD_FRAMES: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    // 按录制时间间隔发送, 1 为实时
+    Rate(f64),
+    // 不等待
+    Max,
+}
+
+impl FromStr for Speed {
+    type Err = anyhow::Error;
+
+    // max, 1x, 10x, 0.5
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "max" {
+            return Ok(Speed::Max);
+        }
+        let rate: f64 = s.trim_end_matches('x').parse()?;
+        if rate.is_nan() || rate <= 0.0 {
+            return Err(anyhow!("invalid speed: {}", s));
+        }
+        Ok(Speed::Rate(rate))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    // 录制目录或单个文件
+    pub path: PathBuf,
+    pub speed: Speed,
+    // 只回放这些 feed, 空为全部
+    pub feeds: Vec<String>,
+    // 接收时间范围(ms)
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub frames: u64,
+    pub skipped: u64,
+    pub first_ts: Option<i64>,
+    pub last_ts: Option<i64>,
+}
+
+/// 回放到 price_tx, 完成或退出信号后返回
+pub async fn replay(
+    opts: &Options,
+    price_tx: UnboundedSender<PriceStream>,
+) -> anyhow::Result<Stats> {
+    let frames = Merge::open(&opts.path, &opts.feeds)?;
+    let mut stats = Stats::default();
+    let mut started: Option<(Instant, i64)> = None;
+    for frame in frames {
+        if shutdown::is_shutdown() {
+            break;
+        }
+        if opts.from.is_some_and(|t| frame.ts < t) {
+            continue;
+        }
+        if opts.to.is_some_and(|t| frame.ts >= t) {
+            break;
+        }
+        let Some((platform, market)) = feed(&frame.venue, &frame.market) else {
+            stats.skipped += 1;
+            continue;
+        };
+
+        let (start, first_ts) = *started.get_or_insert((Instant::now(), frame.ts));
+        match opts.speed {
+            Speed::Rate(rate) => {
+                let offset = (frame.ts - first_ts).max(0) as f64 / rate;
+                tokio::time::sleep_until(start + Duration::from_secs_f64(offset / 1000.0)).await;
+            }
+            Speed::Max => {
+                if stats.frames % YIELD_FRAMES == 0 {
+                    tokio::task::yield_now().await;
+                }
+            }
+        }
+
+        // 与 WebSockets::event_loop 相同的解析
+        let event = match serde_json::from_str::<WebSocketEvent>(&frame.data) {
+            Ok(event) => event,
+            Err(_) => {
+                stats.skipped += 1;
+                continue;
+            }
+        };
+        let stream = price_stream(&platform, &market, event, frame.ts);
+        if price_tx.send(stream).is_err() {
+            return Err(anyhow!("price channel closed"));
+        }
+        metrics::PRICE_BACKLOG.inc();
+        stats.frames += 1;
+        stats.first_ts.get_or_insert(frame.ts);
+        stats.last_ts = Some(frame.ts);
+    }
+    info!(frames = stats.frames, skipped = stats.skipped; "replay finished");
+    Ok(stats)
+}
+
+/// 回放前检查: 必须设置 ARB_CONFIG, 且数据库、Redis 与实盘配置不同
+pub fn ensure_isolated() -> anyhow::Result<()> {
+    let path = std::env::var("ARB_CONFIG").map_err(|_| {
+        anyhow!(
+            "ARB_CONFIG is required for replay, use a config with a separate database and redis"
+        )
+    })?;
+    let live_path = conf::default_config_path();
+    if Path::new(&live_path).exists() {
+        let live = conf::load_config(&live_path)?;
+        check_isolated(&path, stores(&conf::C), &live_path, stores(&live))?;
+    }
+    Ok(())
+}
+
+// (数据库, redis) 地址
+type Stores<'a> = (&'a str, &'a str);
+
+fn stores(c: &Config) -> Stores<'_> {
+    let database = match c.sqlite.as_ref() {
+        Some(sqlite) => sqlite.url.as_str(),
+        None => c.mysql.url.as_str(),
+    };
+    (database, c.redis.url.as_str())
+}
+
+fn check_isolated(path: &str, c: Stores, live_path: &str, live: Stores) -> anyhow::Result<()> {
+    if fs::canonicalize(path).ok() == fs::canonicalize(live_path).ok() {
+        return Err(anyhow!("ARB_CONFIG {} is the live config", path));
+    }
+    if c.0 == live.0 {
+        return Err(anyhow!("replay database is the live database: {}", c.0));
+    }
+    if c.1 == live.1 {
+        return Err(anyhow!("replay redis is the live redis: {}", c.1));
+    }
+    Ok(())
+}
+
+/// 按帧顺序处理回放行情: 设置虚拟时钟, 写入价格缓存后转发到 signal_tx,
+/// 虚拟时钟每进入新的一秒先按已写入的价格计算一轮差价比率
+pub async fn process(
+    mut price_rx: UnboundedReceiver<PriceStream>,
+    signal_tx: UnboundedSender<PriceStream>,
+) -> anyhow::Result<()> {
+    let mut redis = db::get_db()?.redis().await?;
+    let mut diff_rate_his_map = HashMap::new();
+    let mut second: Option<i64> = None;
+    while let Some(stream) = price_rx.recv().await {
+        metrics::PRICE_BACKLOG.dec();
+        let current = stream.local_time.div_euclid(1000);
+        if second.is_some_and(|s| current > s) {
+            clock::set(current * 1000);
+            diff_rate::update_diff_rates(&mut diff_rate_his_map).await;
+        }
+        second = Some(current);
+        clock::set(stream.local_time);
+        price::cache_price(&mut redis, &stream).await;
+        if signal_tx.send(stream).is_ok() {
+            metrics::PRICE_BACKLOG.inc();
+        }
+    }
+    // 最后一秒
+    if second.is_some() {
+        diff_rate::update_diff_rates(&mut diff_rate_his_map).await;
+    }
+    Ok(())
+}
+
+fn feed(venue: &str, market: &str) -> Option<(Platform, Market)> {
+    let platform = match venue {
+        "binance" => Platform::Binance,
+        "bybit" => Platform::Bybit,
+        "kucoin" => Platform::Kucoin,
+        _ => return None,
+    };
+    let market = match market {
+        "spot" => Market::Spot,
+        "futures" => Market::Futures,
+        "delivery" => Market::Delivery,
+        _ => return None,
+    };
+    Some((platform, market))
+}
+
+// 单个 feed 的文件按顺序读取, 文件末尾不完整时跳到下一个文件
+struct FeedReader {
+    files: std::vec::IntoIter<PathBuf>,
+    lines: Option<Lines<BufReader<MultiGzDecoder<fs::File>>>>,
+}
+
+impl Iterator for FeedReader {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        loop {
+            if self.lines.is_none() {
+                let path = self.files.next()?;
+                match fs::File::open(&path) {
+                    Ok(file) => {
+                        self.lines = Some(BufReader::new(MultiGzDecoder::new(file)).lines())
+                    }
+                    Err(e) => {
+                        warn!(path = path.display().to_string().as_str(), err:? = e; "open replay file err");
+                        continue;
+                    }
+                }
+            }
+            match self.lines.as_mut()?.next() {
+                Some(Ok(line)) => match serde_json::from_str::<Frame>(&line) {
+                    Ok(frame) => return Some(frame),
+                    Err(e) => warn!(err:? = e; "invalid replay frame"),
+                },
+                Some(Err(e)) => {
+                    warn!(err:? = e; "read replay file err");
+                    self.lines = None;
+                }
+                None => self.lines = None,
+            }
+        }
+    }
+}
+
+// 多个 feed 按接收时间归并, 时间相同按 feed 顺序
+struct Merge {
+    readers: Vec<FeedReader>,
+    heap: BinaryHeap<Reverse<(i64, usize)>>,
+    heads: Vec<Option<Frame>>,
+}
+
+impl Merge {
+    fn open(path: &Path, feeds: &[String]) -> anyhow::Result<Self> {
+        let mut groups: Vec<(String, Vec<PathBuf>)> = Vec::new();
+        let files = if path.is_dir() {
+            recorder::list(path, recorder::FILE_SUFFIX)?
+        } else {
+            vec![path.to_path_buf()]
+        };
+        for file in files {
+            let feed = file
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !feeds.is_empty() && !feeds.contains(&feed) {
+                continue;
+            }
+            match groups.iter_mut().find(|(f, _)| *f == feed) {
+                Some((_, group)) => group.push(file),
+                None => groups.push((feed, vec![file])),
+            }
+        }
+        if groups.is_empty() {
+            return Err(anyhow!("no recorded files in {}", path.display()));
+        }
+        let readers = groups
+            .into_iter()
+            .map(|(_, files)| FeedReader {
+                files: files.into_iter(),
+                lines: None,
+            })
+            .collect();
+        Ok(Self::new(readers))
+    }
+
+    fn new(mut readers: Vec<FeedReader>) -> Self {
+        let mut heap = BinaryHeap::new();
+        let heads = readers
+            .iter_mut()
+            .enumerate()
+            .map(|(i, r)| {
+                let head = r.next();
+                if let Some(f) = &head {
+                    heap.push(Reverse((f.ts, i)));
+                }
+                head
+            })
+            .collect();
+        Merge {
+            readers,
+            heap,
+            heads,
+        }
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let Reverse((_, i)) = self.heap.pop()?;
+        let frame = self.heads[i].take();
+        self.heads[i] = self.readers[i].next();
+        if let Some(f) = &self.heads[i] {
+            self.heap.push(Reverse((f.ts, i)));
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn test_merge() {
+        assert_eq!("max".parse::<Speed>().unwrap(), Speed::Max);
+        assert_eq!("10x".parse::<Speed>().unwrap(), Speed::Rate(10.0));
+        assert_eq!("0.5".parse::<Speed>().unwrap(), Speed::Rate(0.5));
+        assert!("0x".parse::<Speed>().is_err());
+
+        let dir = std::env::temp_dir().join(format!("replay-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let write = |feed: &str, name: &str, ts: &[i64]| {
+            let (venue, market) = feed.split_once('_').unwrap();
+            fs::create_dir_all(dir.join(feed)).unwrap();
+            let file = fs::File::create(dir.join(feed).join(name)).unwrap();
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            for t in ts {
+                let frame = Frame {
+                    venue: venue.to_string(),
+                    market: market.to_string(),
+                    ts: *t,
+                    data: "{}".to_string(),
+                };
+                writeln!(encoder, "{}", serde_json::to_string(&frame).unwrap()).unwrap();
+            }
+            encoder.finish().unwrap();
+        };
+        write("binance_spot", "1.jsonl.gz", &[1, 4]);
+        write("binance_spot", "2.jsonl.gz", &[6, 9]);
+        write("bybit_futures", "1.jsonl.gz", &[2, 4, 8]);
+
+        let ts = |feeds: &[String]| -> Vec<(i64, String)> {
+            Merge::open(&dir, feeds)
+                .unwrap()
+                .map(|f| (f.ts, f.venue))
+                .collect()
+        };
+        let merged = ts(&[]);
+        let order: Vec<i64> = merged.iter().map(|(t, _)| *t).collect();
+        assert_eq!(order, vec![1, 2, 4, 4, 6, 8, 9]);
+        assert_eq!(merged[2].1, "binance");
+        assert_eq!(ts(&["bybit_futures".to_string()]).len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_isolated() {
+        let dir = std::env::temp_dir().join(format!("replay-conf-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let live_path = dir.join("config.toml");
+        let path = dir.join("replay.toml");
+        fs::write(&live_path, "").unwrap();
+        fs::write(&path, "").unwrap();
+        let (live_path, path) = (live_path.to_str().unwrap(), path.to_str().unwrap());
+        let live = ("mysql://localhost/arb", "redis://127.0.0.1/");
+
+        let replay = ("sqlite://replay.db", "redis://127.0.0.1/1");
+        assert!(check_isolated(path, replay, live_path, live).is_ok());
+        assert!(check_isolated(live_path, replay, live_path, live).is_err());
+        assert!(
+            check_isolated(path, ("mysql://localhost/arb", replay.1), live_path, live).is_err()
+        );
+        assert!(check_isolated(path, (replay.0, "redis://127.0.0.1/"), live_path, live).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,747 @@
+use crate::{model, sql};
+use chrono::{DateTime, Local, TimeZone};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::Path;
+
+// 运营报表: 按时间范围汇总策略开平仓、盈亏、手续费、FOK 过期率、资金利用率及差价
+//
+// 盈亏取 arb_strategy_pnl 最近一次计算的策略累计值, 不是范围内的增量, 列名标注 _to_date 且不计入 summary
+// 基差均值按时间加权, 每条历史记录的比率持续到下一条记录, 范围开始时的比率取范围前最后一条
+// 资金利用率 = sum(名义价值 * 范围内持仓时长) / sum(名义价值 * 范围时长), 名义价值取 from 腿首次开仓成交
+// 最大差价取 arb_diff_rate_his 中各差价范围内的最高比率(含范围开始时的比率), 时间为 UTC
+
+const TOP_SPREADS: usize = 20;
+
+#[derive(Debug, Clone, Default)]
+pub struct StrategyRow {
+    pub id: i64,
+    pub coin: String,
+    pub option_choose: String,
+    pub from_symbol: String,
+    pub to_symbol: String,
+    pub doing_status: i8,
+    // 范围内创建 / 完成
+    pub opened: bool,
+    pub closed: bool,
+    pub notional_usdt: Decimal,
+    // 范围内持仓秒数
+    pub held_secs: i64,
+    pub pnl: Option<model::ArbStrategyPnl>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CoinRow {
+    pub coin: String,
+    pub strategies: usize,
+    pub realized_usdt: Decimal,
+    pub unrealized_usdt: Decimal,
+    pub realized_coin: Decimal,
+    pub fee_usdt: Decimal,
+    pub funding_usdt: Decimal,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OrderStats {
+    pub filled: usize,
+    pub expired: usize,
+    pub pending: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StableCoinRow {
+    pub symbol: String,
+    pub buys: usize,
+    pub sells: usize,
+    pub volume: Decimal,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BasisRow {
+    pub diff_rate_id: i64,
+    pub pair: String,
+    // 范围内比率变化次数
+    pub changes: usize,
+    pub min: Decimal,
+    pub max: Decimal,
+    // 时间加权均值
+    pub time_avg: Decimal,
+    pub last: Decimal,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpreadRow {
+    pub diff_rate_id: i64,
+    pub pair: String,
+    pub diff_rate: Decimal,
+    pub diff_price: Decimal,
+    pub annualized_rate: Option<Decimal>,
+    // 比率出现的时间(秒), 范围开始前的比率记为 start
+    pub at: i64,
+}
+
+// (名称, 表头, 行)
+type Table = (&'static str, Vec<&'static str>, Vec<Vec<String>>);
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    // [start, end) 秒
+    pub start: i64,
+    pub end: i64,
+    pub strategies: Vec<StrategyRow>,
+    pub coins: Vec<CoinRow>,
+    pub orders: OrderStats,
+    pub stable_coins: Vec<StableCoinRow>,
+    pub basis: Vec<BasisRow>,
+    pub spreads: Vec<SpreadRow>,
+}
+
+/// 汇总 [start, end) 的报表
+pub async fn build(start: i64, end: i64) -> anyhow::Result<Report> {
+    let mut report = Report {
+        start,
+        end,
+        ..Default::default()
+    };
+
+    let pnl: HashMap<i64, model::ArbStrategyPnl> = sql::get_arb_strategy_pnl_list()
+        .await?
+        .into_iter()
+        .map(|p| (p.arb_strategy_id, p))
+        .collect();
+    for s in sql::get_arb_strategy_list().await? {
+        let created = s.created.unwrap_or_default();
+        let done = s.doing_status == model::arb_strategy::DOING_STATUS_DONE;
+        let closed_at = done.then(|| s.updated.unwrap_or_default());
+        // 范围内存在过的策略
+        if created >= end || closed_at.is_some_and(|t| t < start) {
+            continue;
+        }
+        let infos = sql::get_arb_strategy_ex_info_list_by_strategy_id(s.id).await?;
+        let (notional_usdt, held_secs) = holding(&s, &infos, start, end);
+        report.strategies.push(StrategyRow {
+            id: s.id,
+            coin: s.coin.clone(),
+            option_choose: s.option_choose.clone(),
+            from_symbol: s.from_symbol.clone(),
+            to_symbol: s.to_symbol.clone(),
+            doing_status: s.doing_status,
+            opened: created >= start,
+            closed: closed_at.is_some_and(|t| t < end),
+            notional_usdt,
+            held_secs,
+            pnl: pnl.get(&s.id).cloned(),
+        });
+    }
+    report.coins = coins(&report.strategies);
+
+    for info in sql::get_arb_strategy_ex_info_list_by_created(start, end).await? {
+        if info.market == "transfer" {
+            continue;
+        }
+        match info.is_ok {
+            model::arb_strategy_ex_info::IS_OK_DONE => report.orders.filled += 1,
+            model::arb_strategy_ex_info::IS_OK_EXPIRED => report.orders.expired += 1,
+            _ => report.orders.pending += 1,
+        }
+    }
+
+    let mut stable: BTreeMap<String, StableCoinRow> = BTreeMap::new();
+    for info in sql::get_arb_stable_coin_info_list_by_created(start, end).await? {
+        let row = stable.entry(info.symbol.clone()).or_default();
+        row.symbol = info.symbol.clone();
+        match info.option_type.as_str() {
+            "buy" => row.buys += 1,
+            _ => row.sells += 1,
+        }
+        row.volume += info.price * info.amount;
+    }
+    report.stable_coins = stable.into_values().collect();
+
+    let mut pairs: HashMap<i64, String> = HashMap::new();
+    let mut before: HashMap<i64, model::ArbDiffRateHis> = HashMap::new();
+    for d in sql::get_arb_diff_rate_list().await? {
+        let pair = format!(
+            "{} {} {} -> {} {}",
+            d.option_choose, d.from_market, d.from_symbol, d.to_market, d.to_symbol
+        );
+        pairs.insert(d.id, pair);
+        if let Some(h) = sql::get_arb_diff_rate_his_before(d.id, start).await? {
+            before.insert(d.id, h);
+        }
+    }
+    let his = sql::get_arb_diff_rate_his_list_by_created(start, end).await?;
+    // 进行中的范围只统计到当前
+    let until = end.min(Local::now().timestamp());
+    let before_rate = before.iter().map(|(id, h)| (*id, h.diff_rate)).collect();
+    report.basis = basis(&his, &before_rate, &pairs, start, until);
+    report.spreads = spreads(&his, &before, &pairs, start);
+
+    Ok(report)
+}
+
+/// from 腿首次开仓成交的名义价值(USDT)及范围内持仓秒数
+fn holding(
+    s: &model::ArbStrategy,
+    infos: &[model::ArbStrategyExInfo],
+    start: i64,
+    end: i64,
+) -> (Decimal, i64) {
+    let filled: Vec<&model::ArbStrategyExInfo> = infos
+        .iter()
+        .filter(|i| i.is_ok == model::arb_strategy_ex_info::IS_OK_DONE && i.market != "transfer")
+        .collect();
+    let Some(first) = filled.iter().find(|i| i.market == s.from_market) else {
+        return (Decimal::ZERO, 0);
+    };
+    let notional = if first.market == "delivery" {
+        // 币本位为张数
+        first.executed_amt * Decimal::from(s.contract_mul)
+    } else {
+        first.executed_amt * first.price
+    };
+    let opened_at = first.created.unwrap_or_default();
+    let closed_at = match s.doing_status {
+        model::arb_strategy::DOING_STATUS_DONE => {
+            filled.last().and_then(|i| i.created).unwrap_or(end)
+        }
+        _ => end,
+    };
+    let held = closed_at.min(end) - opened_at.max(start);
+    (notional, held.max(0))
+}
+
+fn coins(strategies: &[StrategyRow]) -> Vec<CoinRow> {
+    let mut coins: BTreeMap<String, CoinRow> = BTreeMap::new();
+    for s in strategies {
+        let row = coins.entry(s.coin.clone()).or_default();
+        row.coin = s.coin.clone();
+        row.strategies += 1;
+        if let Some(p) = &s.pnl {
+            row.realized_usdt += p.realized_usdt;
+            row.unrealized_usdt += p.unrealized_usdt;
+            row.realized_coin += p.realized_coin;
+            row.fee_usdt += p.fee_usdt;
+            row.funding_usdt += p.funding_usdt;
+        }
+    }
+    coins.into_values().collect()
+}
+
+fn basis(
+    his: &[model::ArbDiffRateHis],
+    before: &HashMap<i64, Decimal>,
+    pairs: &HashMap<i64, String>,
+    start: i64,
+    until: i64,
+) -> Vec<BasisRow> {
+    let mut changes: BTreeMap<i64, Vec<(i64, Decimal)>> = BTreeMap::new();
+    for (id, rate) in before {
+        changes.entry(*id).or_default().push((start, *rate));
+    }
+    for h in his {
+        let t = h.created.unwrap_or(start).max(start);
+        changes.entry(h.diff_rate_id).or_default().push((t, h.diff_rate));
+    }
+    changes
+        .into_iter()
+        .map(|(id, mut points)| {
+            points.sort_by_key(|(t, _)| *t);
+            let mut row = BasisRow {
+                diff_rate_id: id,
+                pair: pairs.get(&id).cloned().unwrap_or_default(),
+                changes: points.len() - before.contains_key(&id) as usize,
+                min: points[0].1,
+                max: points[0].1,
+                last: points[points.len() - 1].1,
+                ..Default::default()
+            };
+            let mut weighted = Decimal::ZERO;
+            let mut secs = 0;
+            for (i, (t, rate)) in points.iter().enumerate() {
+                row.min = row.min.min(*rate);
+                row.max = row.max.max(*rate);
+                let next = points.get(i + 1).map_or(until, |(t, _)| *t).min(until);
+                let held = (next - t).max(0);
+                weighted += *rate * Decimal::from(held);
+                secs += held;
+            }
+            row.time_avg = if secs > 0 {
+                (weighted / Decimal::from(secs)).round_dp(4)
+            } else {
+                row.last
+            };
+            row
+        })
+        .collect()
+}
+
+// 各差价范围内比率最高的记录, 按比率从高到低取前 TOP_SPREADS 个
+fn spreads(
+    his: &[model::ArbDiffRateHis],
+    before: &HashMap<i64, model::ArbDiffRateHis>,
+    pairs: &HashMap<i64, String>,
+    start: i64,
+) -> Vec<SpreadRow> {
+    let mut top: HashMap<i64, SpreadRow> = HashMap::new();
+    let points = before
+        .values()
+        .map(|h| (h, start))
+        .chain(his.iter().map(|h| (h, h.created.unwrap_or(start).max(start))));
+    for (h, at) in points {
+        let row = SpreadRow {
+            diff_rate_id: h.diff_rate_id,
+            pair: pairs.get(&h.diff_rate_id).cloned().unwrap_or_default(),
+            diff_rate: h.diff_rate,
+            diff_price: h.diff_price,
+            annualized_rate: h.annualized_rate,
+            at,
+        };
+        match top.get(&h.diff_rate_id) {
+            Some(r) if r.diff_rate >= row.diff_rate => {}
+            _ => {
+                top.insert(h.diff_rate_id, row);
+            }
+        }
+    }
+    let mut rows: Vec<SpreadRow> = top.into_values().collect();
+    rows.sort_by(|a, b| {
+        b.diff_rate
+            .cmp(&a.diff_rate)
+            .then(a.diff_rate_id.cmp(&b.diff_rate_id))
+    });
+    rows.truncate(TOP_SPREADS);
+    rows
+}
+
+impl Report {
+    /// FOK 过期率, 已成交及已过期订单中过期的比例
+    pub fn expiry_rate(&self) -> Decimal {
+        let total = self.orders.filled + self.orders.expired;
+        if total == 0 {
+            return Decimal::ZERO;
+        }
+        (Decimal::from(self.orders.expired) / Decimal::from(total)).round_dp(4)
+    }
+
+    /// 资金利用率及平均占用资金(USDT)
+    pub fn utilization(&self) -> (Decimal, Decimal) {
+        let range = Decimal::from((self.end - self.start).max(1));
+        let used: Decimal = self
+            .strategies
+            .iter()
+            .map(|s| s.notional_usdt * Decimal::from(s.held_secs))
+            .sum();
+        let capital: Decimal = self.strategies.iter().map(|s| s.notional_usdt).sum();
+        if capital.is_zero() {
+            return (Decimal::ZERO, Decimal::ZERO);
+        }
+        (
+            (used / (capital * range)).round_dp(4),
+            (used / range).round_dp(4),
+        )
+    }
+
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let (utilization, deployed) = self.utilization();
+        vec![
+            ("start", time(self.start)),
+            ("end", time(self.end)),
+            ("strategies_active", self.strategies.len().to_string()),
+            (
+                "strategies_opened",
+                self.strategies
+                    .iter()
+                    .filter(|s| s.opened)
+                    .count()
+                    .to_string(),
+            ),
+            (
+                "strategies_closed",
+                self.strategies
+                    .iter()
+                    .filter(|s| s.closed)
+                    .count()
+                    .to_string(),
+            ),
+            ("orders_filled", self.orders.filled.to_string()),
+            ("orders_expired", self.orders.expired.to_string()),
+            ("orders_pending", self.orders.pending.to_string()),
+            ("fok_expiry_rate", self.expiry_rate().to_string()),
+            ("capital_utilization", utilization.to_string()),
+            ("avg_deployed_usdt", deployed.to_string()),
+            (
+                "stable_coin_orders",
+                self.stable_coins
+                    .iter()
+                    .map(|s| s.buys + s.sells)
+                    .sum::<usize>()
+                    .to_string(),
+            ),
+        ]
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let pnl = |s: &StrategyRow, f: fn(&model::ArbStrategyPnl) -> Decimal| {
+            s.pnl.as_ref().map(|p| f(p).to_string()).unwrap_or_default()
+        };
+        vec![
+            (
+                "summary",
+                vec!["key", "value"],
+                self.summary()
+                    .into_iter()
+                    .map(|(k, v)| vec![k.to_string(), v])
+                    .collect(),
+            ),
+            (
+                "strategies",
+                vec![
+                    "id",
+                    "coin",
+                    "option_choose",
+                    "from_symbol",
+                    "to_symbol",
+                    "doing_status",
+                    "opened",
+                    "closed",
+                    "notional_usdt",
+                    "held_secs",
+                    "realized_usdt_to_date",
+                    "unrealized_usdt_to_date",
+                    "realized_coin_to_date",
+                    "fee_usdt_to_date",
+                    "funding_usdt_to_date",
+                    "pnl_updated",
+                ],
+                self.strategies
+                    .iter()
+                    .map(|s| {
+                        vec![
+                            s.id.to_string(),
+                            s.coin.clone(),
+                            s.option_choose.clone(),
+                            s.from_symbol.clone(),
+                            s.to_symbol.clone(),
+                            s.doing_status.to_string(),
+                            s.opened.to_string(),
+                            s.closed.to_string(),
+                            s.notional_usdt.round_dp(4).to_string(),
+                            s.held_secs.to_string(),
+                            pnl(s, |p| p.realized_usdt),
+                            pnl(s, |p| p.unrealized_usdt),
+                            pnl(s, |p| p.realized_coin),
+                            pnl(s, |p| p.fee_usdt),
+                            pnl(s, |p| p.funding_usdt),
+                            s.pnl
+                                .as_ref()
+                                .and_then(|p| p.updated)
+                                .map(time)
+                                .unwrap_or_default(),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (
+                "coins",
+                vec![
+                    "coin",
+                    "strategies",
+                    "realized_usdt_to_date",
+                    "unrealized_usdt_to_date",
+                    "realized_coin_to_date",
+                    "fee_usdt_to_date",
+                    "funding_usdt_to_date",
+                ],
+                self.coins
+                    .iter()
+                    .map(|c| {
+                        vec![
+                            c.coin.clone(),
+                            c.strategies.to_string(),
+                            c.realized_usdt.to_string(),
+                            c.unrealized_usdt.to_string(),
+                            c.realized_coin.to_string(),
+                            c.fee_usdt.to_string(),
+                            c.funding_usdt.to_string(),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (
+                "stable_coins",
+                vec!["symbol", "buys", "sells", "volume"],
+                self.stable_coins
+                    .iter()
+                    .map(|s| {
+                        vec![
+                            s.symbol.clone(),
+                            s.buys.to_string(),
+                            s.sells.to_string(),
+                            s.volume.round_dp(4).to_string(),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (
+                "basis",
+                vec![
+                    "diff_rate_id",
+                    "pair",
+                    "changes",
+                    "min",
+                    "max",
+                    "time_avg",
+                    "last",
+                ],
+                self.basis
+                    .iter()
+                    .map(|b| {
+                        vec![
+                            b.diff_rate_id.to_string(),
+                            b.pair.clone(),
+                            b.changes.to_string(),
+                            b.min.to_string(),
+                            b.max.to_string(),
+                            b.time_avg.to_string(),
+                            b.last.to_string(),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (
+                "spreads",
+                vec![
+                    "diff_rate_id",
+                    "pair",
+                    "diff_rate",
+                    "diff_price",
+                    "annualized_rate",
+                    "at_utc",
+                ],
+                self.spreads
+                    .iter()
+                    .map(|s| {
+                        vec![
+                            s.diff_rate_id.to_string(),
+                            s.pair.clone(),
+                            s.diff_rate.to_string(),
+                            s.diff_price.to_string(),
+                            s.annualized_rate.map(|r| r.to_string()).unwrap_or_default(),
+                            DateTime::from_timestamp(s.at, 0)
+                                .unwrap_or_default()
+                                .to_rfc3339(),
+                        ]
+                    })
+                    .collect(),
+            ),
+        ]
+    }
+
+    /// 每个表一个 csv 及 index.html, 返回写入的文件
+    pub fn write(&self, dir: &Path) -> anyhow::Result<Vec<String>> {
+        std::fs::create_dir_all(dir)?;
+        let mut files = Vec::new();
+        for (name, header, rows) in self.tables() {
+            let path = dir.join(format!("{}.csv", name));
+            let mut content = csv_line(header.iter().map(|h| h.to_string()));
+            for row in rows {
+                content.push_str(&csv_line(row));
+            }
+            std::fs::write(&path, content)?;
+            files.push(path.display().to_string());
+        }
+        let path = dir.join("index.html");
+        std::fs::write(&path, self.html())?;
+        files.push(path.display().to_string());
+        Ok(files)
+    }
+
+    pub fn html(&self) -> String {
+        let title = format!("Arbitrage report {} - {}", time(self.start), time(self.end));
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\
+             body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse;margin-bottom:2em}}\
+             th,td{{border:1px solid #ccc;padding:4px 8px;text-align:right}}th{{background:#f4f4f4}}\
+             </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            escape(&title),
+            escape(&title)
+        );
+        for (name, header, rows) in self.tables() {
+            let _ = write!(html, "<h2>{}</h2>\n<table>\n<tr>", name);
+            for h in header {
+                let _ = write!(html, "<th>{}</th>", escape(h));
+            }
+            html.push_str("</tr>\n");
+            for row in rows {
+                html.push_str("<tr>");
+                for v in row {
+                    let _ = write!(html, "<td>{}</td>", escape(&v));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn time(t: i64) -> String {
+    Local
+        .timestamp_opt(t, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+fn csv_line(fields: impl IntoIterator<Item = String>) -> String {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|f| {
+            if f.contains([',', '"', '\n']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f
+            }
+        })
+        .collect();
+    fields.join(",") + "\n"
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let s = model::ArbStrategy {
+            id: 1,
+            coin: "BTC".to_string(),
+            from_market: "spot".to_string(),
+            doing_status: model::arb_strategy::DOING_STATUS_DONE,
+            ..Default::default()
+        };
+        let info = |market: &str, price: i64, created: i64| model::ArbStrategyExInfo {
+            market: market.to_string(),
+            price: Decimal::from(price),
+            executed_amt: Decimal::ONE,
+            is_ok: model::arb_strategy_ex_info::IS_OK_DONE,
+            created: Some(created),
+            ..Default::default()
+        };
+        // 范围 [0, 100), 50 开仓 150 平仓
+        let infos = [
+            info("spot", 1000, 50),
+            info("transfer", 0, 60),
+            info("spot", 1100, 150),
+        ];
+        assert_eq!(holding(&s, &infos, 0, 100), (Decimal::from(1000), 50));
+
+        let report = Report {
+            start: 0,
+            end: 100,
+            strategies: vec![StrategyRow {
+                id: 1,
+                coin: "BTC,X".to_string(),
+                notional_usdt: Decimal::from(1000),
+                held_secs: 50,
+                ..Default::default()
+            }],
+            orders: OrderStats {
+                filled: 3,
+                expired: 1,
+                pending: 0,
+            },
+            ..Default::default()
+        };
+        assert_eq!(report.expiry_rate(), Decimal::new(25, 2));
+        assert_eq!(
+            report.utilization(),
+            (Decimal::new(5, 1), Decimal::from(500))
+        );
+        assert_eq!(
+            csv_line(["a".to_string(), "b,\"c\"".to_string()]),
+            "a,\"b,\"\"c\"\"\"\n"
+        );
+        assert!(report.html().contains("<td>1</td><td>BTC,X</td>"));
+        // 累计盈亏不计入范围汇总
+        assert!(report
+            .summary()
+            .iter()
+            .all(|(k, _)| !k.contains("usdt") || *k == "avg_deployed_usdt"));
+    }
+
+    #[test]
+    fn test_basis_time_weighted() {
+        let his = |id: i64, rate: i64, created: i64| model::ArbDiffRateHis {
+            diff_rate_id: id,
+            diff_rate: Decimal::new(rate, 3),
+            created: Some(created),
+            ..Default::default()
+        };
+        // 范围 [0, 100), 开始前为 0.001, 10 变为 0.010, 20 回到 0.001
+        let before = HashMap::from([(1, Decimal::new(1, 3)), (2, Decimal::new(5, 3))]);
+        let rows = basis(
+            &[his(1, 10, 10), his(1, 1, 20)],
+            &before,
+            &HashMap::new(),
+            0,
+            100,
+        );
+        assert_eq!(rows[0].changes, 2);
+        assert_eq!(rows[0].min, Decimal::new(1, 3));
+        assert_eq!(rows[0].max, Decimal::new(10, 3));
+        // (0.001 * 90 + 0.010 * 10) / 100, 按记录条数平均为 0.004
+        assert_eq!(rows[0].time_avg, Decimal::new(19, 4));
+        assert_eq!(rows[0].last, Decimal::new(1, 3));
+        // 范围内无变化
+        assert_eq!(rows[1].changes, 0);
+        assert_eq!(rows[1].time_avg, Decimal::new(5, 3));
+    }
+
+    #[test]
+    fn test_spreads() {
+        let his = |id: i64, rate: i64, created: i64| model::ArbDiffRateHis {
+            diff_rate_id: id,
+            diff_rate: Decimal::new(rate, 3),
+            created: Some(created),
+            ..Default::default()
+        };
+        // 范围 [100, 200), 1 在范围内升到 0.010, 2 范围内无变化, 3 只有范围前的记录更高
+        let before = HashMap::from([(2, his(2, 5, 50)), (3, his(3, 20, 90))]);
+        let rows = spreads(
+            &[his(1, 3, 110), his(1, 10, 120), his(1, 2, 130), his(3, 1, 150)],
+            &before,
+            &HashMap::new(),
+            100,
+        );
+        let top: Vec<(i64, Decimal, i64)> = rows
+            .iter()
+            .map(|r| (r.diff_rate_id, r.diff_rate, r.at))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                (3, Decimal::new(20, 3), 100),
+                (1, Decimal::new(10, 3), 120),
+                (2, Decimal::new(5, 3), 100),
+            ]
+        );
+        let report = Report {
+            spreads: rows,
+            ..Default::default()
+        };
+        assert!(report.html().contains("1970-01-01T00:02:00+00:00"));
+    }
+}
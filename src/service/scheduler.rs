@@ -0,0 +1,371 @@
+use crate::conf::C;
+use crate::helper::shutdown;
+use crate::service::{binance_strategy, stable_coin_hedging};
+use crate::{model, sql};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use log::{error, info};
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Mutex;
+
+// 策略调度: 每个策略同一时间最多排队或执行一次, 执行期间的唤醒合并为执行完成后再执行一次
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Job {
+    // arb_strategy 期现套利
+    Strategy(i64),
+    // arb_stable_coin 稳定币对冲
+    StableCoin(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Queued,
+    Running,
+    // 执行期间又被唤醒
+    Dirty,
+    // 人工操作占用, 期间的唤醒忽略
+    Held,
+}
+
+static SLOTS: Lazy<DashMap<Job, Slot>> = Lazy::new(DashMap::new);
+// diff_rate_id -> 运行中的策略, 执行时维护
+static BY_DIFF_RATE: Lazy<DashMap<i64, HashSet<i64>>> = Lazy::new(DashMap::new);
+static QUEUE: OnceCell<UnboundedSender<Job>> = OnceCell::new();
+
+/// 启动 worker, 数量由 [scheduler] workers 配置
+pub fn start() {
+    let (tx, rx) = unbounded_channel::<Job>();
+    if QUEUE.set(tx).is_err() {
+        return;
+    }
+    let rx = Arc::new(Mutex::new(rx));
+    let workers = C.scheduler.workers.max(1);
+    for _ in 0..workers {
+        let rx = rx.clone();
+        let token = shutdown::token();
+        // 退出时不再取新任务, 正在执行的步骤执行完毕
+        shutdown::spawn(async move {
+            loop {
+                let job = select! {
+                    biased;
+                    _ = token.cancelled() => break,
+                    job = async { rx.lock().await.recv().await } => match job {
+                        Some(job) => job,
+                        None => break,
+                    },
+                };
+                run(job).await;
+            }
+        });
+    }
+    info!(workers = workers; "strategy scheduler started");
+}
+
+/// 唤醒策略, 已在队列中则忽略, 执行中则执行完成后再执行一次
+pub fn wake(strategy_id: i64) {
+    wake_job(Job::Strategy(strategy_id));
+}
+
+/// 唤醒稳定币对冲策略
+pub fn wake_stable_coin(stable_coin_id: i64) {
+    wake_job(Job::StableCoin(stable_coin_id));
+}
+
+fn wake_job(job: Job) {
+    enqueue(&SLOTS, QUEUE.get(), job);
+}
+
+// 未启动或已退出时不排队
+fn enqueue<K: Eq + Hash + Copy>(
+    slots: &DashMap<K, Slot>,
+    queue: Option<&UnboundedSender<K>>,
+    key: K,
+) {
+    if !transit(slots, key) {
+        return;
+    }
+    let sent = queue.map(|tx| tx.send(key).is_ok()).unwrap_or(false);
+    if !sent {
+        slots.remove(&key);
+    }
+}
+
+/// 延迟唤醒, 用于下单后检查订单状态
+pub fn wake_after(strategy_id: i64, delay: Duration) {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        wake(strategy_id);
+    });
+}
+
+/// 差价变化时唤醒关联策略
+pub fn wake_diff_rate(diff_rate_id: i64) {
+    for id in tracked(&BY_DIFF_RATE, diff_rate_id) {
+        wake(id);
+    }
+}
+
+fn tracked(index: &DashMap<i64, HashSet<i64>>, diff_rate_id: i64) -> Vec<i64> {
+    index
+        .get(&diff_rate_id)
+        .map(|ids| ids.iter().copied().collect())
+        .unwrap_or_default()
+}
+
+/// 占用策略执行槽位, 等待正在排队或执行的任务结束, 释放前调度器不再执行该策略
+pub(crate) async fn hold_strategy(strategy_id: i64) -> Hold {
+    let job = Job::Strategy(strategy_id);
+    while !hold(&SLOTS, job) {
+        tokio::time::sleep(Duration::from_millis(HOLD_CHECK_MS)).await;
+    }
+    Hold(job)
+}
+
+const HOLD_CHECK_MS: u64 = 100;
+
+/// 策略执行槽位占用, drop 时释放
+pub(crate) struct Hold(Job);
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        SLOTS.remove_if(&self.0, |_, s| *s == Slot::Held);
+    }
+}
+
+/// 定时唤醒全部运行中策略, 启动后首次唤醒同时恢复 diff_rate 索引
+pub async fn schedule_strategy() {
+    let token = shutdown::token();
+    let interval = Duration::from_millis(C.scheduler.interval_ms);
+    loop {
+        wake_running_strategies().await;
+        select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+async fn wake_running_strategies() {
+    match sql::get_arb_strategy_list_by_doing_status(model::arb_strategy::DOING_STATUS_RUN).await
+    {
+        Ok(strategy_list) => {
+            for s in strategy_list {
+                wake(s.id);
+            }
+        }
+        Err(e) => {
+            error!(err:? = e; "get running strategy err");
+        }
+    }
+}
+
+/// 定时唤醒运行中的稳定币对冲策略, 指标由K线 websocket 实时更新
+pub async fn schedule_stable_coin() {
+    let token = shutdown::token();
+    let interval = Duration::from_millis(C.scheduler.interval_ms);
+    loop {
+        match sql::get_arb_stable_coin_list_by_doing_status(
+            model::arb_stable_coin::DOING_STATUS_RUN,
+        )
+        .await
+        {
+            Ok(list) => {
+                for s in list {
+                    wake_stable_coin(s.id);
+                }
+            }
+            Err(e) => {
+                error!(err:? = e; "get running stable coin err");
+            }
+        }
+        select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+async fn run(job: Job) {
+    SLOTS.insert(job, Slot::Running);
+    loop {
+        match job {
+            Job::Strategy(id) => run_strategy(id).await,
+            Job::StableCoin(id) => run_stable_coin(id).await,
+        }
+        if finish(&SLOTS, job) || shutdown::is_shutdown() {
+            SLOTS.remove(&job);
+            return;
+        }
+    }
+}
+
+async fn run_strategy(strategy_id: i64) {
+    // 每次执行读取最新状态, 已停止的策略不再执行
+    match sql::get_arb_strategy_by_id(strategy_id).await {
+        Ok(strategy) if strategy.doing_status == model::arb_strategy::DOING_STATUS_RUN => {
+            track(&BY_DIFF_RATE, strategy.diff_rate_id, strategy_id, true);
+            binance_strategy::run_strategy(strategy).await;
+        }
+        Ok(strategy) => {
+            track(&BY_DIFF_RATE, strategy.diff_rate_id, strategy_id, false);
+        }
+        Err(e) => {
+            error!(strategy_id = strategy_id, err:? = e; "get strategy err");
+        }
+    }
+}
+
+async fn run_stable_coin(stable_coin_id: i64) {
+    match sql::get_arb_stable_coin_by_id(stable_coin_id).await {
+        Ok(stable_coin)
+            if stable_coin.doing_status == model::arb_stable_coin::DOING_STATUS_RUN =>
+        {
+            stable_coin_hedging::run_stable_coin(stable_coin).await;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!(stable_coin_id = stable_coin_id, err:? = e; "get stable coin err");
+        }
+    }
+}
+
+// 维护 diff_rate 索引, 运行中加入, 停止或完成后移除; 策略更换 diff_rate 时从旧索引移除
+fn track(index: &DashMap<i64, HashSet<i64>>, diff_rate_id: i64, strategy_id: i64, running: bool) {
+    index.retain(|k, ids| {
+        if *k != diff_rate_id || !running {
+            ids.remove(&strategy_id);
+        }
+        !ids.is_empty()
+    });
+    if running {
+        index.entry(diff_rate_id).or_default().insert(strategy_id);
+    }
+}
+
+// 返回是否需要放入队列
+fn transit<K: Eq + Hash + Copy>(slots: &DashMap<K, Slot>, key: K) -> bool {
+    match slots.entry(key) {
+        Entry::Vacant(e) => {
+            e.insert(Slot::Queued);
+            true
+        }
+        Entry::Occupied(mut e) => {
+            if *e.get() == Slot::Running {
+                e.insert(Slot::Dirty);
+            }
+            false
+        }
+    }
+}
+
+// 空闲时占用槽位, 排队或执行中返回 false
+fn hold<K: Eq + Hash + Copy>(slots: &DashMap<K, Slot>, key: K) -> bool {
+    match slots.entry(key) {
+        Entry::Vacant(e) => {
+            e.insert(Slot::Held);
+            true
+        }
+        Entry::Occupied(_) => false,
+    }
+}
+
+// 执行完成, 返回 false 表示执行期间被唤醒需要再执行一次
+fn finish<K: Eq + Hash + Copy>(slots: &DashMap<K, Slot>, key: K) -> bool {
+    if slots.remove_if(&key, |_, s| *s != Slot::Dirty).is_some() {
+        return true;
+    }
+    slots.insert(key, Slot::Running);
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wake_coalesce() {
+        let slots = DashMap::new();
+        assert!(transit(&slots, 1));
+        // 排队中重复唤醒
+        assert!(!transit(&slots, 1));
+
+        slots.insert(1, Slot::Running);
+        assert!(!transit(&slots, 1));
+        assert!(!transit(&slots, 1));
+        assert_eq!(*slots.get(&1).unwrap(), Slot::Dirty);
+
+        // 执行期间被唤醒, 再执行一次后结束
+        assert!(!finish(&slots, 1));
+        assert!(finish(&slots, 1));
+        assert!(slots.get(&1).is_none());
+        assert!(transit(&slots, 1));
+
+        // 排队中不能占用, 占用期间唤醒不排队
+        assert!(!hold(&slots, 1));
+        slots.remove(&1);
+        assert!(hold(&slots, 1));
+        assert!(!transit(&slots, 1));
+        assert_eq!(*slots.get(&1).unwrap(), Slot::Held);
+        assert!(!hold(&slots, 1));
+    }
+
+    #[test]
+    fn test_track() {
+        let index = DashMap::new();
+        track(&index, 1, 10, true);
+        track(&index, 1, 11, true);
+        track(&index, 2, 12, true);
+        // 更换 diff_rate
+        track(&index, 2, 11, true);
+        assert_eq!(*index.get(&1).unwrap(), HashSet::from([10]));
+        assert_eq!(*index.get(&2).unwrap(), HashSet::from([11, 12]));
+        track(&index, 1, 10, false);
+        assert!(index.get(&1).is_none());
+    }
+
+    #[test]
+    fn test_wake_diff_rate() {
+        // 使用独立的索引、槽位及队列, 不受其他测试唤醒的影响
+        let index = DashMap::new();
+        let slots = DashMap::new();
+        let (tx, mut rx) = unbounded_channel::<Job>();
+        let wake_diff_rate = |diff_rate_id: i64| {
+            for id in tracked(&index, diff_rate_id) {
+                enqueue(&slots, Some(&tx), Job::Strategy(id));
+            }
+        };
+        track(&index, 1, 10, true);
+        track(&index, 1, 11, true);
+        track(&index, 2, 12, true);
+
+        wake_diff_rate(1);
+        let mut woken = vec![rx.try_recv().unwrap(), rx.try_recv().unwrap()];
+        woken.sort_by_key(|job| match job {
+            Job::Strategy(id) | Job::StableCoin(id) => *id,
+        });
+        assert_eq!(woken, vec![Job::Strategy(10), Job::Strategy(11)]);
+        assert!(rx.try_recv().is_err());
+        // 排队中不重复入队
+        wake_diff_rate(1);
+        assert!(rx.try_recv().is_err());
+
+        // 执行完成, 其中一个已停止
+        for job in woken {
+            assert!(finish(&slots, job));
+        }
+        track(&index, 1, 11, false);
+        wake_diff_rate(1);
+        assert_eq!(rx.try_recv().unwrap(), Job::Strategy(10));
+        assert!(rx.try_recv().is_err());
+
+        // 未启动时不占用槽位
+        enqueue(&slots, None, Job::Strategy(12));
+        assert!(slots.get(&Job::Strategy(12)).is_none());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::helper::clock;
+use crate::{model, sql};
+use anyhow::anyhow;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+
+// 期现套利开平仓信号, 模板按 open_trigger 将信号值与 option_open/option_close 比较
+//
+// threshold: 当前差价比率
+// annualized: 年化差价比率, 见 basis
+// zscore: 最新一条 arb_diff_rate_sample 相对其前 lookback 条采样的 z-score, 采样不足或过期时不触发
+
+// z-score 保留位数
+const ZSCORE_SCALE: u32 = 4;
+// 逐秒采样, lookback 上限为 1 小时
+pub const MAX_LOOKBACK: i64 = 3600;
+// 最新采样超过该秒数视为行情中断
+const SAMPLE_MAX_AGE_SECS: i64 = 5;
+
+/// 策略的信号值, None 表示数据不足不执行
+pub async fn value(
+    s: &model::ArbStrategy,
+    info: &model::ArbDiffRateInfo,
+) -> anyhow::Result<Option<Decimal>> {
+    match s.signal_mode.as_str() {
+        "" | model::arb_strategy::SIGNAL_MODE_THRESHOLD => Ok(Some(info.diff_rate)),
+        model::arb_strategy::SIGNAL_MODE_ANNUALIZED => match info.annualized_rate {
+            Some(rate) => Ok(Some(rate)),
+            None => Err(anyhow!(
+                "no annualized rate for diff_rate_id: {}",
+                s.diff_rate_id
+            )),
+        },
+        model::arb_strategy::SIGNAL_MODE_ZSCORE => {
+            // 重启后直接从采样表恢复窗口, 无需重新预热
+            let lookback = s.lookback.clamp(2, MAX_LOOKBACK);
+            let samples =
+                sql::get_arb_diff_rate_sample_list(s.diff_rate_id, lookback as u32 + 1).await?;
+            let Some((current, window)) = samples.split_first() else {
+                return Ok(None);
+            };
+            if clock::now().timestamp() - current.created > SAMPLE_MAX_AGE_SECS {
+                return Ok(None);
+            }
+            let rates: Vec<Decimal> = window.iter().map(|h| h.diff_rate).collect();
+            Ok(zscore(&rates, lookback as usize, current.diff_rate))
+        }
+        mode => Err(anyhow!("unknown signal_mode: {}", mode)),
+    }
+}
+
+/// 校验信号配置
+pub fn validate(s: &model::ArbStrategy) -> Vec<String> {
+    let mut errs = Vec::new();
+    match s.signal_mode.as_str() {
+        ""
+        | model::arb_strategy::SIGNAL_MODE_THRESHOLD
+        | model::arb_strategy::SIGNAL_MODE_ANNUALIZED => {}
+        model::arb_strategy::SIGNAL_MODE_ZSCORE => {
+            if s.lookback < 2 || s.lookback > MAX_LOOKBACK {
+                errs.push(format!("zscore requires 2 <= lookback <= {}", MAX_LOOKBACK));
+            }
+        }
+        mode => errs.push(format!("unknown signal_mode: {}", mode)),
+    }
+    errs
+}
+
+/// history 不足 lookback 条或标准差为 0 时返回 None
+pub fn zscore(history: &[Decimal], lookback: usize, current: Decimal) -> Option<Decimal> {
+    if lookback < 2 || history.len() < lookback {
+        return None;
+    }
+    let values: Vec<f64> = history[..lookback]
+        .iter()
+        .map(|v| v.to_f64())
+        .collect::<Option<_>>()?;
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let sd = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if sd == 0.0 {
+        return None;
+    }
+    let mut z = Decimal::from_f64((current.to_f64()? - mean) / sd)?;
+    z.rescale(ZSCORE_SCALE);
+    Some(z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zscore() {
+        let history: Vec<Decimal> = [1, 2, 3, 2, 1, 2, 3, 2]
+            .iter()
+            .map(|v| Decimal::new(*v, 3))
+            .collect();
+        // 预热中
+        assert_eq!(zscore(&history[..4], 8, Decimal::new(2, 3)), None);
+        // 均值 0.002, 标准差 sqrt(0.5) * 0.001
+        assert_eq!(zscore(&history, 8, Decimal::new(2, 3)), Some(Decimal::ZERO));
+        let z = zscore(&history, 8, Decimal::new(4, 3)).unwrap();
+        assert_eq!(z, Decimal::new(28284, 4));
+        // 只取最近 lookback 条
+        assert_eq!(zscore(&history, 4, Decimal::new(2, 3)), Some(Decimal::ZERO));
+        assert_eq!(zscore(&[Decimal::ONE; 5], 5, Decimal::ONE), None);
+    }
+
+    #[test]
+    fn test_validate_lookback() {
+        let mut s = model::ArbStrategy {
+            signal_mode: model::arb_strategy::SIGNAL_MODE_ZSCORE.to_string(),
+            lookback: MAX_LOOKBACK,
+            ..Default::default()
+        };
+        assert!(validate(&s).is_empty());
+        s.lookback = MAX_LOOKBACK + 1;
+        assert_eq!(validate(&s).len(), 1);
+        s.lookback = 1;
+        assert_eq!(validate(&s).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_zscore_from_samples() {
+        crate::db::init_test_env().await.unwrap();
+        let diff_rate_id = 42_001;
+        let s = model::ArbStrategy {
+            diff_rate_id,
+            signal_mode: model::arb_strategy::SIGNAL_MODE_ZSCORE.to_string(),
+            lookback: 4,
+            ..Default::default()
+        };
+        let info = model::ArbDiffRateInfo::default();
+        let now = clock::now().timestamp();
+        // 窗口内全精度采样, 截断到 3 位时全部相同
+        let rates = [10_000, 10_002, 10_000, 10_002, 10_003];
+        for (i, r) in rates.iter().enumerate() {
+            let created = now - (rates.len() - 1 - i) as i64;
+            sql::insert_arb_diff_rate_sample(
+                model::ArbDiffRateSample {
+                    id: 0,
+                    diff_rate_id,
+                    diff_rate: Decimal::new(*r, 7),
+                    created,
+                },
+                now - 2 * MAX_LOOKBACK,
+            )
+            .await
+            .unwrap();
+        }
+        // 最新 0.0010003 相对均值 0.0010001, 标准差 0.0000001
+        assert_eq!(value(&s, &info).await.unwrap(), Some(Decimal::new(20_000, 4)));
+
+        // 超出保留时间的采样被清理
+        sql::insert_arb_diff_rate_sample(
+            model::ArbDiffRateSample {
+                id: 0,
+                diff_rate_id,
+                diff_rate: Decimal::new(10_003, 7),
+                created: now + 1,
+            },
+            now - 1,
+        )
+        .await
+        .unwrap();
+        let samples = sql::get_arb_diff_rate_sample_list(diff_rate_id, 100).await.unwrap();
+        assert_eq!(samples.len(), 3);
+    }
+}
@@ -1,158 +1,89 @@
 use crate::binance::api::OrderRequest;
-use crate::binance::rest_model::{KlineSummaries, KlineSummary, OrderSide, OrderType, TimeInForce};
-use crate::binance::MyApi;
-use crate::{db, model, sql};
+use crate::binance::rest_model::{KlineSummary, OrderSide, OrderType, TimeInForce};
+use crate::binance::{account, MyApi};
+use crate::helper::metrics;
+use crate::service::{indicator, kline};
+use crate::{model, sql};
 use anyhow::anyhow;
 use chrono::Local;
 use log::{error, info};
-use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
-use rust_decimal::Decimal;
-use std::collections::HashMap;
+use rust_decimal::prelude::ToPrimitive;
 use std::ops::{Add, Sub};
-use ta::indicators::BollingerBands;
-use ta::Next;
-use tokio::select;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-pub async fn event_stable_coin_start(rxs: HashMap<i64, UnboundedReceiver<model::ArbStableCoin>>) {
-    for (_, mut rx) in rxs {
-        let api = MyApi::new();
-        tokio::spawn(async move {
-            loop {
-                select! {
-                    Some(stable_coin) = rx.recv() => {
-                        match stable_coin.strategy.as_str() {
-                            // boll 15m
-                            "11" => {
-                                if let Err(e) = boll(api.clone(), stable_coin).await {
-                                    error!("boll err: {:?}", e);
-                                }
-                            },
-                            // 百分比
-                            "21" => {
-                                if let Err(e) = percentage(api.clone(), stable_coin).await {
-                                    error!("percentage err: {:?}", e);
-                                }
-                            },
-                            // 固定阈值
-                            "31" => {
-                                if let Err(e) = fixed_threshold(api.clone(), stable_coin).await {
-                                    error!("fixed threshold err: {:?}", e);
-                                }
-                            },
-                            _ => {
+// 取最近 1000 条K线计算指标
+const SIGNAL_KLINES: usize = 1000;
 
-                            }
-                        }
-                    }
-                }
-            }
-        });
-    }
-}
-
-async fn boll(api: MyApi, stable: model::ArbStableCoin) -> anyhow::Result<()> {
-    // 初始化K线数据
-    let kline_key = stable.symbol.clone() + "_15m";
-    let kline_interval = "15m";
-
-    let mut klines: Vec<KlineSummary> = Vec::new();
-    if let Some(en) = db::get_db()?.rocksdb().get(kline_key.clone())? {
-        klines = bincode::deserialize(&en[..])?;
+/// 执行稳定币对冲策略的一个步骤, 由 scheduler 保证同一策略不会并发执行
+pub(crate) async fn run_stable_coin(stable_coin: model::ArbStableCoin) {
+    // 按策略所属账户获取 api
+    let api = match account::get_api(stable_coin.user_id) {
+        Ok(api) => api,
+        Err(e) => {
+            error!(stable_coin_id = stable_coin.id, user_id = stable_coin.user_id, err:? = e; "get api err");
+            return;
+        }
+    };
+    let (stable_coin_id, user_id) = (stable_coin.id, stable_coin.user_id);
+    // signal_config 或 strategy 预设的指标信号
+    let config = match indicator::config(&stable_coin) {
+        Ok(config) => config,
+        Err(e) => {
+            error!(stable_coin_id = stable_coin_id, user_id = user_id, err:? = e; "signal config err");
+            return;
+        }
+    };
+    if let Some(config) = config {
+        if let Err(e) = signal(api, stable_coin, config).await {
+            error!(stable_coin_id = stable_coin_id, user_id = user_id, err:? = e; "signal err");
+        }
+        return;
     }
-
-    if klines.len() == 0 {
-        match api
-            .get_klines(stable.symbol.clone(), kline_interval, 1000, None, None)
-            .await
-        {
-            Ok(KlineSummaries::AllKlineSummaries(new_klines)) => {
-                let encode = bincode::serialize(&new_klines)?;
-                let _ = db::get_db()?.rocksdb().put(kline_key.clone(), encode)?;
-            }
-            Err(e) => {
-                return Err(anyhow!(
-                    "get {:?} klines error, : {:?}",
-                    kline_key.clone(),
-                    e.to_string()
-                ));
+    match stable_coin.strategy.as_str() {
+        // 百分比
+        "21" => {
+            if let Err(e) = percentage(api, stable_coin).await {
+                error!(stable_coin_id = stable_coin_id, user_id = user_id, err:? = e; "percentage err");
             }
         }
-    } else {
-        // 增量K线
-        if let Some(last) = klines.last() {
-            match api
-                .get_klines(
-                    stable.symbol.clone(),
-                    kline_interval,
-                    5,
-                    Some(last.open_time as u64),
-                    None,
-                )
-                .await
-            {
-                Ok(KlineSummaries::AllKlineSummaries(new_klines)) => {
-                    if new_klines.len() == 1 {
-                        let mut k1: Vec<KlineSummary> = klines.clone();
-                        k1.remove(klines.len() - 1);
-                        k1.push(new_klines[0].clone());
-                        let encode = bincode::serialize(&k1)?;
-                        db::get_db()?.rocksdb().put(kline_key.clone(), encode)?;
-                    } else {
-                        let mut k2: Vec<KlineSummary> = klines.clone();
-                        for (i, kline) in new_klines.iter().enumerate() {
-                            if i == 0 {
-                                k2.remove(klines.len() - 1);
-                                k2.push(kline.clone());
-                            } else {
-                                k2.remove(0);
-                                k2.push(kline.clone());
-                            }
-                        }
-                        let encode = bincode::serialize(&k2)?;
-                        db::get_db()?.rocksdb().put(kline_key.clone(), encode)?;
-                    }
-                }
-                Err(e) => {
-                    return Err(anyhow!(
-                        "get last {:?} klines error, : {:?}",
-                        kline_key.clone(),
-                        e.to_string()
-                    ));
-                }
+        // 固定阈值
+        "31" => {
+            if let Err(e) = fixed_threshold(api, stable_coin).await {
+                error!(stable_coin_id = stable_coin_id, user_id = user_id, err:? = e; "fixed threshold err");
             }
         }
+        _ => {}
     }
+}
 
-    // 计算Boll
-    let mut bb = BollingerBands::new(20, 2.0_f64)?;
-    // let mut average = 0.0;
-    let mut upper = 0.0;
-    let mut lower = 0.0;
-    for k in klines.clone() {
-        let out = bb.next(k.close);
-        // average = out.average;
-        upper = out.upper;
-        lower = out.lower;
-    }
-
-    let mut upp = Decimal::from_f64(upper).ok_or(anyhow!("decimal from f64 upp"))?;
-    upp.rescale(stable.price_truncate as u32);
-    /*let mut avg = Decimal::from_f64(average).ok_or(anyhow!(""))?;
-    avg.rescale(stable.price_truncate as u32);*/
-    let mut low = Decimal::from_f64(lower).ok_or(anyhow!("decimal from f64 low"))?;
-    low.rescale(stable.price_truncate as u32);
+async fn signal(
+    api: MyApi,
+    stable: model::ArbStableCoin,
+    config: indicator::SignalConfig,
+) -> anyhow::Result<()> {
+    // K线由 websocket 维护, 首次使用时订阅
+    let key = kline::KlineKey::new("spot", &stable.symbol, &config.interval);
+    kline::watch(&key).await?;
+    let klines = kline::latest(&key, SIGNAL_KLINES)?;
+    signal_step(api, stable, &config, &klines).await
+}
 
-    let price = klines.last().ok_or(anyhow!("last price"))?.close;
-    let mut last_price = Decimal::from_f64(price).ok_or(anyhow!("decimal from f64 price"))?;
-    last_price.rescale(stable.price_truncate as u32);
+// 按K线计算指标, 满足规则时下单
+async fn signal_step(
+    api: MyApi,
+    stable: model::ArbStableCoin,
+    config: &indicator::SignalConfig,
+    klines: &[KlineSummary],
+) -> anyhow::Result<()> {
+    // 计算指标
+    let values = config.evaluate(&stable, klines)?;
+    let last_price = values["close"];
 
-    // 策略，price < 1 && price <= low buy -> price >= upp sell
+    // 满足 entry 买入 -> 满足 exit 卖出, 如 boll: price < 1 && price <= low buy -> price >= upp sell
     // 获取stable_coin_info 表最后1条数据状态
     let info_list = sql::get_arb_stable_coin_info_list_by_stable_coin_id(stable.id, 1).await?;
     // 表为空或者上一条记录为sell
     if info_list.is_empty() || info_list[0].option_type == "sell" {
-        if last_price.lt(&Decimal::from(1)) && last_price.le(&low) {
+        if indicator::matches(&config.entry, &values)? {
             let mut price = last_price.add(stable.fok_diff);
             price.rescale(stable.price_truncate as u32);
             let mut amount = stable.option_amt;
@@ -168,6 +99,7 @@ async fn boll(api: MyApi, stable: model::ArbStableCoin) -> anyhow::Result<()> {
                     ..OrderRequest::default()
                 })
                 .await?;
+            metrics::order("buy", "placed");
             let last_id = sql::insert_arb_stable_coin_info(model::ArbStableCoinInfo {
                 id: 0,
                 stable_coin_id: stable.id,
@@ -188,16 +120,19 @@ async fn boll(api: MyApi, stable: model::ArbStableCoin) -> anyhow::Result<()> {
             .await?;
 
             info!(
-                "--------------- boll, up: {:?}, dn: {:?}, current price: {:?}",
-                upp, low, price
-            );
-            info!(
-                "buy, amount: {:?}, orderId: {:?}, info table lastInsertId: {:?}",
-                stable.option_amt, tran.order_id, last_id
+                stable_coin_id = stable.id,
+                symbol = tran.symbol.as_str(),
+                option_type = "buy",
+                values:? = values,
+                price:% = price,
+                amount:% = amount,
+                order_id = tran.order_id,
+                info_id = last_id;
+                "signal place order"
             );
         }
     } else if info_list[0].option_type == "buy" {
-        if last_price.ge(&upp) {
+        if indicator::matches(&config.exit, &values)? {
             let mut price = last_price.sub(stable.fok_diff);
             price.rescale(stable.price_truncate as u32);
             let mut amount = info_list[0].amount;
@@ -213,6 +148,7 @@ async fn boll(api: MyApi, stable: model::ArbStableCoin) -> anyhow::Result<()> {
                     ..OrderRequest::default()
                 })
                 .await?;
+            metrics::order("sell", "placed");
             let last_id = sql::insert_arb_stable_coin_info(model::ArbStableCoinInfo {
                 id: 0,
                 stable_coin_id: stable.id,
@@ -233,25 +169,19 @@ async fn boll(api: MyApi, stable: model::ArbStableCoin) -> anyhow::Result<()> {
             .await?;
 
             info!(
-                "--------------- boll, up: {:?}, dn: {:?}, current price: {:?}",
-                upp, low, price
-            );
-            info!(
-                "sell, amount: {:?}, orderId: {:?}, info table lastInsertId: {:?}",
-                info_list[0].amount, tran.order_id, last_id
+                stable_coin_id = stable.id,
+                symbol = tran.symbol.as_str(),
+                option_type = "sell",
+                values:? = values,
+                price:% = price,
+                amount:% = amount,
+                order_id = tran.order_id,
+                info_id = last_id;
+                "signal place order"
             );
         }
     }
 
-    /*println!("{:?}", info_list);
-    println!(
-        "up: {:?} dn: {:?} count: {:?} price: {:?}",
-        upp,
-        low,
-        klines.len(),
-        last_price
-    );*/
-
     Ok(())
 }
 
@@ -267,28 +197,94 @@ async fn fixed_threshold(api: MyApi, stable: model::ArbStableCoin) -> anyhow::Re
     Ok(())
 }
 
-pub async fn inspect_stable_coin(txs: HashMap<i64, UnboundedSender<model::ArbStableCoin>>) {
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::mock::{MockServer, Script};
+    use rust_decimal::Decimal;
 
-        match sql::get_arb_stable_coin_list_by_doing_status(
-            model::arb_stable_coin::DOING_STATUS_RUN,
-        )
-        .await
-        {
-            Ok(strategy_list) => {
-                for s in strategy_list {
-                    let sharding = s.id % 10;
-                    if let Some(tx) = txs.get(&sharding) {
-                        if let Err(e) = tx.send(s.clone()) {
-                            error!("send error: {:?}", e.to_string());
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                error!("{:?}", e);
-            }
+    fn kline(close: f64) -> KlineSummary {
+        KlineSummary {
+            open: close,
+            high: close + 0.001,
+            low: close - 0.001,
+            close,
+            ..Default::default()
         }
     }
+
+    // 价格在 0.9990/1.0010 之间稳定后收于 last
+    fn klines(last: f64) -> Vec<KlineSummary> {
+        let mut klines: Vec<KlineSummary> = (0..30)
+            .map(|i| kline(if i % 2 == 0 { 0.9990 } else { 1.0010 }))
+            .collect();
+        klines.push(kline(last));
+        klines
+    }
+
+    #[tokio::test]
+    async fn test_boll_signal_on_mock() {
+        db::init_test_env().await.unwrap();
+        let server = MockServer::start(Script::default(), "127.0.0.1:0", "127.0.0.1:0")
+            .await
+            .unwrap();
+        let id = sql::insert_arb_stable_coin(model::ArbStableCoin {
+            user_id: 1,
+            platform: "binance".to_string(),
+            coin: "USDC".to_string(),
+            market: "spot".to_string(),
+            symbol: "USDCUSDT".to_string(),
+            price_truncate: 4,
+            amt_truncate: 0,
+            strategy: "11".to_string(),
+            option_amt: Decimal::from(100),
+            fok_diff: Decimal::new(1, 4),
+            doing_status: model::arb_stable_coin::DOING_STATUS_RUN,
+            ..Default::default()
+        })
+        .await
+        .unwrap() as i64;
+        let stable = sql::get_arb_stable_coin_by_id(id).await.unwrap();
+        let config = indicator::config(&stable).unwrap().unwrap();
+
+        // 未跌破下轨不下单
+        signal_step(server.api(), stable.clone(), &config, &klines(1.0000))
+            .await
+            .unwrap();
+        assert!(server.orders().is_empty());
+
+        // 跌破下轨买入
+        signal_step(server.api(), stable.clone(), &config, &klines(0.9950))
+            .await
+            .unwrap();
+        let orders = server.orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].symbol, "USDCUSDT");
+        assert_eq!(orders[0].side, "BUY");
+        assert_eq!(orders[0].time_in_force, "FOK");
+        assert_eq!(orders[0].price, 0.9951);
+        assert_eq!(orders[0].qty, 100.0);
+
+        // 持仓时再次跌破下轨不重复买入, 突破上轨卖出
+        signal_step(server.api(), stable.clone(), &config, &klines(0.9950))
+            .await
+            .unwrap();
+        assert_eq!(server.orders().len(), 1);
+        signal_step(server.api(), stable.clone(), &config, &klines(1.0050))
+            .await
+            .unwrap();
+        let orders = server.orders();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[1].side, "SELL");
+        assert_eq!(orders[1].price, 1.0049);
+        assert_eq!(orders[1].qty, 100.0);
+
+        let infos = sql::get_arb_stable_coin_info_list_by_stable_coin_id(id, 10)
+            .await
+            .unwrap();
+        let types: Vec<&str> = infos.iter().map(|i| i.option_type.as_str()).collect();
+        assert_eq!(types, vec!["sell", "buy"]);
+        assert_eq!(infos[1].order_id, orders[0].order_id.to_string());
+    }
 }
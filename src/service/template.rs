@@ -0,0 +1,297 @@
+use crate::binance::rest_model::{OrderSide, OrderType, UniversalTransferType};
+use crate::conf::C;
+use crate::model;
+use anyhow::anyhow;
+use once_cell::sync::OnceCell;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Deserialize;
+use std::ops::{Div, Mul, Sub};
+
+// 策略模板: 描述多腿策略的执行顺序, 每个 leg 的市场/方向/数量来源及所属开平仓阶段
+
+const BUILTIN_TEMPLATES: &str = include_str!("templates.toml");
+
+static TEMPLATES: OnceCell<Vec<Template>> = OnceCell::new();
+
+#[derive(Debug, Clone, Deserialize)]
+struct TemplateFile {
+    templates: Vec<Template>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub option_choose: String,
+    // 不配置则不限制
+    pub from_market: Option<String>,
+    pub to_market: Option<String>,
+    pub open_trigger: Trigger,
+    pub legs: Vec<LegTemplate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    // 差价比率 >= option_open 开仓, <= option_close 平仓
+    Above,
+    // 差价比率 <= option_open 开仓, >= option_close 平仓
+    Below,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegMarket {
+    From,
+    To,
+    Transfer,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegTemplate {
+    pub option_type: String,
+    pub phase: Phase,
+    pub market: LegMarket,
+    pub side: Option<OrderSide>,
+    #[serde(default = "default_order_type")]
+    pub order_type: OrderType,
+    pub transfer: Option<UniversalTransferType>,
+    pub quantity: Quantity,
+}
+
+fn default_order_type() -> OrderType {
+    OrderType::Limit
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Planned,
+    Executed,
+    ExchangeBase,
+    Contracts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fee {
+    Spot,
+    Futures,
+    Delivery,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Quantity {
+    pub source: Source,
+    // 数量来源的 leg 序号, 只能引用之前的 leg
+    pub leg: Option<usize>,
+    pub fee: Option<Fee>,
+    pub truncate: Option<LegMarket>,
+}
+
+/// 加载模板, 配置 templates 时使用配置文件, 否则使用内置模板
+pub fn templates() -> anyhow::Result<&'static [Template]> {
+    let list = TEMPLATES.get_or_try_init(|| match C.templates.as_ref() {
+        Some(path) => parse(&std::fs::read_to_string(path)?),
+        None => builtin(),
+    })?;
+    Ok(list.as_slice())
+}
+
+/// 内置模板
+pub fn builtin() -> anyhow::Result<Vec<Template>> {
+    parse(BUILTIN_TEMPLATES)
+}
+
+/// 按 option_choose 及 from/to 市场匹配模板
+pub fn find(s: &model::ArbStrategy) -> anyhow::Result<&'static Template> {
+    templates()?.iter().find(|t| t.matches(s)).ok_or(anyhow!(
+        "no template for option_choose: {}, from_market: {}, to_market: {}",
+        s.option_choose,
+        s.from_market,
+        s.to_market
+    ))
+}
+
+pub fn parse(content: &str) -> anyhow::Result<Vec<Template>> {
+    let file: TemplateFile = toml::from_str(content)?;
+    for t in file.templates.iter() {
+        t.validate()?;
+    }
+    Ok(file.templates)
+}
+
+/// 下一个执行的 leg, 之前的 leg 必须全部完成, 全部完成返回 None
+pub fn next_leg(done: &[bool]) -> anyhow::Result<Option<usize>> {
+    let count = done.iter().filter(|d| **d).count();
+    if count == done.len() {
+        return Ok(None);
+    }
+    if done[..count].iter().any(|d| !d) {
+        return Err(anyhow!(
+            "done count err, count: {}, legs: {:?}",
+            count,
+            done
+        ));
+    }
+    Ok(Some(count))
+}
+
+impl Template {
+    fn matches(&self, s: &model::ArbStrategy) -> bool {
+        self.option_choose == s.option_choose
+            && self
+                .from_market
+                .as_ref()
+                .map_or(true, |m| *m == s.from_market)
+            && self.to_market.as_ref().map_or(true, |m| *m == s.to_market)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for (i, leg) in self.legs.iter().enumerate() {
+            let err = |msg: &str| {
+                anyhow!(
+                    "template {} leg {} {}: {}",
+                    self.name,
+                    i,
+                    leg.option_type,
+                    msg
+                )
+            };
+            match leg.market {
+                LegMarket::Transfer if leg.transfer.is_none() => {
+                    return Err(err("transfer required"))
+                }
+                LegMarket::From | LegMarket::To if leg.side.is_none() => {
+                    return Err(err("side required"))
+                }
+                _ => {}
+            }
+            match leg.quantity.source {
+                Source::Executed | Source::ExchangeBase if leg.quantity.leg.is_none() => {
+                    return Err(err("quantity leg required"))
+                }
+                Source::Contracts if leg.market == LegMarket::Transfer => {
+                    return Err(err("contracts on transfer"))
+                }
+                _ => {}
+            }
+            if leg.quantity.leg.map_or(false, |l| l >= i) {
+                return Err(err("quantity leg must be an earlier leg"));
+            }
+            if leg.quantity.truncate == Some(LegMarket::Transfer) {
+                return Err(err("truncate must be from/to"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前差价是否满足该阶段的执行条件
+    pub fn triggered(&self, phase: Phase, diff_rate: Decimal, s: &model::ArbStrategy) -> bool {
+        let (above, threshold) = match phase {
+            Phase::Open => (self.open_trigger == Trigger::Above, s.option_open),
+            Phase::Close => (self.open_trigger == Trigger::Below, s.option_close),
+        };
+        if above {
+            diff_rate >= threshold
+        } else {
+            diff_rate <= threshold
+        }
+    }
+}
+
+impl LegTemplate {
+    /// 生成 arb_strategy_ex 的 market/symbol
+    pub fn ex_market_symbol(&self, s: &model::ArbStrategy) -> (String, String) {
+        match self.market {
+            LegMarket::From => (s.from_market.clone(), s.from_symbol.clone()),
+            LegMarket::To => (s.to_market.clone(), s.to_symbol.clone()),
+            LegMarket::Transfer => ("transfer".to_string(), s.coin.clone()),
+        }
+    }
+
+    /// 扣除手续费, 换算张数并截取精度, base 为数量来源的值, price 为本 leg 市场价格
+    pub fn amount(&self, s: &model::ArbStrategy, base: Decimal, price: Decimal) -> Decimal {
+        let q = &self.quantity;
+        let mut amount = match q.fee {
+            Some(Fee::Spot) => base.mul(Decimal::ONE.sub(s.spot_fee)),
+            Some(Fee::Futures) => base.mul(Decimal::ONE.sub(s.futures_fee)),
+            Some(Fee::Delivery) => base.mul(Decimal::ONE.sub(s.delivery_fee)),
+            None => base,
+        };
+        if q.source == Source::Contracts {
+            // 计算可开张数
+            let cont = amount.mul(price).div(Decimal::from(s.contract_mul));
+            amount = cont.ceil().sub(Decimal::ONE);
+        }
+        // 向下截取, 四舍五入可能超出可用数量
+        let dp = match q.truncate {
+            Some(LegMarket::From) => s.from_amt_truncate as u32,
+            Some(LegMarket::To) => s.to_amt_truncate as u32,
+            _ => return amount,
+        };
+        amount = amount.round_dp_with_strategy(dp, RoundingStrategy::ToZero);
+        amount.rescale(dp);
+        amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_templates() {
+        let list = builtin().unwrap();
+        let s = model::ArbStrategy {
+            option_choose: "reverse".to_string(),
+            from_market: "delivery".to_string(),
+            to_market: "delivery".to_string(),
+            option_open: Decimal::new(-5, 2),
+            option_close: Decimal::ZERO,
+            ..Default::default()
+        };
+        let t = list.iter().find(|t| t.matches(&s)).unwrap();
+        assert_eq!(t.name, "reverse_coinm");
+        assert!(t.triggered(Phase::Open, Decimal::new(-6, 2), &s));
+        assert!(!t.triggered(Phase::Close, Decimal::new(-6, 2), &s));
+
+        let positive = list.iter().find(|t| t.name == "positive").unwrap();
+        assert_eq!(positive.legs.len(), 6);
+        assert_eq!(
+            positive.legs[1].transfer,
+            Some(UniversalTransferType::MainCmfuture)
+        );
+    }
+
+    #[test]
+    fn test_next_leg() {
+        assert_eq!(next_leg(&[false, false]).unwrap(), Some(0));
+        assert_eq!(next_leg(&[true, false]).unwrap(), Some(1));
+        assert_eq!(next_leg(&[true, true]).unwrap(), None);
+        assert!(next_leg(&[false, true]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        let content = r#"
+            [[templates]]
+            name = "x"
+            option_choose = "positive"
+            open_trigger = "above"
+            [[templates.legs]]
+            option_type = "spot_buy"
+            phase = "open"
+            market = "from"
+            side = "BUY"
+            quantity = { source = "executed", leg = 0 }
+        "#;
+        assert!(parse(content).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::binance::account;
+use crate::binance::rest_model::{Filters, SymbolInfo};
+use crate::binance::MyApi;
+use crate::service::{basis, indicator, signal};
+use crate::{model, sql};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use std::ops::Mul;
+
+/// 校验期现套利策略配置及交易所交易规则, 返回不通过的原因, 为空表示通过
+pub async fn validate_strategy(s: &model::ArbStrategy) -> anyhow::Result<Vec<String>> {
+    let mut errs = Vec::new();
+    match s.option_choose.as_str() {
+        "positive" => {
+            if s.from_market != "spot" || s.to_market != "delivery" {
+                errs.push("positive requires from_market spot and to_market delivery".to_string());
+            }
+            if s.option_open <= s.option_close {
+                errs.push("positive requires option_open > option_close".to_string());
+            }
+        }
+        "reverse" => {
+            if s.from_market != s.to_market
+                || (s.from_market != "futures" && s.from_market != "delivery")
+            {
+                errs.push("reverse requires both markets futures or both delivery".to_string());
+            }
+            if s.option_open >= s.option_close {
+                errs.push("reverse requires option_open < option_close".to_string());
+            }
+        }
+        _ => errs.push(format!("unknown option_choose: {}", s.option_choose)),
+    }
+    if s.option_amt <= Decimal::ZERO {
+        errs.push("option_amt must be > 0".to_string());
+    }
+    if s.margin_mul < 1 {
+        errs.push("margin_mul must be >= 1".to_string());
+    }
+    if (s.from_market == "delivery" || s.to_market == "delivery") && s.contract_mul <= 0 {
+        errs.push("contract_mul must be > 0 for delivery".to_string());
+    }
+    if s.fok_diff < Decimal::ZERO {
+        errs.push("fok_diff must be >= 0".to_string());
+    }
+    errs.extend(signal::validate(s));
+    if s.signal_mode == model::arb_strategy::SIGNAL_MODE_ANNUALIZED {
+        match basis::pair_delivery_time(&s.from_market, &s.from_symbol, &s.to_market, &s.to_symbol)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => errs.push("annualized requires a delivery contract".to_string()),
+            Err(e) => errs.push(format!("get delivery time err: {}", e)),
+        }
+    }
+
+    match sql::get_arb_diff_rate_by_id(s.diff_rate_id).await {
+        Ok(d) => {
+            if d.option_choose != s.option_choose
+                || d.from_symbol != s.from_symbol
+                || d.to_symbol != s.to_symbol
+            {
+                errs.push(format!(
+                    "arb_diff_rate {} does not match option_choose/from_symbol/to_symbol",
+                    s.diff_rate_id
+                ));
+            }
+        }
+        Err(_) => errs.push(format!("arb_diff_rate {} not found", s.diff_rate_id)),
+    }
+
+    // 交易所交易规则
+    let api = match account::get_api(s.user_id) {
+        Ok(api) => api,
+        Err(e) => {
+            errs.push(e.to_string());
+            return Ok(errs);
+        }
+    };
+    let price_info = sql::get_arb_diff_rate_info_by_diff_rate_id(s.diff_rate_id).await.ok();
+    let amount = s.option_amt.mul(Decimal::from(s.margin_mul));
+    let legs = [
+        (
+            &s.from_market,
+            &s.from_symbol,
+            s.from_price_truncate,
+            s.from_amt_truncate,
+            price_info.as_ref().map(|p| p.from_price),
+        ),
+        (
+            &s.to_market,
+            &s.to_symbol,
+            s.to_price_truncate,
+            s.to_amt_truncate,
+            price_info.as_ref().map(|p| p.to_price),
+        ),
+    ];
+    for (market, symbol, price_truncate, amt_truncate, price) in legs {
+        // 币本位数量为合约张数, 运行时按价格计算, 这里只校验精度
+        let qty = (market != "delivery").then_some(amount);
+        errs.extend(
+            check_symbol(&api, market, symbol, price_truncate, amt_truncate, qty, price).await,
+        );
+    }
+
+    Ok(errs)
+}
+
+/// 校验稳定币对冲策略配置及交易所交易规则
+pub async fn validate_stable_coin(s: &model::ArbStableCoin) -> anyhow::Result<Vec<String>> {
+    let mut errs = Vec::new();
+    if !["11", "21", "31"].contains(&s.strategy.as_str()) {
+        errs.push(format!("unknown strategy: {}", s.strategy));
+    }
+    if let Err(e) = indicator::config(s) {
+        errs.push(format!("invalid signal_config: {}", e));
+    }
+    if s.market != "spot" {
+        errs.push("stable coin hedging only supports spot".to_string());
+    }
+    if s.option_amt <= Decimal::ZERO {
+        errs.push("option_amt must be > 0".to_string());
+    }
+
+    let api = match account::get_api(s.user_id) {
+        Ok(api) => api,
+        Err(e) => {
+            errs.push(e.to_string());
+            return Ok(errs);
+        }
+    };
+    errs.extend(
+        check_symbol(
+            &api,
+            &s.market,
+            &s.symbol,
+            s.price_truncate,
+            s.amt_truncate,
+            Some(s.option_amt),
+            // 稳定币价格按 1 估算名义价值
+            Some(Decimal::ONE),
+        )
+        .await,
+    );
+
+    Ok(errs)
+}
+
+async fn check_symbol(
+    api: &MyApi,
+    market: &str,
+    symbol: &str,
+    price_truncate: i8,
+    amt_truncate: i8,
+    qty: Option<Decimal>,
+    price: Option<Decimal>,
+) -> Vec<String> {
+    match api.get_symbol_info(market, symbol).await {
+        Ok(info) => check_filters(&info, price_truncate, amt_truncate, qty, price),
+        Err(e) => vec![format!("{} {} exchange info err: {}", market, symbol, e)],
+    }
+}
+
+pub fn check_filters(
+    info: &SymbolInfo,
+    price_truncate: i8,
+    amt_truncate: i8,
+    qty: Option<Decimal>,
+    price: Option<Decimal>,
+) -> Vec<String> {
+    let mut errs = Vec::new();
+    if !info.is_trading() {
+        errs.push(format!("{} is not trading", info.symbol));
+    }
+
+    for filter in &info.filters {
+        match filter {
+            Filters::PriceFilter { tick_size, .. } => {
+                let scale = decimal_places(*tick_size);
+                if price_truncate as u32 > scale {
+                    errs.push(format!(
+                        "{} price_truncate {} finer than tick size {}",
+                        info.symbol, price_truncate, tick_size
+                    ));
+                }
+            }
+            Filters::LotSize {
+                min_qty, step_size, ..
+            } => {
+                let scale = decimal_places(*step_size);
+                if amt_truncate as u32 > scale {
+                    errs.push(format!(
+                        "{} amt_truncate {} finer than step size {}",
+                        info.symbol, amt_truncate, step_size
+                    ));
+                }
+                if let (Some(q), Some(min)) = (qty, Decimal::from_f64(*min_qty)) {
+                    if q < min {
+                        errs.push(format!("{} amount {} below min qty {}", info.symbol, q, min));
+                    }
+                }
+            }
+            Filters::MinNotional {
+                min_notional,
+                notional,
+            } => errs.extend(check_notional(info, qty, price, min_notional.or(*notional))),
+            Filters::Notional { min_notional } => {
+                errs.extend(check_notional(info, qty, price, *min_notional))
+            }
+            Filters::Others => {}
+        }
+    }
+
+    errs
+}
+
+fn check_notional(
+    info: &SymbolInfo,
+    qty: Option<Decimal>,
+    price: Option<Decimal>,
+    min_notional: Option<f64>,
+) -> Option<String> {
+    let (q, p, min) = (qty?, price?, Decimal::from_f64(min_notional?)?);
+    (q.mul(p) < min).then(|| {
+        format!(
+            "{} notional {} below min notional {}",
+            info.symbol,
+            q.mul(p),
+            min
+        )
+    })
+}
+
+fn decimal_places(v: f64) -> u32 {
+    Decimal::from_f64(v).map(|d| d.normalize().scale()).unwrap_or(0)
+}
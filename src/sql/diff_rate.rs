@@ -1,40 +1,129 @@
 use crate::{db, model};
-use chrono::Local;
+use crate::helper::clock;
 use rust_decimal::Decimal;
 
 pub async fn get_arb_diff_rate_list_by_diff_status(
     diff_status: i8,
 ) -> anyhow::Result<Vec<model::ArbDiffRate>> {
-    let diff_rate_list = sqlx::query_as::<_, model::ArbDiffRate>(
+    let diff_rate_list = db::query_as::<model::ArbDiffRate>(
         "select * from arb_diff_rate where diff_status = ?",
     )
     .bind(diff_status)
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(diff_rate_list)
 }
 
 pub async fn insert_arb_diff_rate_his(his: model::ArbDiffRateHis) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query("insert into arb_diff_rate_his (diff_rate_id, diff_price, diff_rate, created, updated) values (?, ?, ?, ?, ?)")
+    let last_insert_id = db::query("insert into arb_diff_rate_his (diff_rate_id, diff_price, diff_rate, annualized_rate, created, updated) values (?, ?, ?, ?, ?, ?)")
         .bind(his.diff_rate_id)
         .bind(his.diff_price)
         .bind(his.diff_rate)
+        .bind(his.annualized_rate)
         .bind(his.created)
         .bind(his.updated)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .last_insert_id();
     Ok(last_insert_id)
 }
 
+/// 最近 limit 条差价比率历史, 按 id 倒序
+pub async fn get_arb_diff_rate_his_list_by_diff_rate_id(
+    diff_rate_id: i64,
+    limit: u32,
+) -> anyhow::Result<Vec<model::ArbDiffRateHis>> {
+    let his_list = db::query_as::<model::ArbDiffRateHis>(
+        "select * from arb_diff_rate_his where diff_rate_id = ? order by id desc limit ?",
+    )
+    .bind(diff_rate_id)
+    .bind(limit)
+    .fetch_all()
+    .await?;
+    Ok(his_list)
+}
+
+/// created 早于 t 的最后一条差价比率历史
+pub async fn get_arb_diff_rate_his_before(
+    diff_rate_id: i64,
+    t: i64,
+) -> anyhow::Result<Option<model::ArbDiffRateHis>> {
+    let his = db::query_as::<model::ArbDiffRateHis>(
+        "select * from arb_diff_rate_his where diff_rate_id = ? and created < ? order by created desc, id desc limit 1",
+    )
+    .bind(diff_rate_id)
+    .bind(t)
+    .fetch_all()
+    .await?;
+    Ok(his.into_iter().next())
+}
+
+/// created 在 [start, end) 内的差价比率历史
+pub async fn get_arb_diff_rate_his_list_by_created(
+    start: i64,
+    end: i64,
+) -> anyhow::Result<Vec<model::ArbDiffRateHis>> {
+    let his_list = db::query_as::<model::ArbDiffRateHis>(
+        "select * from arb_diff_rate_his where created >= ? and created < ? order by id",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all()
+    .await?;
+    Ok(his_list)
+}
+
+/// 写入一条差价比率采样, 并删除 created 早于 keep_from 的旧采样
+pub async fn insert_arb_diff_rate_sample(
+    sample: model::ArbDiffRateSample,
+    keep_from: i64,
+) -> anyhow::Result<()> {
+    let mut tx = db::begin().await?;
+    db::query("insert into arb_diff_rate_sample (diff_rate_id, diff_rate, created) values (?, ?, ?)")
+        .bind(sample.diff_rate_id)
+        .bind(sample.diff_rate)
+        .bind(sample.created)
+        .execute_in(&mut tx)
+        .await?;
+    db::query("delete from arb_diff_rate_sample where diff_rate_id = ? and created < ?")
+        .bind(sample.diff_rate_id)
+        .bind(keep_from)
+        .execute_in(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 最近 limit 条差价比率采样, 按时间倒序
+pub async fn get_arb_diff_rate_sample_list(
+    diff_rate_id: i64,
+    limit: u32,
+) -> anyhow::Result<Vec<model::ArbDiffRateSample>> {
+    let list = db::query_as::<model::ArbDiffRateSample>(
+        "select * from arb_diff_rate_sample where diff_rate_id = ? order by created desc, id desc limit ?",
+    )
+    .bind(diff_rate_id)
+    .bind(limit)
+    .fetch_all()
+    .await?;
+    Ok(list)
+}
+
+pub async fn get_arb_diff_rate_list() -> anyhow::Result<Vec<model::ArbDiffRate>> {
+    let diff_rate_list = db::query_as::<model::ArbDiffRate>("select * from arb_diff_rate")
+        .fetch_all()
+        .await?;
+    Ok(diff_rate_list)
+}
+
 pub async fn get_arb_diff_rate_info_by_diff_rate_id(
     diff_rate_id: i64,
 ) -> anyhow::Result<model::ArbDiffRateInfo> {
-    let diff_rate_info = sqlx::query_as::<_, model::ArbDiffRateInfo>(
+    let diff_rate_info = db::query_as::<model::ArbDiffRateInfo>(
         "select * from arb_diff_rate_info where diff_rate_id = ?",
     )
     .bind(diff_rate_id)
-    .fetch_one(db::get_db()?.database())
+    .fetch_one()
     .await?;
     Ok(diff_rate_info)
 }
@@ -42,11 +131,11 @@ pub async fn get_arb_diff_rate_info_by_diff_rate_id(
 pub async fn get_arb_strategy_ex_list_by_strategy_id(
     strategy_id: i64,
 ) -> anyhow::Result<Vec<model::ArbStrategyEx>> {
-    let ex_list = sqlx::query_as::<_, model::ArbStrategyEx>(
+    let ex_list = db::query_as::<model::ArbStrategyEx>(
         "select * from arb_strategy_ex where arb_strategy_id = ?",
     )
     .bind(strategy_id)
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(ex_list)
 }
@@ -57,22 +146,26 @@ pub async fn update_arb_diff_rate_info_by_id(
     to_price: Decimal,
     diff_price: Decimal,
     diff_rate: Decimal,
+    annualized_rate: Option<Decimal>,
+    delivery_time: Option<i64>,
 ) -> anyhow::Result<u64> {
-    let rows = sqlx::query("update arb_diff_rate_info set from_price = ?, to_price = ?, diff_price = ?, diff_rate = ?, updated = ? where id = ?")
+    let rows = db::query("update arb_diff_rate_info set from_price = ?, to_price = ?, diff_price = ?, diff_rate = ?, annualized_rate = ?, delivery_time = ?, updated = ? where id = ?")
         .bind(from_price)
         .bind(to_price)
         .bind(diff_price)
         .bind(diff_rate)
-        .bind(Local::now().timestamp())
+        .bind(annualized_rate)
+        .bind(delivery_time)
+        .bind(clock::now().timestamp())
         .bind(id)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .rows_affected();
     Ok(rows)
 }
 
 pub async fn insert_arb_diff_rate_info(info: model::ArbDiffRateInfo) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query(
+    let last_insert_id = db::query(
         "insert into arb_diff_rate_info (
         diff_rate_id,
         platform,
@@ -86,9 +179,11 @@ pub async fn insert_arb_diff_rate_info(info: model::ArbDiffRateInfo) -> anyhow::
         to_price,
         diff_price,
         diff_rate,
+        annualized_rate,
+        delivery_time,
         created,
         updated
-        ) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        ) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
     )
     .bind(info.diff_rate_id)
     .bind(info.platform)
@@ -102,10 +197,29 @@ pub async fn insert_arb_diff_rate_info(info: model::ArbDiffRateInfo) -> anyhow::
     .bind(info.to_price)
     .bind(info.diff_price)
     .bind(info.diff_rate)
+    .bind(info.annualized_rate)
+    .bind(info.delivery_time)
     .bind(info.created)
     .bind(info.updated)
-    .execute(db::get_db()?.database())
+    .execute()
     .await?
     .last_insert_id();
     Ok(last_insert_id)
 }
+
+pub async fn get_arb_diff_rate_by_id(id: i64) -> anyhow::Result<model::ArbDiffRate> {
+    let diff_rate =
+        db::query_as::<model::ArbDiffRate>("select * from arb_diff_rate where id = ?")
+            .bind(id)
+            .fetch_one()
+            .await?;
+    Ok(diff_rate)
+}
+
+pub async fn get_arb_diff_rate_info_list() -> anyhow::Result<Vec<model::ArbDiffRateInfo>> {
+    let info_list =
+        db::query_as::<model::ArbDiffRateInfo>("select * from arb_diff_rate_info")
+            .fetch_all()
+            .await?;
+    Ok(info_list)
+}
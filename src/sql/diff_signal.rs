@@ -3,10 +3,10 @@ use chrono::Local;
 use rust_decimal::Decimal;
 
 pub async fn get_all_arb_coin_prices() -> anyhow::Result<Vec<model::ArbCoinPrice>> {
-    let diff_rate_list = sqlx::query_as::<_, model::ArbCoinPrice>(
+    let diff_rate_list = db::query_as::<model::ArbCoinPrice>(
         "select * from arb_coin_price",
     )
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(diff_rate_list)
 }
@@ -14,10 +14,10 @@ pub async fn get_all_arb_coin_prices() -> anyhow::Result<Vec<model::ArbCoinPrice
 pub async fn get_arb_coin_price_by_symbol(
     symbol: &String,
 ) -> anyhow::Result<model::ArbCoinPrice> {
-    let coin_price_list = sqlx::query_as::<_, model::ArbCoinPrice>(
+    let coin_price_list = db::query_as::<model::ArbCoinPrice>(
         "SELECT * FROM arb_coin_price WHERE symbol = ?",
     ).bind(symbol)
-        .fetch_one(db::get_db()?.database())
+        .fetch_one()
         .await?;
     Ok(coin_price_list)
 }
@@ -29,18 +29,18 @@ pub async fn update_arb_coin_price_by_id(
     updated: String,
 ) -> anyhow::Result<u64> {
     let query = format!("UPDATE arb_coin_price SET {} = ?, updated = ? WHERE id = ?", price_field);
-    let rows = sqlx::query(&query)
+    let rows = db::query(query)
         .bind(price)
         .bind(updated)
         .bind(id)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .rows_affected();
     Ok(rows)
 }
 
 pub async fn insert_arb_coin_price(coin_price: model::ArbCoinPrice) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query("INSERT INTO arb_coin_price (platform, symbol, binance_spot_price, binance_futures_price, bybit_futures_price, kucoin_futures_price, created, updated) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+    let last_insert_id = db::query("INSERT INTO arb_coin_price (platform, symbol, binance_spot_price, binance_futures_price, bybit_futures_price, kucoin_futures_price, created, updated) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
         .bind(coin_price.platform)
         .bind(coin_price.symbol)
         .bind(coin_price.binance_spot_price)
@@ -49,14 +49,14 @@ pub async fn insert_arb_coin_price(coin_price: model::ArbCoinPrice) -> anyhow::R
         .bind(coin_price.kucoin_futures_price)
         .bind(coin_price.created)
         .bind(coin_price.updated)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .last_insert_id();
     Ok(last_insert_id)
 }
 
 pub async fn insert_arb_diff_signal(diff_signal: model::ArbDiffSignal) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query("INSERT INTO arb_diff_signal (symbol, from_compare, to_compare, price_diff, price_diff_rate, binance_futures_price, bybit_futures_price, kucoin_futures_price, created, updated) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+    let last_insert_id = db::query("INSERT INTO arb_diff_signal (symbol, from_compare, to_compare, price_diff, price_diff_rate, binance_futures_price, bybit_futures_price, kucoin_futures_price, created, updated) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
         .bind(diff_signal.symbol)
         .bind(diff_signal.from_compare)
         .bind(diff_signal.to_compare)
@@ -67,7 +67,7 @@ pub async fn insert_arb_diff_signal(diff_signal: model::ArbDiffSignal) -> anyhow
         .bind(diff_signal.kucoin_futures_price)
         .bind(diff_signal.created)
         .bind(diff_signal.updated)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .last_insert_id();
     Ok(last_insert_id)
@@ -78,13 +78,13 @@ pub async fn get_arb_diff_signal_by_symbol_from_and_to_compare(
     from_compare: &String,
     to_compare: &String,
 ) -> anyhow::Result<model::ArbDiffSignal> {
-    let diff_signal = sqlx::query_as::<_, model::ArbDiffSignal>(
+    let diff_signal = db::query_as::<model::ArbDiffSignal>(
         "SELECT * FROM arb_diff_signal WHERE symbol = ? AND from_compare = ? AND to_compare = ?",
     )
         .bind(symbol)
         .bind(from_compare)
         .bind(to_compare)
-        .fetch_one(db::get_db()?.database())
+        .fetch_one()
         .await?;
     Ok(diff_signal)
 }
@@ -98,7 +98,7 @@ pub async fn update_arb_diff_signal_by_id(
     kucoin_futures_price: Decimal,
     updated: String,
 ) -> anyhow::Result<u64> {
-    let rows = sqlx::query("UPDATE arb_diff_signal SET price_diff = ?, price_diff_rate = ?, binance_futures_price = ?, bybit_futures_price = ?, kucoin_futures_price = ?, updated = ? WHERE id = ?")
+    let rows = db::query("UPDATE arb_diff_signal SET price_diff = ?, price_diff_rate = ?, binance_futures_price = ?, bybit_futures_price = ?, kucoin_futures_price = ?, updated = ? WHERE id = ?")
         .bind(price_diff)
         .bind(price_diff_rate)
         .bind(binance_futures_price)
@@ -106,8 +106,18 @@ pub async fn update_arb_diff_signal_by_id(
         .bind(kucoin_futures_price)
         .bind(updated)
         .bind(id)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .rows_affected();
     Ok(rows)
 }
+
+pub async fn get_arb_diff_signal_list(limit: u32) -> anyhow::Result<Vec<model::ArbDiffSignal>> {
+    let diff_signal_list = db::query_as::<model::ArbDiffSignal>(
+        "SELECT * FROM arb_diff_signal ORDER BY price_diff_rate + 0 DESC LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all()
+    .await?;
+    Ok(diff_signal_list)
+}
@@ -1,4 +1,5 @@
 pub mod diff_rate;
+mod pnl;
 mod stable_coin;
 pub mod strategy;
 mod diff_signal;
@@ -10,19 +11,50 @@ pub use diff_signal::update_arb_coin_price_by_id;
 pub use diff_signal::get_all_arb_coin_prices;
 pub use diff_signal::get_arb_coin_price_by_symbol;
 pub use diff_signal::get_arb_diff_signal_by_symbol_from_and_to_compare;
+pub use diff_signal::get_arb_diff_signal_list;
+pub use diff_rate::get_arb_diff_rate_by_id;
+pub use diff_rate::get_arb_diff_rate_his_before;
+pub use diff_rate::get_arb_diff_rate_his_list_by_created;
+pub use diff_rate::get_arb_diff_rate_his_list_by_diff_rate_id;
+pub use diff_rate::get_arb_diff_rate_sample_list;
 pub use diff_rate::get_arb_diff_rate_info_by_diff_rate_id;
+pub use diff_rate::get_arb_diff_rate_info_list;
+pub use diff_rate::get_arb_diff_rate_list;
 pub use diff_rate::get_arb_diff_rate_list_by_diff_status;
 pub use diff_rate::insert_arb_diff_rate_his;
+pub use diff_rate::insert_arb_diff_rate_sample;
 pub use diff_rate::insert_arb_diff_rate_info;
 pub use diff_rate::update_arb_diff_rate_info_by_id;
+pub use pnl::get_arb_strategy_pnl_by_strategy_id;
+pub use pnl::get_arb_strategy_pnl_list;
+pub use pnl::save_arb_strategy_pnl;
+pub use stable_coin::get_arb_stable_coin_by_id;
+pub use stable_coin::get_arb_stable_coin_info_list_by_created;
 pub use stable_coin::get_arb_stable_coin_info_list_by_stable_coin_id;
+pub use stable_coin::get_arb_stable_coin_list;
 pub use stable_coin::get_arb_stable_coin_list_by_doing_status;
+pub use stable_coin::insert_arb_stable_coin;
 pub use stable_coin::insert_arb_stable_coin_info;
+pub use stable_coin::update_stable_coin_by_id;
+pub use strategy::get_arb_strategy_by_id;
+pub use strategy::get_arb_strategy_count_by_doing_status;
+pub use strategy::get_arb_strategy_ex_by_id;
 pub use strategy::get_arb_strategy_ex_info_by_order_id;
+pub use strategy::get_arb_strategy_ex_info_list_by_created;
+pub use strategy::get_arb_strategy_ex_info_list_by_symbol;
+pub use strategy::get_arb_strategy_ex_info_list_by_strategy_id;
 pub use strategy::get_arb_strategy_ex_list_by_strategy_id;
+pub use strategy::get_arb_strategy_list;
 pub use strategy::get_arb_strategy_list_by_doing_status;
+pub use strategy::insert_arb_strategy;
 pub use strategy::insert_arb_strategy_ex;
 pub use strategy::insert_arb_strategy_ex_info;
 pub use strategy::update_strategy_by_id;
-pub use strategy::update_strategy_ex_by_id;
-pub use strategy::update_strategy_ex_info_by_id;
+pub use strategy::update_strategy_manual_by_id;
+pub use strategy::update_strategy_ex_manual_by_id;
+pub use strategy::leg_cancelled;
+pub use strategy::leg_unwound;
+pub use strategy::leg_expired;
+pub use strategy::leg_filled;
+pub use strategy::leg_order_placed;
+pub use strategy::leg_transferred;
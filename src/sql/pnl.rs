@@ -0,0 +1,47 @@
+use crate::{db, model};
+
+pub async fn get_arb_strategy_pnl_by_strategy_id(
+    strategy_id: i64,
+) -> anyhow::Result<Option<model::ArbStrategyPnl>> {
+    let list = db::query_as::<model::ArbStrategyPnl>(
+        "select * from arb_strategy_pnl where arb_strategy_id = ?",
+    )
+    .bind(strategy_id)
+    .fetch_all()
+    .await?;
+    Ok(list.into_iter().next())
+}
+
+pub async fn get_arb_strategy_pnl_list() -> anyhow::Result<Vec<model::ArbStrategyPnl>> {
+    let list = db::query_as::<model::ArbStrategyPnl>("select * from arb_strategy_pnl")
+        .fetch_all()
+        .await?;
+    Ok(list)
+}
+
+/// 每个策略一条, 已存在时替换
+pub async fn save_arb_strategy_pnl(pnl: model::ArbStrategyPnl) -> anyhow::Result<u64> {
+    let mut tx = db::begin().await?;
+    db::query("delete from arb_strategy_pnl where arb_strategy_id = ?")
+        .bind(pnl.arb_strategy_id)
+        .execute_in(&mut tx)
+        .await?;
+    let last_insert_id = db::query("insert into arb_strategy_pnl (arb_strategy_id, user_id, coin, realized_usdt, unrealized_usdt, realized_coin, unrealized_coin, fee_usdt, funding_usdt, coin_price, created, updated) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+        .bind(pnl.arb_strategy_id)
+        .bind(pnl.user_id)
+        .bind(pnl.coin)
+        .bind(pnl.realized_usdt)
+        .bind(pnl.unrealized_usdt)
+        .bind(pnl.realized_coin)
+        .bind(pnl.unrealized_coin)
+        .bind(pnl.fee_usdt)
+        .bind(pnl.funding_usdt)
+        .bind(pnl.coin_price)
+        .bind(pnl.created)
+        .bind(pnl.updated)
+        .execute_in(&mut tx)
+        .await?
+        .last_insert_id();
+    tx.commit().await?;
+    Ok(last_insert_id)
+}
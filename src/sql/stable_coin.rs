@@ -1,13 +1,14 @@
+use chrono::Local;
 use crate::{db, model};
 
 pub async fn get_arb_stable_coin_list_by_doing_status(
     doing_status: i8,
 ) -> anyhow::Result<Vec<model::ArbStableCoin>> {
-    let stable_coin_list = sqlx::query_as::<_, model::ArbStableCoin>(
+    let stable_coin_list = db::query_as::<model::ArbStableCoin>(
         "select * from arb_stable_coin where doing_status = ?",
     )
     .bind(doing_status)
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(stable_coin_list)
 }
@@ -16,18 +17,18 @@ pub async fn get_arb_stable_coin_info_list_by_stable_coin_id(
     stable_coin_id: i64,
     limit: u32,
 ) -> anyhow::Result<Vec<model::ArbStableCoinInfo>> {
-    let info_list = sqlx::query_as::<_, model::ArbStableCoinInfo>(
+    let info_list = db::query_as::<model::ArbStableCoinInfo>(
         "select * from arb_stable_coin_info where stable_coin_id = ? order by id desc limit ?",
     )
     .bind(stable_coin_id)
     .bind(limit)
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(info_list)
 }
 
 pub async fn insert_arb_stable_coin_info(info: model::ArbStableCoinInfo) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query(
+    let last_insert_id = db::query(
         "insert into arb_stable_coin_info (stable_coin_id, user_id, platform, coin, market, symbol, option_type, price, amount, order_id, is_ok, created) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
         .bind(info.stable_coin_id)
         .bind(info.user_id)
@@ -41,8 +42,77 @@ pub async fn insert_arb_stable_coin_info(info: model::ArbStableCoinInfo) -> anyh
         .bind(info.order_id)
         .bind(info.is_ok)
         .bind(info.created)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .last_insert_id();
     Ok(last_insert_id)
 }
+
+pub async fn get_arb_stable_coin_list() -> anyhow::Result<Vec<model::ArbStableCoin>> {
+    let stable_coin_list =
+        db::query_as::<model::ArbStableCoin>("select * from arb_stable_coin")
+            .fetch_all()
+            .await?;
+    Ok(stable_coin_list)
+}
+
+pub async fn get_arb_stable_coin_by_id(id: i64) -> anyhow::Result<model::ArbStableCoin> {
+    let stable_coin =
+        db::query_as::<model::ArbStableCoin>("select * from arb_stable_coin where id = ?")
+            .bind(id)
+            .fetch_one()
+            .await?;
+    Ok(stable_coin)
+}
+
+pub async fn insert_arb_stable_coin(s: model::ArbStableCoin) -> anyhow::Result<u64> {
+    let last_insert_id = db::query(
+        "insert into arb_stable_coin (user_id, platform, coin, market, symbol, price_truncate, amt_truncate, strategy, signal_config, option_open, option_close, option_amt, fok_diff, doing_status, created, updated, bak) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+        .bind(s.user_id)
+        .bind(s.platform)
+        .bind(s.coin)
+        .bind(s.market)
+        .bind(s.symbol)
+        .bind(s.price_truncate)
+        .bind(s.amt_truncate)
+        .bind(s.strategy)
+        .bind(s.signal_config)
+        .bind(s.option_open)
+        .bind(s.option_close)
+        .bind(s.option_amt)
+        .bind(s.fok_diff)
+        .bind(s.doing_status)
+        .bind(s.created)
+        .bind(s.updated)
+        .bind(s.bak)
+        .execute()
+        .await?
+        .last_insert_id();
+    Ok(last_insert_id)
+}
+
+pub async fn update_stable_coin_by_id(id: i64, doing_status: i8) -> anyhow::Result<u64> {
+    let rows = db::query("update arb_stable_coin set doing_status = ?, updated = ? where id = ?")
+        .bind(doing_status)
+        .bind(Local::now().timestamp())
+        .bind(id)
+        .execute()
+        .await?
+        .rows_affected();
+    Ok(rows)
+}
+
+/// created 在 [start, end) 内的下单记录
+pub async fn get_arb_stable_coin_info_list_by_created(
+    start: i64,
+    end: i64,
+) -> anyhow::Result<Vec<model::ArbStableCoinInfo>> {
+    let info_list = db::query_as::<model::ArbStableCoinInfo>(
+        "select * from arb_stable_coin_info where created >= ? and created < ? order by id",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all()
+    .await?;
+    Ok(info_list)
+}
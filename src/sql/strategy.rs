@@ -1,57 +1,141 @@
 use crate::{db, model};
 use chrono::Local;
-use std::collections::HashMap;
+use rust_decimal::Decimal;
+use serde::Deserialize;
 
 pub async fn update_strategy_by_id(id: i64, doing_status: i8) -> anyhow::Result<u64> {
-    let rows = sqlx::query("update arb_strategy set doing_status = ?, updated = ? where id = ?")
+    let rows = db::query("update arb_strategy set doing_status = ?, updated = ? where id = ?")
         .bind(doing_status)
         .bind(Local::now().timestamp())
         .bind(id)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .rows_affected();
     Ok(rows)
 }
 
-pub async fn update_strategy_ex_by_id(
-    id: i64,
-    map: HashMap<String, String>,
-) -> anyhow::Result<u64> {
-    let sql_pre: String = map
-        .iter()
-        .map(|(key, value)| format!("{}={}", key, value))
-        .collect::<Vec<String>>()
-        .join(", ");
-    let sql = format!(
-        "update arb_strategy_ex set {}, updated = ? where id = ?",
-        sql_pre
-    );
-    let rows = sqlx::query(&sql)
+// leg 状态变更, arb_strategy_ex 与 arb_strategy_ex_info 在同一事务中更新
+
+/// 下单成功: 记录当前订单ID, 插入未完成明细
+pub async fn leg_order_placed(ex_id: i64, info: model::ArbStrategyExInfo) -> anyhow::Result<u64> {
+    let mut tx = db::begin().await?;
+    db::query("update arb_strategy_ex set current_order_id = ?, updated = ? where id = ?")
+        .bind(&info.order_id)
         .bind(Local::now().timestamp())
-        .bind(id)
-        .execute(db::get_db()?.database())
-        .await?
-        .rows_affected();
-    Ok(rows)
+        .bind(ex_id)
+        .execute_in(&mut tx)
+        .await?;
+    let info_id = insert_ex_info(info).execute_in(&mut tx).await?.last_insert_id();
+    tx.commit().await?;
+    Ok(info_id)
 }
 
-pub async fn update_strategy_ex_info_by_id(
-    id: i64,
-    map: HashMap<String, String>,
+/// 订单全部成交: leg 完成, 明细完成
+pub async fn leg_filled(ex_id: i64, ex_info_id: i64, executed_amt: Decimal) -> anyhow::Result<()> {
+    let now = Local::now().timestamp();
+    let mut tx = db::begin().await?;
+    db::query("update arb_strategy_ex set option_amount = ?, option_executed_amt = ?, option_status = ?, updated = ? where id = ?")
+        .bind(executed_amt)
+        .bind(executed_amt)
+        .bind(model::arb_strategy_ex::OPTION_STATUS_DONE)
+        .bind(now)
+        .bind(ex_id)
+        .execute_in(&mut tx)
+        .await?;
+    db::query("update arb_strategy_ex_info set executed_amt = ?, is_ok = ?, updated = ? where id = ?")
+        .bind(executed_amt)
+        .bind(model::arb_strategy_ex_info::IS_OK_DONE)
+        .bind(now)
+        .bind(ex_info_id)
+        .execute_in(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 订单未成交: 清空当前订单ID等待重新下单, 明细过期
+pub async fn leg_expired(ex_id: i64, ex_info_id: i64) -> anyhow::Result<()> {
+    let now = Local::now().timestamp();
+    let mut tx = db::begin().await?;
+    db::query("update arb_strategy_ex set current_order_id = '', updated = ? where id = ?")
+        .bind(now)
+        .bind(ex_id)
+        .execute_in(&mut tx)
+        .await?;
+    set_ex_info_status(&mut tx, ex_info_id, model::arb_strategy_ex_info::IS_OK_EXPIRED).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 划转成功: 插入已完成明细, leg 完成
+pub async fn leg_transferred(ex_id: i64, info: model::ArbStrategyExInfo) -> anyhow::Result<u64> {
+    let mut tx = db::begin().await?;
+    db::query("update arb_strategy_ex set current_order_id = ?, option_status = ?, option_amount = ?, option_executed_amt = ?, updated = ? where id = ?")
+        .bind(&info.order_id)
+        .bind(model::arb_strategy_ex::OPTION_STATUS_DONE)
+        .bind(info.executed_amt)
+        .bind(info.executed_amt)
+        .bind(Local::now().timestamp())
+        .bind(ex_id)
+        .execute_in(&mut tx)
+        .await?;
+    let info_id = insert_ex_info(info).execute_in(&mut tx).await?.last_insert_id();
+    tx.commit().await?;
+    Ok(info_id)
+}
+
+/// 紧急平仓下单或划转: 插入已完成明细, 关联 leg 时 leg 完成并记录成交数量
+pub async fn leg_unwound(
+    ex_id: Option<i64>,
+    info: model::ArbStrategyExInfo,
+    bak: &str,
 ) -> anyhow::Result<u64> {
-    let sql_pre: String = map
-        .iter()
-        .map(|(key, value)| format!("{}={}", key, value))
-        .collect::<Vec<String>>()
-        .join(", ");
-    let sql = format!(
-        "update arb_strategy_ex_info set {}, updated = ? where id = ?",
-        sql_pre
-    );
-    let rows = sqlx::query(&sql)
+    let mut tx = db::begin().await?;
+    if let Some(ex_id) = ex_id {
+        db::query("update arb_strategy_ex set current_order_id = ?, option_status = ?, option_executed_amt = ?, bak = ?, updated = ? where id = ?")
+            .bind(&info.order_id)
+            .bind(model::arb_strategy_ex::OPTION_STATUS_DONE)
+            .bind(info.executed_amt)
+            .bind(bak)
+            .bind(Local::now().timestamp())
+            .bind(ex_id)
+            .execute_in(&mut tx)
+            .await?;
+    }
+    let info_id = insert_ex_info(info).execute_in(&mut tx).await?.last_insert_id();
+    tx.commit().await?;
+    Ok(info_id)
+}
+
+/// 人工撤单: 明细过期, 记录 leg 状态及备注
+pub async fn leg_cancelled(
+    ex_id: i64,
+    ex_info_id: Option<i64>,
+    option_status: i8,
+    bak: &str,
+) -> anyhow::Result<()> {
+    let mut tx = db::begin().await?;
+    if let Some(ex_info_id) = ex_info_id {
+        set_ex_info_status(&mut tx, ex_info_id, model::arb_strategy_ex_info::IS_OK_EXPIRED)
+            .await?;
+    }
+    db::query("update arb_strategy_ex set option_status = ?, current_order_id = '', bak = ?, updated = ? where id = ?")
+        .bind(option_status)
+        .bind(bak)
+        .bind(Local::now().timestamp())
+        .bind(ex_id)
+        .execute_in(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn set_ex_info_status(tx: &mut db::Tx, id: i64, is_ok: i8) -> anyhow::Result<u64> {
+    let rows = db::query("update arb_strategy_ex_info set is_ok = ?, updated = ? where id = ?")
+        .bind(is_ok)
         .bind(Local::now().timestamp())
         .bind(id)
-        .execute(db::get_db()?.database())
+        .execute_in(tx)
         .await?
         .rows_affected();
     Ok(rows)
@@ -60,11 +144,11 @@ pub async fn update_strategy_ex_info_by_id(
 pub async fn get_arb_strategy_list_by_doing_status(
     doing_status: i8,
 ) -> anyhow::Result<Vec<model::ArbStrategy>> {
-    let strategy_list = sqlx::query_as::<_, model::ArbStrategy>(
+    let strategy_list = db::query_as::<model::ArbStrategy>(
         "select * from arb_strategy where doing_status = ?",
     )
     .bind(doing_status)
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(strategy_list)
 }
@@ -72,11 +156,11 @@ pub async fn get_arb_strategy_list_by_doing_status(
 pub async fn get_arb_strategy_ex_list_by_strategy_id(
     strategy_id: i64,
 ) -> anyhow::Result<Vec<model::ArbStrategyEx>> {
-    let strategy_ex_list = sqlx::query_as::<_, model::ArbStrategyEx>(
-        "select * from arb_strategy_ex where arb_strategy_id = ?",
+    let strategy_ex_list = db::query_as::<model::ArbStrategyEx>(
+        "select * from arb_strategy_ex where arb_strategy_id = ? order by id",
     )
     .bind(strategy_id)
-    .fetch_all(db::get_db()?.database())
+    .fetch_all()
     .await?;
     Ok(strategy_ex_list)
 }
@@ -84,17 +168,17 @@ pub async fn get_arb_strategy_ex_list_by_strategy_id(
 pub async fn get_arb_strategy_ex_info_by_order_id(
     order_id: String,
 ) -> anyhow::Result<model::ArbStrategyExInfo> {
-    let ex_info = sqlx::query_as::<_, model::ArbStrategyExInfo>(
+    let ex_info = db::query_as::<model::ArbStrategyExInfo>(
         "select * from arb_strategy_ex_info where order_id = ?",
     )
     .bind(order_id)
-    .fetch_one(db::get_db()?.database())
+    .fetch_one()
     .await?;
     Ok(ex_info)
 }
 
 pub async fn insert_arb_strategy_ex(ex: model::ArbStrategyEx) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query("insert into arb_strategy_ex (user_id, platform, option_choose, arb_strategy_id, coin, market, symbol, option_type, option_status, option_amount, option_executed_amt, current_order_id, created, updated) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+    let last_insert_id = db::query("insert into arb_strategy_ex (user_id, platform, option_choose, arb_strategy_id, coin, market, symbol, option_type, option_status, option_amount, option_executed_amt, current_order_id, created, updated) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
         .bind(ex.user_id)
         .bind(ex.platform)
         .bind(ex.option_choose)
@@ -109,15 +193,20 @@ pub async fn insert_arb_strategy_ex(ex: model::ArbStrategyEx) -> anyhow::Result<
         .bind(ex.current_order_id)
         .bind(ex.created)
         .bind(ex.updated)
-        .execute(db::get_db()?.database())
+        .execute()
         .await?
         .last_insert_id();
     Ok(last_insert_id)
 }
 
 pub async fn insert_arb_strategy_ex_info(ex: model::ArbStrategyExInfo) -> anyhow::Result<u64> {
-    let last_insert_id = sqlx::query(
-        "insert into arb_strategy_ex_info (user_id, platform, option_choose, arb_strategy_id, arb_strategy_ex_id, coin, market, symbol, option_type, price, amount, executed_amt, order_id, is_ok, created) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    let last_insert_id = insert_ex_info(ex).execute().await?.last_insert_id();
+    Ok(last_insert_id)
+}
+
+fn insert_ex_info(ex: model::ArbStrategyExInfo) -> db::Query {
+    db::query(
+        "insert into arb_strategy_ex_info (user_id, platform, option_choose, arb_strategy_id, arb_strategy_ex_id, coin, market, symbol, option_type, price, amount, executed_amt, order_id, is_ok, created, updated) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
     )
         .bind(ex.user_id)
         .bind(ex.platform)
@@ -134,8 +223,160 @@ pub async fn insert_arb_strategy_ex_info(ex: model::ArbStrategyExInfo) -> anyhow
         .bind(ex.order_id)
         .bind(ex.is_ok)
         .bind(ex.created)
-        .execute(db::get_db()?.database())
+        .bind(ex.updated)
+}
+
+pub async fn get_arb_strategy_list() -> anyhow::Result<Vec<model::ArbStrategy>> {
+    let strategy_list = db::query_as::<model::ArbStrategy>("select * from arb_strategy")
+        .fetch_all()
+        .await?;
+    Ok(strategy_list)
+}
+
+pub async fn get_arb_strategy_by_id(id: i64) -> anyhow::Result<model::ArbStrategy> {
+    let strategy =
+        db::query_as::<model::ArbStrategy>("select * from arb_strategy where id = ?")
+            .bind(id)
+            .fetch_one()
+            .await?;
+    Ok(strategy)
+}
+
+pub async fn insert_arb_strategy(s: model::ArbStrategy) -> anyhow::Result<u64> {
+    let last_insert_id = db::query("insert into arb_strategy (diff_rate_id, user_id, platform, option_choose, coin, from_market, from_symbol, from_price_truncate, from_amt_truncate, to_market, to_symbol, to_price_truncate, to_amt_truncate, from_to_desc, to_from_desc, option_open, option_close, signal_mode, lookback, option_amt, contract_mul, margin_mul, fok_diff, spot_fee, futures_fee, delivery_fee, doing_status, created, updated, bak) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+        .bind(s.diff_rate_id)
+        .bind(s.user_id)
+        .bind(s.platform)
+        .bind(s.option_choose)
+        .bind(s.coin)
+        .bind(s.from_market)
+        .bind(s.from_symbol)
+        .bind(s.from_price_truncate)
+        .bind(s.from_amt_truncate)
+        .bind(s.to_market)
+        .bind(s.to_symbol)
+        .bind(s.to_price_truncate)
+        .bind(s.to_amt_truncate)
+        .bind(s.from_to_desc)
+        .bind(s.to_from_desc)
+        .bind(s.option_open)
+        .bind(s.option_close)
+        .bind(s.signal_mode)
+        .bind(s.lookback)
+        .bind(s.option_amt)
+        .bind(s.contract_mul)
+        .bind(s.margin_mul)
+        .bind(s.fok_diff)
+        .bind(s.spot_fee)
+        .bind(s.futures_fee)
+        .bind(s.delivery_fee)
+        .bind(s.doing_status)
+        .bind(s.created)
+        .bind(s.updated)
+        .bind(s.bak)
+        .execute()
         .await?
         .last_insert_id();
     Ok(last_insert_id)
 }
+
+pub async fn get_arb_strategy_ex_info_list_by_strategy_id(
+    strategy_id: i64,
+) -> anyhow::Result<Vec<model::ArbStrategyExInfo>> {
+    let ex_info_list = db::query_as::<model::ArbStrategyExInfo>(
+        "select * from arb_strategy_ex_info where arb_strategy_id = ? order by id",
+    )
+    .bind(strategy_id)
+    .fetch_all()
+    .await?;
+    Ok(ex_info_list)
+}
+
+/// created 在 [start, end) 内的执行明细
+pub async fn get_arb_strategy_ex_info_list_by_created(
+    start: i64,
+    end: i64,
+) -> anyhow::Result<Vec<model::ArbStrategyExInfo>> {
+    let ex_info_list = db::query_as::<model::ArbStrategyExInfo>(
+        "select * from arb_strategy_ex_info where created >= ? and created < ? order by id",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all()
+    .await?;
+    Ok(ex_info_list)
+}
+
+/// 账户在某交易对上有成交的执行明细, 用于按持仓分摊资金费
+pub async fn get_arb_strategy_ex_info_list_by_symbol(
+    user_id: i64,
+    market: &str,
+    symbol: &str,
+) -> anyhow::Result<Vec<model::ArbStrategyExInfo>> {
+    let ex_info_list = db::query_as::<model::ArbStrategyExInfo>(
+        "select * from arb_strategy_ex_info where user_id = ? and market = ? and symbol = ? and executed_amt > 0 order by id",
+    )
+    .bind(user_id)
+    .bind(market)
+    .bind(symbol)
+    .fetch_all()
+    .await?;
+    Ok(ex_info_list)
+}
+
+pub async fn get_arb_strategy_ex_by_id(id: i64) -> anyhow::Result<model::ArbStrategyEx> {
+    let ex = db::query_as::<model::ArbStrategyEx>("select * from arb_strategy_ex where id = ?")
+        .bind(id)
+        .fetch_one()
+        .await?;
+    Ok(ex)
+}
+
+// 人工干预, bak 记录操作原因
+pub async fn update_strategy_ex_manual_by_id(
+    id: i64,
+    option_status: i8,
+    current_order_id: &str,
+    bak: &str,
+) -> anyhow::Result<u64> {
+    let rows = db::query("update arb_strategy_ex set option_status = ?, current_order_id = ?, bak = ?, updated = ? where id = ?")
+        .bind(option_status)
+        .bind(current_order_id)
+        .bind(bak)
+        .bind(Local::now().timestamp())
+        .bind(id)
+        .execute()
+        .await?
+        .rows_affected();
+    Ok(rows)
+}
+
+pub async fn update_strategy_manual_by_id(
+    id: i64,
+    doing_status: i8,
+    bak: &str,
+) -> anyhow::Result<u64> {
+    let rows = db::query("update arb_strategy set doing_status = ?, bak = ?, updated = ? where id = ?")
+        .bind(doing_status)
+        .bind(bak)
+        .bind(Local::now().timestamp())
+        .bind(id)
+        .execute()
+        .await?
+        .rows_affected();
+    Ok(rows)
+}
+
+pub async fn get_arb_strategy_count_by_doing_status() -> anyhow::Result<Vec<(i8, i64)>> {
+    #[derive(sqlx::FromRow, Deserialize)]
+    struct Count {
+        doing_status: i8,
+        count: i64,
+    }
+    let counts = db::query_as::<Count>(
+        "select doing_status, count(*) as count from arb_strategy group by doing_status",
+    )
+    .fetch_all()
+    .await?;
+    Ok(counts.into_iter().map(|c| (c.doing_status, c.count)).collect())
+}